@@ -0,0 +1,181 @@
+package bisocm.swiftbot.lib;
+
+import java.util.Arrays;
+
+/**
+ * An image control of the camera, such as exposure, gain, white balance,
+ * brightness or flipping, with its current value and the values it accepts.
+ *
+ * Controls are looked up by key, which is the same for every camera (for
+ * example "exposure_auto" or "white_balance_temperature"), or by the name
+ * the driver reports.
+ */
+public final class CameraControl {
+
+    /**
+     * Kind of value a control takes. The order matches the type IDs used by
+     * the Rust library.
+     */
+    public enum Type {
+        /** A number from the minimum to the maximum, in steps of the step size. */
+        INTEGER,
+        /** 0 (off) or 1 (on). */
+        BOOLEAN,
+        /** The index of one of the menu items. */
+        MENU,
+        /** Performs an action when set, with any value. */
+        BUTTON,
+        /** Cannot be read or set as a number. */
+        OTHER
+    }
+
+    private final int id;
+    private final String key;
+    private final String name;
+    private final Type type;
+    private final long value;
+    private final long defaultValue;
+    private final long minimum;
+    private final long maximum;
+    private final long step;
+    private final int[] menuIndices;
+    private final String[] menuNames;
+    private final boolean readOnly;
+    private final boolean inactive;
+
+    //Called from Rust
+    CameraControl(int id, String key, String name, int type, long value, long defaultValue,
+                  long minimum, long maximum, long step, int[] menuIndices, String[] menuNames,
+                  boolean readOnly, boolean inactive) {
+        this.id = id;
+        this.key = key;
+        this.name = name;
+        this.type = Type.values()[type];
+        this.value = value;
+        this.defaultValue = defaultValue;
+        this.minimum = minimum;
+        this.maximum = maximum;
+        this.step = step;
+        this.menuIndices = menuIndices;
+        this.menuNames = menuNames;
+        this.readOnly = readOnly;
+        this.inactive = inactive;
+    }
+
+    /**
+     * @return The V4L2 control ID.
+     */
+    public int getId() {
+        return id;
+    }
+
+    /**
+     * @return The key used to look the control up, e.g. "exposure_auto".
+     */
+    public String getKey() {
+        return key;
+    }
+
+    /**
+     * @return The name reported by the driver, e.g. "Auto Exposure".
+     */
+    public String getName() {
+        return name;
+    }
+
+    /**
+     * @return The kind of value the control takes.
+     */
+    public Type getType() {
+        return type;
+    }
+
+    /**
+     * @return The current value.
+     */
+    public long getValue() {
+        return value;
+    }
+
+    /**
+     * @return The value the camera starts with.
+     */
+    public long getDefaultValue() {
+        return defaultValue;
+    }
+
+    /**
+     * @return The smallest accepted value.
+     */
+    public long getMinimum() {
+        return minimum;
+    }
+
+    /**
+     * @return The largest accepted value.
+     */
+    public long getMaximum() {
+        return maximum;
+    }
+
+    /**
+     * @return The step between accepted values, counted from the minimum.
+     */
+    public long getStep() {
+        return step;
+    }
+
+    /**
+     * @return The indices of the menu items, for a MENU control. These are
+     *         the accepted values, and may have gaps.
+     */
+    public int[] getMenuIndices() {
+        return menuIndices.clone();
+    }
+
+    /**
+     * @return The names of the menu items, in the same order as
+     *         {@link #getMenuIndices()}.
+     */
+    public String[] getMenuNames() {
+        return menuNames.clone();
+    }
+
+    /**
+     * @param index A menu item index.
+     * @return The name of the menu item, or null if there is no such item.
+     */
+    public String getMenuName(long index) {
+        for (int i = 0; i < menuIndices.length; i++) {
+            if (menuIndices[i] == index) {
+                return menuNames[i];
+            }
+        }
+        return null;
+    }
+
+    /**
+     * @return `true` if the control can only be read.
+     */
+    public boolean isReadOnly() {
+        return readOnly;
+    }
+
+    /**
+     * @return `true` if the control currently has no effect, for example
+     *         manual exposure while automatic exposure is on.
+     */
+    public boolean isInactive() {
+        return inactive;
+    }
+
+    @Override
+    public String toString() {
+        String text = "CameraControl{key=" + key + ", name=" + name + ", type=" + type + ", value=" + value
+                + ", default=" + defaultValue + ", minimum=" + minimum + ", maximum=" + maximum + ", step=" + step;
+        if (type == Type.MENU) {
+            text += ", menu=" + Arrays.toString(menuNames);
+        }
+        return text + ", readOnly=" + readOnly + ", inactive=" + inactive + "}";
+    }
+}
@@ -0,0 +1,46 @@
+package bisocm.swiftbot.lib;
+
+/**
+ * A camera attached to the robot and the capture modes it supports.
+ */
+public final class CameraDevice {
+    private final String path;
+    private final String name;
+    private final CameraMode[] modes;
+
+    //Called from Rust
+    CameraDevice(String path, String name, CameraMode[] modes) {
+        this.path = path;
+        this.name = name;
+        this.modes = modes;
+    }
+
+    /**
+     * @return The device path, e.g. "/dev/video0".
+     */
+    public String getPath() {
+        return path;
+    }
+
+    /**
+     * @return The name reported by the driver, or an empty string if
+     *         unknown.
+     */
+    public String getName() {
+        return name;
+    }
+
+    /**
+     * @return Every supported format, resolution and frame interval
+     *         combination. Continuous ranges are listed by their smallest
+     *         and largest values.
+     */
+    public CameraMode[] getModes() {
+        return modes.clone();
+    }
+
+    @Override
+    public String toString() {
+        return "CameraDevice{path=" + path + ", name=" + name + ", modes=" + modes.length + "}";
+    }
+}
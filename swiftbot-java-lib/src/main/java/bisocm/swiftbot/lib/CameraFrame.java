@@ -0,0 +1,73 @@
+package bisocm.swiftbot.lib;
+
+/**
+ * A frame captured from the camera.
+ */
+public final class CameraFrame {
+    private final long sequence;
+    private final long timestampUs;
+    private final int width;
+    private final int height;
+    private final String format;
+    private final byte[] data;
+
+    //Called from Rust
+    CameraFrame(long sequence, long timestampUs, int width, int height, String format, byte[] data) {
+        this.sequence = sequence;
+        this.timestampUs = timestampUs;
+        this.width = width;
+        this.height = height;
+        this.format = format;
+        this.data = data;
+    }
+
+    /**
+     * @return The frame number, increasing by one for each frame captured.
+     *         A gap means frames were dropped.
+     */
+    public long getSequence() {
+        return sequence;
+    }
+
+    /**
+     * @return The capture time in microseconds since the Unix epoch.
+     */
+    public long getTimestampUs() {
+        return timestampUs;
+    }
+
+    /**
+     * @return The frame width in pixels.
+     */
+    public int getWidth() {
+        return width;
+    }
+
+    /**
+     * @return The frame height in pixels.
+     */
+    public int getHeight() {
+        return height;
+    }
+
+    /**
+     * @return The FourCC pixel format of the data, e.g. "MJPG".
+     */
+    public String getFormat() {
+        return format;
+    }
+
+    /**
+     * @return The frame data, a JPEG image for the MJPG format.
+     */
+    public byte[] getData() {
+        return data;
+    }
+
+    @Override
+    public String toString() {
+        return "CameraFrame{sequence=" + sequence + ", timestampUs=" + timestampUs
+                + ", width=" + width + ", height=" + height + ", format=" + format
+                + ", length=" + data.length + "}";
+    }
+}
@@ -0,0 +1,80 @@
+package bisocm.swiftbot.lib;
+
+/**
+ * A capture mode supported by a camera: a pixel format, resolution and
+ * frame interval.
+ */
+public final class CameraMode {
+    private final String format;
+    private final String description;
+    private final int width;
+    private final int height;
+    private final int intervalNumerator;
+    private final int intervalDenominator;
+
+    //Called from Rust
+    CameraMode(String format, String description, int width, int height,
+               int intervalNumerator, int intervalDenominator) {
+        this.format = format;
+        this.description = description;
+        this.width = width;
+        this.height = height;
+        this.intervalNumerator = intervalNumerator;
+        this.intervalDenominator = intervalDenominator;
+    }
+
+    /**
+     * @return The FourCC pixel format, e.g. "MJPG" or "YUYV".
+     */
+    public String getFormat() {
+        return format;
+    }
+
+    /**
+     * @return The format description from the driver, e.g. "Motion-JPEG".
+     */
+    public String getDescription() {
+        return description;
+    }
+
+    /**
+     * @return The frame width in pixels.
+     */
+    public int getWidth() {
+        return width;
+    }
+
+    /**
+     * @return The frame height in pixels.
+     */
+    public int getHeight() {
+        return height;
+    }
+
+    /**
+     * @return The numerator of the time between frames, in seconds.
+     */
+    public int getIntervalNumerator() {
+        return intervalNumerator;
+    }
+
+    /**
+     * @return The denominator of the time between frames, in seconds.
+     */
+    public int getIntervalDenominator() {
+        return intervalDenominator;
+    }
+
+    /**
+     * @return The frame rate in frames per second.
+     */
+    public double getFps() {
+        return (double) intervalDenominator / intervalNumerator;
+    }
+
+    @Override
+    public String toString() {
+        return "CameraMode{format=" + format + ", width=" + width + ", height=" + height
+                + ", fps=" + getFps() + "}";
+    }
+}
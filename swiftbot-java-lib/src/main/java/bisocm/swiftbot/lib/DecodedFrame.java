@@ -0,0 +1,96 @@
+package bisocm.swiftbot.lib;
+
+/**
+ * A camera frame decoded to RGB or grayscale pixels.
+ */
+public final class DecodedFrame {
+    private final long sequence;
+    private final long timestampUs;
+    private final int width;
+    private final int height;
+    private final int stride;
+    private final int format;
+    private final byte[] pixels;
+
+    //Called from Rust
+    DecodedFrame(long sequence, long timestampUs, int width, int height, int stride, int format, byte[] pixels) {
+        this.sequence = sequence;
+        this.timestampUs = timestampUs;
+        this.width = width;
+        this.height = height;
+        this.stride = stride;
+        this.format = format;
+        this.pixels = pixels;
+    }
+
+    /**
+     * @return The sequence number of the frame this was decoded from, or 0
+     *         for a decoded image.
+     */
+    public long getSequence() {
+        return sequence;
+    }
+
+    /**
+     * @return The capture time in microseconds since the Unix epoch, or 0
+     *         for a decoded image.
+     */
+    public long getTimestampUs() {
+        return timestampUs;
+    }
+
+    /**
+     * @return The width in pixels, after any downscaling.
+     */
+    public int getWidth() {
+        return width;
+    }
+
+    /**
+     * @return The height in pixels, after any downscaling.
+     */
+    public int getHeight() {
+        return height;
+    }
+
+    /**
+     * @return The number of bytes from the start of one row to the next.
+     */
+    public int getStride() {
+        return stride;
+    }
+
+    /**
+     * @return The pixel format, SwiftBot.PIXEL_RGB or SwiftBot.PIXEL_GRAY.
+     */
+    public int getFormat() {
+        return format;
+    }
+
+    /**
+     * @return The pixels, top row first. RGB pixels are 3 bytes (red,
+     *         green, blue), grayscale pixels 1 byte.
+     */
+    public byte[] getPixels() {
+        return pixels;
+    }
+
+    /**
+     * Gets the gray level of a grayscale pixel, or the red component of an
+     * RGB pixel.
+     *
+     * @param x The column, from 0 at the left.
+     * @param y The row, from 0 at the top.
+     * @return The value, 0 to 255.
+     */
+    public int getValue(int x, int y) {
+        return pixels[y * stride + x * (format == SwiftBot.PIXEL_RGB ? 3 : 1)] & 0xFF;
+    }
+
+    @Override
+    public String toString() {
+        return "DecodedFrame{sequence=" + sequence + ", timestampUs=" + timestampUs
+                + ", width=" + width + ", height=" + height + ", stride=" + stride
+                + ", format=" + format + "}";
+    }
+}
@@ -0,0 +1,92 @@
+package bisocm.swiftbot.lib;
+
+/**
+ * A single measurement from the ultrasonic sensor.
+ *
+ * Distances outside the sensor's range are a normal condition (for example
+ * when nothing is in front of the robot), so they are reported through
+ * {@link #getStatus()} instead of as errors.
+ */
+public final class DistanceReading {
+
+    /**
+     * Outcome of a measurement. The order matches the status IDs used by
+     * the Rust library.
+     */
+    public enum Status {
+        /** An echo was received from an object within range. */
+        VALID,
+        /** An echo was received, but the object is closer than the minimum range. */
+        TOO_CLOSE,
+        /** Nothing reflected the pulse within the maximum range (open space). */
+        NO_ECHO,
+        /** The sensor did not answer in time, which usually means a wiring or hardware fault. */
+        TIMEOUT
+    }
+
+    private final double value;
+    private final int unit;
+    private final long timestampMillis;
+    private final Status status;
+    private final long pulseWidthMicros;
+
+    //Called from Rust
+    DistanceReading(double value, int unit, long timestampMillis, int status, long pulseWidthMicros) {
+        this.value = value;
+        this.unit = unit;
+        this.timestampMillis = timestampMillis;
+        this.status = Status.values()[status];
+        this.pulseWidthMicros = pulseWidthMicros;
+    }
+
+    /**
+     * @return The measured distance in the unit returned by {@link #getUnit()},
+     *         or NaN if no echo pulse was measured.
+     */
+    public double getValue() {
+        return value;
+    }
+
+    /**
+     * @return The unit of {@link #getValue()} (SwiftBot.UNIT_CENTIMETERS,
+     *         SwiftBot.UNIT_MILLIMETERS or SwiftBot.UNIT_INCHES).
+     */
+    public int getUnit() {
+        return unit;
+    }
+
+    /**
+     * @return The time at which the measurement was triggered, in milliseconds
+     *         since the Unix epoch.
+     */
+    public long getTimestampMillis() {
+        return timestampMillis;
+    }
+
+    /**
+     * @return Whether the value can be trusted, and if not, why.
+     */
+    public Status getStatus() {
+        return status;
+    }
+
+    /**
+     * @return The raw width of the echo pulse in microseconds.
+     */
+    public long getPulseWidthMicros() {
+        return pulseWidthMicros;
+    }
+
+    /**
+     * @return `true` if the reading holds a distance within the sensor's range.
+     */
+    public boolean isValid() {
+        return status == Status.VALID;
+    }
+
+    @Override
+    public String toString() {
+        return "DistanceReading{value=" + value + ", unit=" + unit + ", status=" + status
+                + ", pulseWidthMicros=" + pulseWidthMicros + ", timestampMillis=" + timestampMillis + "}";
+    }
+}
@@ -0,0 +1,103 @@
+package bisocm.swiftbot.lib;
+
+import java.nio.ByteBuffer;
+
+/**
+ * A reusable direct buffer that camera frames are copied into, so frames
+ * can be processed without allocating memory for each one. The buffer
+ * grows when a frame does not fit, and is then reused at the new size.
+ *
+ * A FrameBuffer is not thread safe; use one per consumer thread.
+ */
+public final class FrameBuffer {
+    private ByteBuffer buffer;
+    private final long[] metadata = new long[5];
+    private int length;
+
+    /**
+     * @param capacity The initial capacity in bytes, e.g. 256 KiB for
+     *                 640x480 MJPG frames.
+     */
+    public FrameBuffer(int capacity) {
+        buffer = ByteBuffer.allocateDirect(capacity);
+    }
+
+    /**
+     * Copies a frame from the continuous capture into this buffer.
+     *
+     * @param afterSequence Only a frame newer than this sequence number is
+     *                      read, or -1 for any frame.
+     * @param timeoutMs How long to wait for such a frame, or 0 to return at
+     *                  once.
+     * @return `true` if a frame was read, `false` if none arrived in time.
+     * @throws IllegalStateException If the capture is not running.
+     */
+    boolean read(long afterSequence, int timeoutMs) {
+        int result = NativeBindings.readFrame(buffer, metadata, afterSequence, timeoutMs);
+        while (result < 0) {
+            //Too small: grow and read again, getting the same frame or a newer one
+            buffer = ByteBuffer.allocateDirect(-result * 3 / 2);
+            result = NativeBindings.readFrame(buffer, metadata, metadata[0] - 1, 0);
+        }
+        //A sequence number of -1 means no frame arrived, as a frame can be empty
+        length = result;
+        return metadata[0] >= 0;
+    }
+
+    /**
+     * @return A read-only view of the frame data, from position 0 to the
+     *         frame length. Only valid until the next read.
+     */
+    public ByteBuffer getData() {
+        ByteBuffer data = buffer.asReadOnlyBuffer();
+        data.position(0).limit(length);
+        return data;
+    }
+
+    /**
+     * @return The length of the frame data in bytes.
+     */
+    public int getLength() {
+        return length;
+    }
+
+    /**
+     * @return The frame number, increasing by one for each frame captured.
+     */
+    public long getSequence() {
+        return metadata[0];
+    }
+
+    /**
+     * @return The capture time in microseconds since the Unix epoch.
+     */
+    public long getTimestampUs() {
+        return metadata[1];
+    }
+
+    /**
+     * @return The frame width in pixels.
+     */
+    public int getWidth() {
+        return (int) metadata[2];
+    }
+
+    /**
+     * @return The frame height in pixels.
+     */
+    public int getHeight() {
+        return (int) metadata[3];
+    }
+
+    /**
+     * @return The FourCC pixel format of the data, e.g. "MJPG".
+     */
+    public String getFormat() {
+        int fourcc = (int) metadata[4];
+        char[] chars = new char[4];
+        for (int i = 0; i < 4; i++) {
+            chars[i] = (char) ((fourcc >> (8 * i)) & 0xFF);
+        }
+        return new String(chars);
+    }
+}
@@ -0,0 +1,5 @@
+package bisocm.swiftbot.lib;
+
+public interface FrameListener {
+    void onFrame(CameraFrame frame);
+}
@@ -0,0 +1,83 @@
+package bisocm.swiftbot.lib;
+
+/**
+ * A single IMU measurement along with the fused orientation of the robot.
+ */
+public final class ImuReading {
+    private final double accelX;
+    private final double accelY;
+    private final double accelZ;
+    private final double gyroX;
+    private final double gyroY;
+    private final double gyroZ;
+    private final double heading;
+    private final double pitch;
+    private final double roll;
+
+    //Called from Rust
+    ImuReading(double accelX, double accelY, double accelZ,
+               double gyroX, double gyroY, double gyroZ,
+               double heading, double pitch, double roll) {
+        this.accelX = accelX;
+        this.accelY = accelY;
+        this.accelZ = accelZ;
+        this.gyroX = gyroX;
+        this.gyroY = gyroY;
+        this.gyroZ = gyroZ;
+        this.heading = heading;
+        this.pitch = pitch;
+        this.roll = roll;
+    }
+
+    /** @return Acceleration along the X axis in m/s^2. */
+    public double getAccelX() {
+        return accelX;
+    }
+
+    /** @return Acceleration along the Y axis in m/s^2. */
+    public double getAccelY() {
+        return accelY;
+    }
+
+    /** @return Acceleration along the Z axis in m/s^2. */
+    public double getAccelZ() {
+        return accelZ;
+    }
+
+    /** @return Angular rate about the X axis in degrees per second. */
+    public double getGyroX() {
+        return gyroX;
+    }
+
+    /** @return Angular rate about the Y axis in degrees per second. */
+    public double getGyroY() {
+        return gyroY;
+    }
+
+    /** @return Angular rate about the Z axis in degrees per second. */
+    public double getGyroZ() {
+        return gyroZ;
+    }
+
+    /** @return Heading in degrees, increasing clockwise, from 0 to 360. */
+    public double getHeading() {
+        return heading;
+    }
+
+    /** @return Pitch (nose up/down tilt) in degrees. */
+    public double getPitch() {
+        return pitch;
+    }
+
+    /** @return Roll (side to side tilt) in degrees. */
+    public double getRoll() {
+        return roll;
+    }
+
+    @Override
+    public String toString() {
+        return "ImuReading{heading=" + heading + ", pitch=" + pitch + ", roll=" + roll
+                + ", accel=(" + accelX + ", " + accelY + ", " + accelZ + ")"
+                + ", gyro=(" + gyroX + ", " + gyroY + ", " + gyroZ + ")}";
+    }
+}
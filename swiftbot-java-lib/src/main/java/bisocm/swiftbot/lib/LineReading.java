@@ -0,0 +1,47 @@
+package bisocm.swiftbot.lib;
+
+/**
+ * A reading from the line sensor array.
+ */
+public final class LineReading {
+    private final double position;
+    private final boolean lost;
+    private final double[] values;
+
+    //Called from Rust
+    LineReading(double position, boolean lost, double[] values) {
+        this.position = position;
+        this.lost = lost;
+        this.values = values;
+    }
+
+    /**
+     * @return The position of the line under the array, from -1.0 (under
+     *         the leftmost sensor) to 1.0 (under the rightmost sensor). If the
+     *         line is lost, this is the last known position.
+     */
+    public double getPosition() {
+        return position;
+    }
+
+    /**
+     * @return `true` if no sensor currently sees the line.
+     */
+    public boolean isLost() {
+        return lost;
+    }
+
+    /**
+     * @return The calibrated value of each sensor from left to right, from
+     *         0.0 (background) to 1.0 (line).
+     */
+    public double[] getValues() {
+        return values.clone();
+    }
+
+    @Override
+    public String toString() {
+        return "LineReading{position=" + position + ", lost=" + lost
+                + ", values=" + java.util.Arrays.toString(values) + "}";
+    }
+}
@@ -1,5 +1,9 @@
 package bisocm.swiftbot.lib;
 
+import java.nio.ByteBuffer;
+import java.util.List;
+import java.util.concurrent.CopyOnWriteArrayList;
+
 class NativeBindings {
 
     /***********************************************************************
@@ -61,6 +65,38 @@ class NativeBindings {
         }
     }
 
+    /***********************************************************************
+     *                       FRAME LISTENER CALLBACK                       *
+     ***********************************************************************
+     * `FrameListener` objects receive frames from the continuous camera
+     * capture. Rust is subscribed while at least one listener is
+     * registered, and calls `onFrame` from its own thread for each frame.
+     * Frames arriving while the listeners are busy are dropped.
+     ***********************************************************************/
+    private static final List<FrameListener> frameListeners = new CopyOnWriteArrayList<>();
+    private static int frameSubscription = -1;
+
+    public static synchronized void addFrameListener(FrameListener listener) {
+        frameListeners.add(listener);
+        if (frameSubscription < 0) {
+            frameSubscription = subscribeFrames();
+        }
+    }
+
+    public static synchronized void removeFrameListener(FrameListener listener) {
+        frameListeners.remove(listener);
+        if (frameListeners.isEmpty() && frameSubscription >= 0) {
+            unsubscribeFrames(frameSubscription);
+            frameSubscription = -1;
+        }
+    }
+
+    public static void onFrame(CameraFrame frame) {
+        for (FrameListener listener : frameListeners) {
+            listener.onFrame(frame);
+        }
+    }
+
     /***********************************************************************
      *                        NATIVE METHODS SECTION                       *
      ***********************************************************************
@@ -78,17 +114,142 @@ class NativeBindings {
     public static native void turnLeft(double speed);
     public static native void turnRight(double speed);
     public static native void setMotorSpeeds(double leftSpeed, double rightSpeed);
+    public static native void turnDegrees(double degrees, double speed);
 
     //Sensor readings
     public static native double readDistance();
+    public static native DistanceReading readDistanceReading();
+    public static native void setSpeedOfSound(double metresPerSecond);
+    public static native void setAmbientTemperature(double temperature);
+    public static native void setAmbientConditions(double temperature, double humidity);
+    public static native void setDistanceUnit(int unit);
     public static native boolean readButton(int buttonId);
 
+    //Additional sensors
+    public static native void registerDigitalSensor(String name, int pin, int pull, boolean activeLow);
+    public static native void registerI2cDevice(String name, int bus, int address);
+    public static native void unregisterSensor(String name);
+    public static native String[] listSensors();
+    public static native boolean readDigitalSensor(String name);
+    public static native byte[] readI2cRegisters(String name, int register, int length);
+    public static native void writeI2cRegisters(String name, int register, byte[] data);
+
+    //IMU
+    public static native void initImu(int kind, int bus, int address);
+    public static native boolean isImuAvailable();
+    public static native void calibrateImu(int samples);
+    public static native void resetImuHeading();
+    public static native ImuReading readImu();
+
+    //Line sensors
+    public static native void initLineSensorGpio(int[] pins, boolean activeLow);
+    public static native void initLineSensorAdc(int bus, int address, int channels);
+    public static native void calibrateLineSensor(int durationMs);
+    public static native void setLineThresholds(double[] white, double[] black);
+    public static native LineReading readLine();
+
     //LED control
     public static native void setButtonLed(int buttonLedId, double value);
     public static native void setUnderlight(int lightId, int red, int green, int blue);
     public static native void fillUnderlighting(int red, int green, int blue);
     public static native void clearUnderlighting();
+    public static native void setUnderlightFrame(int[] colors);
+    public static native void setUnderlights(int[] lightIds, int[] colors);
+    public static native void setUnderlightHsv(int lightId, double hue, double saturation, double value);
+    public static native void setUnderlightHex(int lightId, String hex);
+    public static native void fillUnderlightingHsv(double hue, double saturation, double value);
+    public static native void fillUnderlightingHex(String hex);
+
+    //Underlight color correction
+    public static native void setUnderlightGamma(double gamma);
+    public static native void setUnderlightWhiteBalance(double red, double green, double blue);
+    public static native void setUnderlightBrightnessLimit(double limit);
+
+    //Underlight layout
+    public static native void setUnderlightLayout(int lightId, int firstChannel, int order);
+    public static native void setUnderlightLayouts(int[] firstChannels, int[] orders);
+    public static native void setUnderlightEnabled(int lightId, boolean enabled);
+    public static native boolean isUnderlightEnabled(int lightId);
+    public static native void setUnderlightChannelEnabled(int channel, boolean enabled);
+
+    //Button LED effects
+    public static native void fadeButtonLed(int buttonLedId, double level, int durationMs);
+    public static native void blinkButtonLed(int buttonLedId, double level, int periodMs, double duty);
+    public static native void breatheButtonLed(int buttonLedId, double level, int periodMs);
+    public static native void pulseButtonLed(int buttonLedId, double level, int durationMs);
+    public static native void stopButtonLedEffect(int buttonLedId);
+    public static native boolean isButtonLedEffectRunning(int buttonLedId);
+    public static native void setButtonFeedback(int buttonId, int mode, double level);
+
+    //Underlight effects
+    public static native void startUnderlightEffect(int effect, int red, int green, int blue, int periodMs);
+    public static native void startUnderlightSequence(int[] frames, int[] durationsMs, boolean fade, boolean looping);
+    public static native void stopUnderlightEffect();
+    public static native boolean isUnderlightEffectRunning();
+
+    //Status indicator
+    public static native void setStatusIndicatorEnabled(boolean enabled);
+    public static native void setStatus(int status, boolean active);
+    public static native boolean isStatusActive(int status);
+    public static native int pushStatusLayer(int priority, int effect, int red, int green, int blue, int periodMs);
+    public static native void removeStatusLayer(int layerId);
+
+    //LED state
+    public static native int getUnderlight(int lightId);
+    public static native int[] getUnderlightFrame();
+    public static native double getButtonLed(int buttonLedId);
+    public static native int pushLedSnapshot();
+    public static native void popLedSnapshot();
+
+    //LED power budget
+    public static native void setLedPowerBudget(double limitMa);
+    public static native void setLedAccelerationPowerBudget(double limitMa);
+    public static native double getLedCurrentEstimate();
 
     //Camera control
     public static native byte[] captureImage();
+    public static native void configureCamera(String device, int width, int height, String format, int fps);
+    public static native void closeCamera();
+    public static native String[] getCameraFormats();
+    public static native int[] getCameraResolutions(String format);
+    public static native CameraDevice[] listCameras();
+
+    //Camera image controls
+    public static native CameraControl[] listCameraControls();
+    public static native CameraControl getCameraControl(String name);
+    public static native CameraControl setCameraControl(String name, long value);
+
+    //Continuous camera capture
+    public static native void startCameraCapture();
+    public static native void stopCameraCapture();
+    public static native boolean isCameraCapturing();
+    public static native CameraFrame getLatestFrame();
+    public static native CameraFrame[] getBufferedFrames();
+    public static native int readFrame(ByteBuffer buffer, long[] metadata, long afterSequence, int timeoutMs);
+    private static native int subscribeFrames();
+    private static native void unsubscribeFrames(int subscription);
+
+    //Camera stream over HTTP
+    public static native int startCameraStream(int port);
+    public static native void stopCameraStream();
+    public static native int getCameraStreamPort();
+
+    //Video recording
+    public static native void startRecording(String path, int maxSeconds, long maxBytes);
+    public static native RecordingSummary stopRecording();
+    public static native boolean isRecording();
+
+    //Camera snapshots
+    public static native void setSnapshotDirectory(String directory);
+    public static native String takeSnapshot();
+    public static native void startTimeLapse(double intervalSeconds);
+    public static native void stopTimeLapse();
+    public static native boolean isTimeLapseRunning();
+    public static native void setSnapshotButtons(int mask);
+    public static native void setSnapshotObstacleZone(double zoneCm);
+    public static native String getSnapshotError();
+
+    //Frame decoding
+    public static native DecodedFrame getDecodedFrame(int format, int maxWidth, int maxHeight, long afterSequence, int timeoutMs);
+    public static native DecodedFrame decodeImage(byte[] data, int format, int maxWidth, int maxHeight);
 }
\ No newline at end of file
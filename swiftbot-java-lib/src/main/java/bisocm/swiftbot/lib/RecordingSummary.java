@@ -0,0 +1,53 @@
+package bisocm.swiftbot.lib;
+
+/**
+ * What was recorded to a video file.
+ */
+public final class RecordingSummary {
+    private final String path;
+    private final long frameCount;
+    private final long sizeBytes;
+    private final long durationMs;
+
+    //Called from Rust
+    RecordingSummary(String path, long frameCount, long sizeBytes, long durationMs) {
+        this.path = path;
+        this.frameCount = frameCount;
+        this.sizeBytes = sizeBytes;
+        this.durationMs = durationMs;
+    }
+
+    /**
+     * @return The path of the AVI file.
+     */
+    public String getPath() {
+        return path;
+    }
+
+    /**
+     * @return The number of frames recorded.
+     */
+    public long getFrameCount() {
+        return frameCount;
+    }
+
+    /**
+     * @return The size of the file in bytes.
+     */
+    public long getSizeBytes() {
+        return sizeBytes;
+    }
+
+    /**
+     * @return The time between the first and last frames in milliseconds.
+     */
+    public long getDurationMs() {
+        return durationMs;
+    }
+
+    @Override
+    public String toString() {
+        return "RecordingSummary{path=" + path + ", frameCount=" + frameCount
+                + ", sizeBytes=" + sizeBytes + ", durationMs=" + durationMs + "}";
+    }
+}
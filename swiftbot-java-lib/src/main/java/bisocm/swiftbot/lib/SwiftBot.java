@@ -1,5 +1,7 @@
 package bisocm.swiftbot.lib;
 
+import java.nio.ByteBuffer;
+
 public class SwiftBot {
     private ButtonListener buttonListener;
 
@@ -20,6 +22,90 @@ public class SwiftBot {
     public static final int BUTTON_X = 2;
     public static final int BUTTON_Y = 3;
 
+    /***********************************************************************
+     *                     BUTTON FEEDBACK MODE CONSTANTS                  *
+     ***********************************************************************
+     * How a button's LED reacts to the button by itself, for use with
+     * setButtonFeedback.
+     ***********************************************************************/
+    public static final int FEEDBACK_NONE = 0;
+    public static final int FEEDBACK_HELD = 1;
+    public static final int FEEDBACK_FLASH = 2;
+
+    /***********************************************************************
+     *                       DISTANCE UNIT CONSTANTS                       *
+     ***********************************************************************
+     * Units that can be selected for ultrasonic distance readings.
+     ***********************************************************************/
+    public static final int UNIT_CENTIMETERS = 0;
+    public static final int UNIT_MILLIMETERS = 1;
+    public static final int UNIT_INCHES = 2;
+
+    /***********************************************************************
+     *                     INPUT PULL RESISTOR CONSTANTS                   *
+     ***********************************************************************
+     * Internal pull resistor settings for additional digital sensors.
+     ***********************************************************************/
+    public static final int PULL_NONE = 0;
+    public static final int PULL_UP = 1;
+    public static final int PULL_DOWN = 2;
+
+    /***********************************************************************
+     *                          IMU TYPE CONSTANTS                         *
+     ***********************************************************************
+     * Supported IMU chips for heading and tilt measurement.
+     ***********************************************************************/
+    public static final int IMU_MPU6050 = 0;
+    public static final int IMU_BNO055 = 1;
+
+    /***********************************************************************
+     *                      UNDERLIGHT EFFECT CONSTANTS                    *
+     ***********************************************************************
+     * Built-in animated effects for the underlights.
+     ***********************************************************************/
+    public static final int EFFECT_BREATHE = 0;
+    public static final int EFFECT_BLINK = 1;
+    public static final int EFFECT_CHASE = 2;
+    public static final int EFFECT_RAINBOW = 3;
+    public static final int EFFECT_POLICE = 4;
+    public static final int EFFECT_COMET = 5;
+
+    /***********************************************************************
+     *                  UNDERLIGHT CHANNEL ORDER CONSTANTS                 *
+     ***********************************************************************
+     * Order in which an underlight's color components are wired to the
+     * SN3218 channels, for use with setUnderlightLayout.
+     ***********************************************************************/
+    public static final int ORDER_RGB = 0;
+    public static final int ORDER_RBG = 1;
+    public static final int ORDER_GRB = 2;
+    public static final int ORDER_GBR = 3;
+    public static final int ORDER_BRG = 4;
+    public static final int ORDER_BGR = 5;
+
+    /***********************************************************************
+     *                         ROBOT STATUS CONSTANTS                      *
+     ***********************************************************************
+     * States of the robot shown by the status indicator. Low battery,
+     * obstacle warning and error always take priority over application
+     * layers.
+     ***********************************************************************/
+    public static final int STATUS_BOOTING = 0;
+    public static final int STATUS_IDLE = 1;
+    public static final int STATUS_MOVING = 2;
+    public static final int STATUS_OBSTACLE_WARNING = 3;
+    public static final int STATUS_ERROR = 4;
+    public static final int STATUS_LOW_BATTERY = 5;
+    public static final int STATUS_REMOTE_CONNECTED = 6;
+
+    /***********************************************************************
+     *                        PIXEL FORMAT CONSTANTS                       *
+     ***********************************************************************
+     * Pixel formats camera frames can be decoded to.
+     ***********************************************************************/
+    public static final int PIXEL_RGB = 0;
+    public static final int PIXEL_GRAY = 1;
+
     /***********************************************************************
      *                     MOTOR CONTROL METHODS                           *
      ***********************************************************************
@@ -70,6 +156,7 @@ public class SwiftBot {
 
     /**
      * Stops the SwiftBot's movement immediately by setting motor speeds to 0.
+     * A turnDegrees call running on another thread is ended.
      */
     public void stop() {
         NativeBindings.stop();
@@ -88,23 +175,288 @@ public class SwiftBot {
         NativeBindings.setMotorSpeeds(leftSpeed, rightSpeed);
     }
 
+    /**
+     * Turns the SwiftBot on the spot by the given angle, then stops. If an
+     * IMU is available the turn follows the measured heading, otherwise it
+     * is timed. This call blocks until the turn is complete, or stop is
+     * called from another thread.
+     *
+     * @param degrees The angle to turn, positive to the right and negative
+     *                to the left.
+     * @param speed   The speed to turn at, from 0.0 to 1.0.
+     */
+    public void turnDegrees(double degrees, double speed) {
+        NativeBindings.turnDegrees(degrees, speed);
+    }
+
     /***********************************************************************
-     *                       SENSOR READING METHOD                         *
+     *                       SENSOR READING METHODS                        *
      ***********************************************************************
-     * Method to read the current distance to an object using the
-     * ultrasonic sensor on the SwiftBot.
+     * Methods to read the current distance to an object using the
+     * ultrasonic sensor on the SwiftBot, and to correct those readings
+     * for the ambient conditions.
      ***********************************************************************/
 
     /**
      * Reads and returns the distance from the SwiftBot to the nearest object.
      *
-     * @return The distance in centimeters. Returns -1.0 if an error occurs
-     *         (such as when the sensor is unavailable).
+     * @return The distance in the unit selected with {@link #setDistanceUnit}
+     *         (centimeters by default). Returns -1.0 if nothing is detected
+     *         within the sensor's range.
      */
     public double readDistance() {
         return NativeBindings.readDistance();
     }
 
+    /**
+     * Reads the distance to the nearest object along with the status of the
+     * measurement, so open space can be told apart from a sensor fault.
+     *
+     * @return The reading, including its value, unit, timestamp, status and
+     *         raw echo pulse width.
+     */
+    public DistanceReading readDistanceReading() {
+        return NativeBindings.readDistanceReading();
+    }
+
+    /**
+     * Sets the speed of sound used to convert echo times into distances.
+     *
+     * @param metresPerSecond The speed of sound in metres per second.
+     */
+    public void setSpeedOfSound(double metresPerSecond) {
+        NativeBindings.setSpeedOfSound(metresPerSecond);
+    }
+
+    /**
+     * Corrects distance readings for the ambient air temperature.
+     *
+     * @param temperature The ambient temperature in degrees Celsius.
+     */
+    public void setAmbientTemperature(double temperature) {
+        NativeBindings.setAmbientTemperature(temperature);
+    }
+
+    /**
+     * Corrects distance readings for the ambient air temperature and
+     * relative humidity.
+     *
+     * @param temperature The ambient temperature in degrees Celsius.
+     * @param humidity    The relative humidity in percent (0 to 100).
+     */
+    public void setAmbientConditions(double temperature, double humidity) {
+        NativeBindings.setAmbientConditions(temperature, humidity);
+    }
+
+    /**
+     * Selects the unit used for distance readings.
+     *
+     * @param unit The unit (UNIT_CENTIMETERS, UNIT_MILLIMETERS or UNIT_INCHES).
+     */
+    public void setDistanceUnit(int unit) {
+        NativeBindings.setDistanceUnit(unit);
+    }
+
+    /***********************************************************************
+     *                      ADDITIONAL SENSOR METHODS                      *
+     ***********************************************************************
+     * Methods to register extra sensors on spare GPIO pins or I2C buses,
+     * such as line sensors, bump switches or an IMU, and read them by
+     * name. Sensors can also be declared in the Rust library's config.rs.
+     ***********************************************************************/
+
+    /**
+     * Registers a digital sensor on a spare GPIO pin.
+     *
+     * @param name      The name the sensor is read by.
+     * @param pin       The BCM GPIO pin number, from 0 to 27.
+     * @param pull      The internal pull resistor (PULL_NONE, PULL_UP or
+     *                  PULL_DOWN).
+     * @param activeLow `true` if the sensor is active when the pin is low.
+     * @throws IllegalArgumentException If the pin is out of range.
+     */
+    public void registerDigitalSensor(String name, int pin, int pull, boolean activeLow) {
+        NativeBindings.registerDigitalSensor(name, pin, pull, activeLow);
+    }
+
+    /**
+     * Registers a device on an I2C bus.
+     *
+     * @param name    The name the device is addressed by.
+     * @param bus     The I2C bus number (1 on the Raspberry Pi header).
+     * @param address The 7-bit device address, from 0x03 to 0x77.
+     * @throws IllegalArgumentException If the address is out of range.
+     */
+    public void registerI2cDevice(String name, int bus, int address) {
+        NativeBindings.registerI2cDevice(name, bus, address);
+    }
+
+    /**
+     * Removes a registered sensor, releasing its pin or bus.
+     *
+     * @param name The name of the sensor.
+     */
+    public void unregisterSensor(String name) {
+        NativeBindings.unregisterSensor(name);
+    }
+
+    /**
+     * @return The names of all registered sensors.
+     */
+    public String[] listSensors() {
+        return NativeBindings.listSensors();
+    }
+
+    /**
+     * Reads a registered digital sensor.
+     *
+     * @param name The name of the sensor.
+     * @return `true` if the sensor is active, `false` otherwise.
+     */
+    public boolean readDigitalSensor(String name) {
+        return NativeBindings.readDigitalSensor(name);
+    }
+
+    /**
+     * Reads consecutive registers from a registered I2C device.
+     *
+     * @param name     The name of the device.
+     * @param register The first register to read, from 0 to 255.
+     * @param length   The number of bytes to read, from 1 to 32.
+     * @return The register values.
+     * @throws IllegalArgumentException If the register or length is out of
+     *         range.
+     */
+    public byte[] readI2cRegisters(String name, int register, int length) {
+        return NativeBindings.readI2cRegisters(name, register, length);
+    }
+
+    /**
+     * Writes consecutive registers on a registered I2C device.
+     *
+     * @param name     The name of the device.
+     * @param register The first register to write, from 0 to 255.
+     * @param data     The bytes to write.
+     * @throws IllegalArgumentException If the register is out of range.
+     */
+    public void writeI2cRegisters(String name, int register, byte[] data) {
+        NativeBindings.writeI2cRegisters(name, register, data);
+    }
+
+    /***********************************************************************
+     *                             IMU METHODS                             *
+     ***********************************************************************
+     * Methods to use an optional IMU for heading and tilt. An IMU can also
+     * be declared in the Rust library's config.rs.
+     ***********************************************************************/
+
+    /**
+     * Opens an IMU on an I2C bus.
+     *
+     * @param kind    The IMU type (IMU_MPU6050 or IMU_BNO055).
+     * @param bus     The I2C bus number (1 on the Raspberry Pi header).
+     * @param address The 7-bit device address (usually 0x68 for the
+     *                MPU-6050 and 0x28 for the BNO055).
+     */
+    public void initImu(int kind, int bus, int address) {
+        NativeBindings.initImu(kind, bus, address);
+    }
+
+    /**
+     * @return `true` if an IMU is available.
+     */
+    public boolean isImuAvailable() {
+        return NativeBindings.isImuAvailable();
+    }
+
+    /**
+     * Measures the gyro offset of the IMU. Keep the SwiftBot still while
+     * this runs.
+     *
+     * @param samples The number of samples to average.
+     */
+    public void calibrateImu(int samples) {
+        NativeBindings.calibrateImu(samples);
+    }
+
+    /**
+     * Makes the current heading read as 0 degrees.
+     */
+    public void resetImuHeading() {
+        NativeBindings.resetImuHeading();
+    }
+
+    /**
+     * Reads the IMU.
+     *
+     * @return The acceleration, angular rate, heading, pitch and roll.
+     */
+    public ImuReading readImu() {
+        return NativeBindings.readImu();
+    }
+
+    /***********************************************************************
+     *                         LINE SENSOR METHODS                         *
+     ***********************************************************************
+     * Methods to follow a line with an array of reflective IR sensors,
+     * attached either to spare GPIO pins or to an ADS7830 I2C ADC.
+     ***********************************************************************/
+
+    /**
+     * Sets up a line sensor array of digital sensors on spare GPIO pins.
+     *
+     * @param pins      The BCM GPIO pin of each sensor, from left to right.
+     * @param activeLow `true` if the sensors pull their output low over the
+     *                  line.
+     */
+    public void initLineSensorGpio(int[] pins, boolean activeLow) {
+        NativeBindings.initLineSensorGpio(pins, activeLow);
+    }
+
+    /**
+     * Sets up a line sensor array of analogue sensors on an ADS7830 ADC.
+     *
+     * @param bus      The I2C bus number (1 on the Raspberry Pi header).
+     * @param address  The 7-bit address of the ADC (usually 0x48).
+     * @param channels The number of sensors, wired to ADC inputs 0
+     *                 (leftmost) upwards.
+     */
+    public void initLineSensorAdc(int bus, int address, int channels) {
+        NativeBindings.initLineSensorAdc(bus, address, channels);
+    }
+
+    /**
+     * Calibrates the line sensors. Move the SwiftBot so every sensor passes
+     * over both the line and the background while this runs, for example
+     * by turning on the spot.
+     *
+     * @param durationMs How long to sample for, in milliseconds.
+     */
+    public void calibrateLineSensor(int durationMs) {
+        NativeBindings.calibrateLineSensor(durationMs);
+    }
+
+    /**
+     * Sets the raw background and line values for each sensor, instead of
+     * calibrating.
+     *
+     * @param white The raw value of each sensor over the background.
+     * @param black The raw value of each sensor over the line.
+     */
+    public void setLineThresholds(double[] white, double[] black) {
+        NativeBindings.setLineThresholds(white, black);
+    }
+
+    /**
+     * Reads the line sensor array.
+     *
+     * @return The line position, whether the line was lost, and the value of
+     *         each sensor.
+     */
+    public LineReading readLine() {
+        return NativeBindings.readLine();
+    }
+
     /***********************************************************************
      *                       BUTTON INTERACTION METHODS                    *
      ***********************************************************************
@@ -157,6 +509,105 @@ public class SwiftBot {
         NativeBindings.setButtonLed(buttonLedId, value);
     }
 
+    /***********************************************************************
+     *                      BUTTON LED EFFECT METHODS                      *
+     ***********************************************************************
+     * Methods to play effects on the button LEDs, such as a breathing
+     * "press A to continue" cue. Effects run on a native thread, so no Java
+     * thread or sleep loop is needed. Setting a button LED directly stops
+     * its effect.
+     ***********************************************************************/
+
+    /**
+     * Fades a button LED from its current brightness to a new one, which it
+     * then holds.
+     *
+     * @param buttonLedId The ID of the button LED (BUTTON_A, BUTTON_B, BUTTON_X or BUTTON_Y).
+     * @param level       Brightness to fade to (0.0 to 1.0).
+     * @param durationMs  Duration of the fade in milliseconds.
+     * @throws IllegalArgumentException If the ID or level is invalid.
+     */
+    public void fadeButtonLed(int buttonLedId, double level, int durationMs) {
+        NativeBindings.fadeButtonLed(buttonLedId, level, durationMs);
+    }
+
+    /**
+     * Blinks a button LED until it is set directly or the effect is stopped.
+     *
+     * @param buttonLedId The ID of the button LED (BUTTON_A, BUTTON_B, BUTTON_X or BUTTON_Y).
+     * @param level       Brightness while on (0.0 to 1.0).
+     * @param periodMs    Duration of one on/off cycle in milliseconds.
+     * @param duty        Fraction of each cycle the LED is on (0.0 to 1.0).
+     * @throws IllegalArgumentException If any argument is invalid.
+     */
+    public void blinkButtonLed(int buttonLedId, double level, int periodMs, double duty) {
+        NativeBindings.blinkButtonLed(buttonLedId, level, periodMs, duty);
+    }
+
+    /**
+     * Fades a button LED in and out until it is set directly or the effect
+     * is stopped.
+     *
+     * @param buttonLedId The ID of the button LED (BUTTON_A, BUTTON_B, BUTTON_X or BUTTON_Y).
+     * @param level       Peak brightness (0.0 to 1.0).
+     * @param periodMs    Duration of one breath in milliseconds.
+     * @throws IllegalArgumentException If any argument is invalid.
+     */
+    public void breatheButtonLed(int buttonLedId, double level, int periodMs) {
+        NativeBindings.breatheButtonLed(buttonLedId, level, periodMs);
+    }
+
+    /**
+     * Pulses a button LED once: it rises to a brightness and falls back to
+     * its current one.
+     *
+     * @param buttonLedId The ID of the button LED (BUTTON_A, BUTTON_B, BUTTON_X or BUTTON_Y).
+     * @param level       Peak brightness (0.0 to 1.0).
+     * @param durationMs  Duration of the pulse in milliseconds.
+     * @throws IllegalArgumentException If any argument is invalid.
+     */
+    public void pulseButtonLed(int buttonLedId, double level, int durationMs) {
+        NativeBindings.pulseButtonLed(buttonLedId, level, durationMs);
+    }
+
+    /**
+     * Stops the effect playing on a button LED. The LED keeps its current
+     * brightness.
+     *
+     * @param buttonLedId The ID of the button LED (BUTTON_A, BUTTON_B, BUTTON_X or BUTTON_Y).
+     */
+    public void stopButtonLedEffect(int buttonLedId) {
+        NativeBindings.stopButtonLedEffect(buttonLedId);
+    }
+
+    /**
+     * Checks whether an effect is playing on a button LED. Fades and pulses
+     * stop by themselves when they finish.
+     *
+     * @param buttonLedId The ID of the button LED (BUTTON_A, BUTTON_B, BUTTON_X or BUTTON_Y).
+     * @return True while an effect is playing.
+     */
+    public boolean isButtonLedEffectRunning(int buttonLedId) {
+        return NativeBindings.isButtonLedEffectRunning(buttonLedId);
+    }
+
+    /**
+     * Makes a button's LED react to the button by itself: FEEDBACK_HELD
+     * lights it while the button is held, then puts back the level and effect
+     * it had before, and FEEDBACK_FLASH flashes it when the button is
+     * pressed. This is handled natively, so it responds even
+     * while the application is busy. FEEDBACK_NONE returns control of the
+     * LED to the application.
+     *
+     * @param buttonId The ID of the button (BUTTON_A, BUTTON_B, BUTTON_X or BUTTON_Y).
+     * @param mode     The feedback mode (FEEDBACK_NONE, FEEDBACK_HELD or FEEDBACK_FLASH).
+     * @param level    The brightness of the feedback (0.0 to 1.0).
+     * @throws IllegalArgumentException If any argument is invalid.
+     */
+    public void setButtonFeedback(int buttonId, int mode, double level) {
+        NativeBindings.setButtonFeedback(buttonId, mode, level);
+    }
+
     /***********************************************************************
      *                    UNDERLIGHT (SN3218) CONTROL METHODS              *
      ***********************************************************************
@@ -176,6 +627,18 @@ public class SwiftBot {
         NativeBindings.setUnderlight(lightId, red, green, blue);
     }
 
+    /**
+     * Sets the color of the underlight at a position.
+     *
+     * @param position The position of the underlight.
+     * @param red      Red component (0 to 255).
+     * @param green    Green component (0 to 255).
+     * @param blue     Blue component (0 to 255).
+     */
+    public void setUnderlight(UnderlightPosition position, int red, int green, int blue) {
+        NativeBindings.setUnderlight(position.getId(), red, green, blue);
+    }
+
     /**
      * Sets all underlights to the specified color.
      *
@@ -194,12 +657,925 @@ public class SwiftBot {
         NativeBindings.clearUnderlighting();
     }
 
-    /***********************************************************************
-     *                         CAMERA CAPTURE METHOD                       *
-     ***********************************************************************
-     * Method to capture an image from the SwiftBot's camera and return it
-     * as a byte array.
-     ***********************************************************************/
+    /**
+     * Sets the color of every underlight in a single update, which avoids the
+     * flicker of setting them one at a time. Nothing is sent to the LEDs if
+     * the colors have not changed.
+     *
+     * @param colors One 0xRRGGBB color per underlight, indexed by light ID (6 values).
+     * @throws IllegalArgumentException If the array does not hold 6 colors.
+     */
+    public void setUnderlightFrame(int[] colors) {
+        NativeBindings.setUnderlightFrame(colors);
+    }
+
+    /**
+     * Sets the color of several underlights in a single update, leaving the
+     * others unchanged.
+     *
+     * @param lightIds The IDs of the underlights to set.
+     * @param colors   One 0xRRGGBB color per entry in lightIds.
+     * @throws IllegalArgumentException If the arrays differ in length.
+     */
+    public void setUnderlights(int[] lightIds, int[] colors) {
+        NativeBindings.setUnderlights(lightIds, colors);
+    }
+
+    /**
+     * Sets the color of an individual underlight LED from hue, saturation and value.
+     *
+     * @param lightId    The ID of the underlight LED (range may vary by model).
+     * @param hue        Hue in degrees (0 = red, 120 = green, 240 = blue).
+     * @param saturation Saturation (0.0 to 1.0).
+     * @param value      Value, or brightness (0.0 to 1.0).
+     */
+    public void setUnderlightHsv(int lightId, double hue, double saturation, double value) {
+        NativeBindings.setUnderlightHsv(lightId, hue, saturation, value);
+    }
+
+    /**
+     * Sets the color of the underlight at a position from hue, saturation and value.
+     *
+     * @param position   The position of the underlight.
+     * @param hue        Hue in degrees (0 = red, 120 = green, 240 = blue).
+     * @param saturation Saturation (0.0 to 1.0).
+     * @param value      Value, or brightness (0.0 to 1.0).
+     */
+    public void setUnderlightHsv(UnderlightPosition position, double hue, double saturation, double value) {
+        NativeBindings.setUnderlightHsv(position.getId(), hue, saturation, value);
+    }
+
+    /**
+     * Sets the color of an individual underlight LED from a hex string.
+     *
+     * @param lightId The ID of the underlight LED (range may vary by model).
+     * @param hex     The color as "#RRGGBB" or "#RGB".
+     * @throws IllegalArgumentException If the string is not a valid color.
+     */
+    public void setUnderlightHex(int lightId, String hex) {
+        NativeBindings.setUnderlightHex(lightId, hex);
+    }
+
+    /**
+     * Sets the color of the underlight at a position from a hex string.
+     *
+     * @param position The position of the underlight.
+     * @param hex      The color as "#RRGGBB" or "#RGB".
+     * @throws IllegalArgumentException If the string is not a valid color.
+     */
+    public void setUnderlightHex(UnderlightPosition position, String hex) {
+        NativeBindings.setUnderlightHex(position.getId(), hex);
+    }
+
+    /**
+     * Sets all underlights to a color given as hue, saturation and value.
+     *
+     * @param hue        Hue in degrees (0 = red, 120 = green, 240 = blue).
+     * @param saturation Saturation (0.0 to 1.0).
+     * @param value      Value, or brightness (0.0 to 1.0).
+     */
+    public void fillUnderlightingHsv(double hue, double saturation, double value) {
+        NativeBindings.fillUnderlightingHsv(hue, saturation, value);
+    }
+
+    /**
+     * Sets all underlights to a color given as a hex string.
+     *
+     * @param hex The color as "#RRGGBB" or "#RGB".
+     * @throws IllegalArgumentException If the string is not a valid color.
+     */
+    public void fillUnderlightingHex(String hex) {
+        NativeBindings.fillUnderlightingHex(hex);
+    }
+
+    /**
+     * Sets the gamma used to correct underlight brightness, so that brightness
+     * values look evenly spaced. 1.0, the default, disables the correction;
+     * around 2.2 suits the underlights.
+     *
+     * @param gamma The gamma (greater than 0, at most 5). The default is 1.0.
+     * @throws IllegalArgumentException If the gamma is out of range.
+     */
+    public void setUnderlightGamma(double gamma) {
+        NativeBindings.setUnderlightGamma(gamma);
+    }
+
+    /**
+     * Sets the white balance of the underlights. Lower the gain of any
+     * component that looks too strong when the underlights are white.
+     *
+     * @param red   Red gain (0.0 to 1.0).
+     * @param green Green gain (0.0 to 1.0).
+     * @param blue  Blue gain (0.0 to 1.0).
+     * @throws IllegalArgumentException If a gain is out of range.
+     */
+    public void setUnderlightWhiteBalance(double red, double green, double blue) {
+        NativeBindings.setUnderlightWhiteBalance(red, green, blue);
+    }
+
+    /**
+     * Limits the overall brightness of the underlights, including effects.
+     *
+     * @param limit The brightness limit (0.0 to 1.0).
+     * @throws IllegalArgumentException If the limit is out of range.
+     */
+    public void setUnderlightBrightnessLimit(double limit) {
+        NativeBindings.setUnderlightBrightnessLimit(limit);
+    }
+
+    /**
+     * Changes which SN3218 channels drive the underlight at a position. Only
+     * needed for board revisions wired differently from the standard SwiftBot.
+     *
+     * @param position     The position of the underlight.
+     * @param firstChannel The first of three consecutive SN3218 channels driving the LED (0 to 15).
+     * @param order        The order of the color components on those channels (ORDER_RGB, ORDER_GRB, ...).
+     * @throws IllegalArgumentException If the order is invalid.
+     */
+    public void setUnderlightLayout(UnderlightPosition position, int firstChannel, int order) {
+        NativeBindings.setUnderlightLayout(position.getId(), firstChannel, order);
+    }
+
+    /**
+     * Changes which SN3218 channels drive every underlight at once. Use this
+     * when lights swap channels, which setUnderlightLayout cannot do one
+     * light at a time. Nothing changes if the layout is rejected.
+     *
+     * @param firstChannels The first channel of each underlight (0 to 15), indexed by
+     *                      UnderlightPosition ID.
+     * @param orders        The color component order of each underlight (ORDER_RGB,
+     *                      ORDER_GRB, ...), indexed by UnderlightPosition ID.
+     * @throws IllegalArgumentException If an array does not have 6 entries or an order is invalid.
+     */
+    public void setUnderlightLayout(int[] firstChannels, int[] orders) {
+        NativeBindings.setUnderlightLayouts(firstChannels, orders);
+    }
+
+    /**
+     * Switches the underlight at a position on or off. A switched-off
+     * underlight stays dark whatever color it is set to.
+     *
+     * @param position The position of the underlight.
+     * @param enabled  True to switch the underlight on, false to switch it off.
+     */
+    public void setUnderlightEnabled(UnderlightPosition position, boolean enabled) {
+        NativeBindings.setUnderlightEnabled(position.getId(), enabled);
+    }
+
+    /**
+     * Checks whether the underlight at a position is switched on.
+     *
+     * @param position The position of the underlight.
+     * @return True if the underlight is switched on.
+     */
+    public boolean isUnderlightEnabled(UnderlightPosition position) {
+        return NativeBindings.isUnderlightEnabled(position.getId());
+    }
+
+    /**
+     * Switches a single SN3218 channel on or off.
+     *
+     * @param channel The SN3218 channel (0 to 17).
+     * @param enabled True to switch the channel on, false to switch it off.
+     */
+    public void setUnderlightChannelEnabled(int channel, boolean enabled) {
+        NativeBindings.setUnderlightChannelEnabled(channel, enabled);
+    }
+
+    /***********************************************************************
+     *                      UNDERLIGHT EFFECT METHODS                      *
+     ***********************************************************************
+     * Methods to play animated effects on the underlights. Effects run on
+     * a native thread, so no Java thread or sleep loop is needed. Setting,
+     * filling or clearing the underlights directly stops the effect.
+     ***********************************************************************/
+
+    /**
+     * Starts a built-in effect, replacing any effect already playing.
+     *
+     * @param effect   The effect (EFFECT_BREATHE, EFFECT_BLINK, EFFECT_CHASE,
+     *                 EFFECT_RAINBOW, EFFECT_POLICE or EFFECT_COMET).
+     * @param red      Red component (0 to 255), ignored by rainbow and police.
+     * @param green    Green component (0 to 255), ignored by rainbow and police.
+     * @param blue     Blue component (0 to 255), ignored by rainbow and police.
+     * @param periodMs The length of one cycle of the effect, in milliseconds.
+     */
+    public void startUnderlightEffect(int effect, int red, int green, int blue, int periodMs) {
+        NativeBindings.startUnderlightEffect(effect, red, green, blue, periodMs);
+    }
+
+    /**
+     * Starts a keyframe sequence, replacing any effect already playing.
+     *
+     * @param frames      Six colors per keyframe, packed as 0xRRGGBB and
+     *                    ordered by underlight ID.
+     * @param durationsMs How long each keyframe lasts, in milliseconds.
+     * @param fade        `true` to fade into each keyframe from the previous
+     *                    one, `false` to switch at once.
+     * @param looping     `true` to repeat the sequence until stopped.
+     */
+    public void startUnderlightSequence(int[] frames, int[] durationsMs, boolean fade, boolean looping) {
+        NativeBindings.startUnderlightSequence(frames, durationsMs, fade, looping);
+    }
+
+    /**
+     * Stops the effect playing, if any, and turns the underlights off.
+     */
+    public void stopUnderlightEffect() {
+        NativeBindings.stopUnderlightEffect();
+    }
+
+    /**
+     * @return `true` while an underlight effect is playing.
+     */
+    public boolean isUnderlightEffectRunning() {
+        return NativeBindings.isUnderlightEffectRunning();
+    }
+
+    /***********************************************************************
+     *                       STATUS INDICATOR METHODS                      *
+     ***********************************************************************
+     * Methods to show the robot's status on the underlights and button
+     * LEDs. Active statuses and application layers are stacked by
+     * priority, and only the highest is shown:
+     *   error > obstacle warning > low battery > application layers >
+     *   booting > remote connected > moving > idle
+     ***********************************************************************/
+
+    /**
+     * Starts or stops the status indicator. While it is enabled, setting the
+     * LEDs directly still works, but is replaced when the status next changes.
+     * Underlight colors and effects set while a safety status (error,
+     * obstacle warning or low battery) is shown are ignored, as are button
+     * LED levels, effects and feedback while the error status is shown.
+     *
+     * @param enabled True to show status, false to turn the LEDs off and leave
+     *                them to the application.
+     */
+    public void setStatusIndicatorEnabled(boolean enabled) {
+        NativeBindings.setStatusIndicatorEnabled(enabled);
+    }
+
+    /**
+     * Turns a status on or off. STATUS_IDLE is on by default.
+     *
+     * @param status The status (STATUS_BOOTING, STATUS_IDLE, ...).
+     * @param active True to turn the status on, false to turn it off.
+     * @throws IllegalArgumentException If the status is invalid.
+     */
+    public void setStatus(int status, boolean active) {
+        NativeBindings.setStatus(status, active);
+    }
+
+    /**
+     * Checks whether a status is on.
+     *
+     * @param status The status (STATUS_BOOTING, STATUS_IDLE, ...).
+     * @return True if the status is on.
+     * @throws IllegalArgumentException If the status is invalid.
+     */
+    public boolean isStatusActive(int status) {
+        return NativeBindings.isStatusActive(status);
+    }
+
+    /**
+     * Adds a layer showing one of the built-in underlight effects. Layers are
+     * shown above the normal statuses but never above low battery, obstacle
+     * warning or error.
+     *
+     * @param priority The priority of the layer (0 to 99). Among equal
+     *                 priorities, the newest layer is shown.
+     * @param effect   The effect (EFFECT_BREATHE, EFFECT_BLINK, ...).
+     * @param red      Red component (0 to 255).
+     * @param green    Green component (0 to 255).
+     * @param blue     Blue component (0 to 255).
+     * @param periodMs Duration of one cycle of the effect in milliseconds.
+     * @return The ID of the layer, used to remove it.
+     * @throws IllegalArgumentException If any argument is invalid.
+     */
+    public int pushStatusLayer(int priority, int effect, int red, int green, int blue, int periodMs) {
+        return NativeBindings.pushStatusLayer(priority, effect, red, green, blue, periodMs);
+    }
+
+    /**
+     * Removes a layer added with pushStatusLayer.
+     *
+     * @param layerId The ID returned by pushStatusLayer.
+     */
+    public void removeStatusLayer(int layerId) {
+        NativeBindings.removeStatusLayer(layerId);
+    }
+
+    /***********************************************************************
+     *                          LED STATE METHODS                          *
+     ***********************************************************************
+     * Methods to read back the LEDs, and to save and restore their state
+     * around a temporary effect, for example:
+     *   robot.pushLedSnapshot();
+     *   robot.fillUnderlighting(255, 0, 0);
+     *   ...
+     *   robot.popLedSnapshot();
+     ***********************************************************************/
+
+    /**
+     * Gets the color of an underlight, as last set by the application or an
+     * effect, before gamma and brightness correction.
+     *
+     * @param lightId The ID of the underlight (0 to 5).
+     * @return The color as 0xRRGGBB.
+     * @throws IllegalArgumentException If the ID is invalid.
+     */
+    public int getUnderlight(int lightId) {
+        return NativeBindings.getUnderlight(lightId);
+    }
+
+    /**
+     * Gets the colors of every underlight, before gamma and brightness
+     * correction.
+     *
+     * @return One 0xRRGGBB color per underlight, indexed by light ID.
+     */
+    public int[] getUnderlightFrame() {
+        return NativeBindings.getUnderlightFrame();
+    }
+
+    /**
+     * Gets the brightness of a button LED, as last set by the application or
+     * an effect.
+     *
+     * @param buttonLedId The ID of the button LED (BUTTON_A, BUTTON_B, BUTTON_X or BUTTON_Y).
+     * @return The brightness (0.0 to 1.0).
+     * @throws IllegalArgumentException If the ID is invalid.
+     */
+    public double getButtonLed(int buttonLedId) {
+        return NativeBindings.getButtonLed(buttonLedId);
+    }
+
+    /**
+     * Saves the state of every LED, including any effects playing. Up to 16
+     * snapshots can be saved at once.
+     *
+     * @return The number of snapshots saved, including this one.
+     */
+    public int pushLedSnapshot() {
+        return NativeBindings.pushLedSnapshot();
+    }
+
+    /**
+     * Restores the LEDs to the state saved by the last pushLedSnapshot call.
+     * Saved effects start again from the beginning.
+     *
+     * @throws IllegalStateException If no snapshot is saved, or a safety
+     *         status is shown. The snapshot is kept in that case.
+     */
+    public void popLedSnapshot() {
+        NativeBindings.popLedSnapshot();
+    }
+
+    /***********************************************************************
+     *                        LED POWER BUDGET METHODS                     *
+     ***********************************************************************
+     * Methods to limit the current drawn by the underlights and button
+     * LEDs, so they do not brown out the battery when the motors start.
+     * The current is estimated from the LED brightness, and every LED is
+     * dimmed by the same factor when the estimate is over the limit.
+     ***********************************************************************/
+
+    /**
+     * Limits the current drawn by the underlights and button LEDs.
+     *
+     * @param limitMa The limit in mA, or 0 to remove it.
+     * @throws IllegalArgumentException If the limit is negative.
+     */
+    public void setLedPowerBudget(double limitMa) {
+        NativeBindings.setLedPowerBudget(limitMa);
+    }
+
+    /**
+     * Limits the current drawn by the underlights and button LEDs for a short
+     * time after a motor speeds up or reverses, on top of the normal budget.
+     *
+     * @param limitMa The limit in mA, or 0 to remove it.
+     * @throws IllegalArgumentException If the limit is negative.
+     */
+    public void setLedAccelerationPowerBudget(double limitMa) {
+        NativeBindings.setLedAccelerationPowerBudget(limitMa);
+    }
+
+    /**
+     * Estimates the current drawn by the underlights and button LEDs, after
+     * any dimming by the power budget.
+     *
+     * @return The estimated current in mA.
+     */
+    public double getLedCurrentEstimate() {
+        return NativeBindings.getLedCurrentEstimate();
+    }
+
+    /***********************************************************************
+     *                        CAMERA CAPTURE METHODS                       *
+     ***********************************************************************
+     * Methods to choose the camera and its capture mode, and to capture an
+     * image from the SwiftBot's camera as a byte array. The camera opens
+     * /dev/video0 at 640x480 MJPG, 30 fps, unless configured otherwise.
+     ***********************************************************************/
+
+    /**
+     * Opens the camera, or switches an open camera to another device or
+     * capture mode. If the new mode cannot be started, the previous one is
+     * kept. A running continuous capture is restarted in the new mode.
+     *
+     * @param device The V4L2 device path, e.g. "/dev/video0", or "replay:"
+     *               followed by images to replay instead of the camera (see
+     *               replayCamera).
+     * @param width The frame width in pixels.
+     * @param height The frame height in pixels.
+     * @param format The FourCC pixel format, e.g. "MJPG" or "YUYV".
+     * @param fps The frame rate in frames per second.
+     * @throws IllegalArgumentException If an argument is invalid.
+     * @throws IllegalStateException If a recording is running.
+     */
+    public void configureCamera(String device, int width, int height, String format, int fps) {
+        NativeBindings.configureCamera(device, width, height, format, fps);
+    }
+
+    /**
+     * Stops the camera and releases the device. It is opened again by the
+     * next capture.
+     */
+    public void closeCamera() {
+        NativeBindings.closeCamera();
+    }
+
+    /**
+     * Lists the pixel formats the camera supports.
+     *
+     * @return FourCC codes, e.g. "MJPG" and "YUYV".
+     */
+    public String[] getCameraFormats() {
+        return NativeBindings.getCameraFormats();
+    }
+
+    /**
+     * Lists the resolutions the camera supports for a pixel format. Cameras
+     * with a continuous range of sizes report only the smallest and largest.
+     *
+     * @param format The FourCC pixel format, e.g. "MJPG".
+     * @return Width and height pairs: {width0, height0, width1, height1, ...}.
+     */
+    public int[] getCameraResolutions(String format) {
+        return NativeBindings.getCameraResolutions(format);
+    }
+
+    /**
+     * Finds the cameras attached to the robot and the capture modes each
+     * supports, so a mode can be picked instead of guessed.
+     *
+     * @return One CameraDevice per camera, in device number order.
+     */
+    public CameraDevice[] listCameras() {
+        return NativeBindings.listCameras();
+    }
+
+    /**
+     * Opens a camera in one of the modes it reported. The frame rate is
+     * rounded to whole frames per second.
+     *
+     * @param camera The camera, from listCameras.
+     * @param mode One of the camera's modes.
+     */
+    public void configureCamera(CameraDevice camera, CameraMode mode) {
+        configureCamera(camera.getPath(), mode.getWidth(), mode.getHeight(), mode.getFormat(),
+                (int) Math.max(1, Math.round(mode.getFps())));
+    }
+
+    /**
+     * Replays JPEG images in a loop instead of using the camera, so vision
+     * code can be tested without one. Every camera feature, such as
+     * captureImage, continuous capture, decoding, streaming and recording,
+     * then uses the replayed images. Call configureCamera with a /dev path
+     * to go back to the camera.
+     *
+     * @param path A directory of .jpg or .jpeg files, replayed in name
+     *             order, a single JPEG file, or an MJPEG or AVI file, such
+     *             as one from startRecording.
+     * @param fps The rate to replay the images at, in frames per second.
+     */
+    public void replayCamera(String path, int fps) {
+        configureCamera("replay:" + path, 640, 480, "MJPG", fps);
+    }
+
+    /***********************************************************************
+     *                       CAMERA CONTROL METHODS                        *
+     ***********************************************************************
+     * Methods to read and adjust the camera's image controls, such as
+     * exposure, gain, white balance, brightness, contrast and flipping.
+     * Which controls exist depends on the camera, so list them first. To
+     * set the exposure by hand, for example:
+     *   robot.setCameraControl("exposure_auto", 1); //Manual mode
+     *   robot.setCameraControl("exposure_absolute", 100);
+     ***********************************************************************/
+
+    /**
+     * Lists the camera's image controls, with their current values and the
+     * values each accepts.
+     *
+     * @return The controls, in the order the driver reports them.
+     */
+    public CameraControl[] listCameraControls() {
+        return NativeBindings.listCameraControls();
+    }
+
+    /**
+     * Reads one camera control.
+     *
+     * @param name The control's key, e.g. "brightness", or its driver name.
+     * @return The control with its current value.
+     */
+    public CameraControl getCameraControl(String name) {
+        return NativeBindings.getCameraControl(name);
+    }
+
+    /**
+     * Sets a camera control. Manual exposure, gain and white balance
+     * temperature usually only take effect once the matching automatic
+     * control ("exposure_auto", "gain_auto" or "white_balance_auto") is
+     * turned off.
+     *
+     * @param name The control's key, e.g. "contrast", or its driver name.
+     * @param value A number within the control's range, 0 or 1 for a
+     *              boolean, or a menu item index.
+     * @return The control with the value the camera applied.
+     */
+    public CameraControl setCameraControl(String name, long value) {
+        return NativeBindings.setCameraControl(name, value);
+    }
+
+    /**
+     * Turns a boolean camera control, such as "horizontal_flip", on or off.
+     *
+     * @param name The control's key or driver name.
+     * @param on `true` to turn it on.
+     * @return The control with the value the camera applied.
+     */
+    public CameraControl setCameraControl(String name, boolean on) {
+        return NativeBindings.setCameraControl(name, on ? 1 : 0);
+    }
+
+    /***********************************************************************
+     *                     CONTINUOUS CAPTURE METHODS                      *
+     ***********************************************************************
+     * Methods to capture frames continuously on a background thread. The
+     * latest 8 frames are kept, and each new frame is passed to the frame
+     * listeners, for example:
+     *   robot.addFrameListener(frame -> process(frame.getData()));
+     *   robot.startCameraCapture();
+     ***********************************************************************/
+
+    /**
+     * Starts capturing frames continuously. While running, captureImage
+     * returns the next frame from the capture.
+     */
+    public void startCameraCapture() {
+        NativeBindings.startCameraCapture();
+    }
+
+    /**
+     * Stops the continuous capture. The camera stays open.
+     */
+    public void stopCameraCapture() {
+        NativeBindings.stopCameraCapture();
+    }
+
+    /**
+     * Checks whether the continuous capture is running. It stops by itself
+     * if the camera fails.
+     *
+     * @return `true` while frames are being captured.
+     * @throws Exception With the camera error, if the capture stopped
+     *         because of it.
+     */
+    public boolean isCameraCapturing() {
+        return NativeBindings.isCameraCapturing();
+    }
+
+    /**
+     * Gets the latest captured frame without waiting.
+     *
+     * @return The latest frame, or `null` if none has been captured yet.
+     */
+    public CameraFrame getLatestFrame() {
+        return NativeBindings.getLatestFrame();
+    }
+
+    /**
+     * Gets every frame kept by the continuous capture.
+     *
+     * @return The latest frames, oldest first.
+     */
+    public CameraFrame[] getBufferedFrames() {
+        return NativeBindings.getBufferedFrames();
+    }
+
+    /**
+     * Copies the next frame into a reusable buffer, without allocating
+     * memory for each frame, for example:
+     *   FrameBuffer buffer = new FrameBuffer(256 * 1024);
+     *   long last = -1;
+     *   while (robot.readFrame(buffer, last, 1000)) {
+     *       last = buffer.getSequence();
+     *       process(buffer.getData());
+     *   }
+     *
+     * @param buffer The buffer to copy the frame into. It grows if needed.
+     * @param afterSequence Only a frame newer than this sequence number is
+     *                      read, or -1 for any frame.
+     * @param timeoutMs How long to wait for such a frame, or 0 to return at
+     *                  once.
+     * @return `true` if a frame was read, `false` if none arrived in time.
+     * @throws IllegalStateException If the capture is not running.
+     */
+    public boolean readFrame(FrameBuffer buffer, long afterSequence, int timeoutMs) {
+        return buffer.read(afterSequence, timeoutMs);
+    }
+
+    /**
+     * Copies the next frame into a caller-provided direct ByteBuffer, from
+     * position 0. The position and limit are left unchanged.
+     *
+     * @param buffer A direct ByteBuffer to copy the frame data into.
+     * @param metadata At least 5 values, set to the sequence number,
+     *                 timestamp in microseconds, width, height and FourCC
+     *                 pixel format (packed little endian) of the frame.
+     * @param afterSequence Only a frame newer than this sequence number is
+     *                      read, or -1 for any frame.
+     * @param timeoutMs How long to wait for such a frame, or 0 to return at
+     *                  once.
+     * @return The frame length, or minus the frame length if the buffer is
+     *         too small. If no frame arrived in time, 0 is returned and the
+     *         sequence number is set to -1.
+     * @throws IllegalArgumentException If the buffer is not direct or the
+     *         metadata array is too short.
+     * @throws IllegalStateException If the capture is not running.
+     */
+    public int readFrame(ByteBuffer buffer, long[] metadata, long afterSequence, int timeoutMs) {
+        return NativeBindings.readFrame(buffer, metadata, afterSequence, timeoutMs);
+    }
+
+    /**
+     * Registers a listener called with each captured frame. Listeners are
+     * called on a background thread; frames arriving while they are busy
+     * are dropped.
+     *
+     * @param listener The listener to add.
+     */
+    public void addFrameListener(FrameListener listener) {
+        NativeBindings.addFrameListener(listener);
+    }
+
+    /**
+     * Removes a frame listener.
+     *
+     * @param listener The listener to remove.
+     */
+    public void removeFrameListener(FrameListener listener) {
+        NativeBindings.removeFrameListener(listener);
+    }
+
+    /***********************************************************************
+     *                       CAMERA STREAM METHODS                         *
+     ***********************************************************************
+     * Methods to watch the camera from a browser. The Rust library serves
+     * the continuous capture as an MJPEG stream at /stream.mjpg (and /),
+     * and the latest frame at /snapshot.jpg. Requires the library to be
+     * built with the http-stream feature and the camera to capture MJPG.
+     ***********************************************************************/
+
+    /**
+     * Starts streaming the camera over HTTP on port 8080, and starts the
+     * continuous capture.
+     *
+     * @return The port the stream is served on.
+     * @throws UnsupportedOperationException If the library was built
+     *         without the http-stream feature.
+     */
+    public int startCameraStream() {
+        return startCameraStream(8080);
+    }
+
+    /**
+     * Starts streaming the camera over HTTP, and starts the continuous
+     * capture. A stream already running is stopped first.
+     *
+     * @param port The TCP port to listen on, or 0 to pick a free port.
+     * @return The port the stream is served on.
+     * @throws UnsupportedOperationException If the library was built
+     *         without the http-stream feature.
+     */
+    public int startCameraStream(int port) {
+        return NativeBindings.startCameraStream(port);
+    }
+
+    /**
+     * Stops streaming the camera over HTTP. The continuous capture keeps
+     * running.
+     */
+    public void stopCameraStream() {
+        NativeBindings.stopCameraStream();
+    }
+
+    /**
+     * Gets the port the camera stream is served on.
+     *
+     * @return The port, or -1 if the stream is not running.
+     */
+    public int getCameraStreamPort() {
+        return NativeBindings.getCameraStreamPort();
+    }
+
+    /***********************************************************************
+     *                      VIDEO RECORDING METHODS                        *
+     ***********************************************************************
+     * Methods to record the camera to an MJPEG AVI file, which most video
+     * players can open. Each frame keeps its capture time in a JPEG
+     * comment. Requires the camera to capture MJPG.
+     ***********************************************************************/
+
+    /**
+     * Starts recording the camera, and starts the continuous capture. A
+     * recording in progress is stopped first.
+     *
+     * @param path The AVI file to record to. It is replaced if it exists.
+     * @param maxSeconds Stop after this many seconds, or 0 for no limit.
+     * @param maxBytes Stop before the file grows past this size, or 0 for
+     *                 no limit. Files are never larger than 1 GiB.
+     */
+    public void startRecording(String path, int maxSeconds, long maxBytes) {
+        NativeBindings.startRecording(path, maxSeconds, maxBytes);
+    }
+
+    /**
+     * Starts recording the camera until stopped, up to 1 GiB.
+     *
+     * @param path The AVI file to record to. It is replaced if it exists.
+     */
+    public void startRecording(String path) {
+        startRecording(path, 0, 0);
+    }
+
+    /**
+     * Stops recording and finishes the file. The continuous capture keeps
+     * running.
+     *
+     * @return What was recorded, or null if nothing was being recorded.
+     */
+    public RecordingSummary stopRecording() {
+        return NativeBindings.stopRecording();
+    }
+
+    /**
+     * Checks whether the camera is being recorded. Recordings stop by
+     * themselves at their limits; call stopRecording to get the summary.
+     *
+     * @return true while recording.
+     */
+    public boolean isRecording() {
+        return NativeBindings.isRecording();
+    }
+
+    /***********************************************************************
+     *                         SNAPSHOT METHODS                            *
+     ***********************************************************************
+     * Methods to save camera frames to a directory, on demand, on a
+     * schedule, or when a button is pressed or an obstacle comes close.
+     * Each JPEG is named after its UTC capture time, frame number and
+     * trigger, e.g. 20240131-235959.123_42_button_a.jpg, and has a JSON
+     * sidecar of the same name holding the motor speeds, latest distance
+     * reading, orientation and statuses. Requires the camera to capture
+     * MJPG. Enabling any of these starts the continuous capture.
+     ***********************************************************************/
+
+    /**
+     * Sets the directory snapshots are saved to, creating it if needed.
+     *
+     * @param directory The directory path.
+     */
+    public void setSnapshotDirectory(String directory) {
+        NativeBindings.setSnapshotDirectory(directory);
+    }
+
+    /**
+     * Saves the next frame captured, so it is no older than the call.
+     *
+     * @return The path of the JPEG. The sidecar ends in .json instead.
+     */
+    public String takeSnapshot() {
+        return NativeBindings.takeSnapshot();
+    }
+
+    /**
+     * Starts saving a snapshot at a fixed interval, the first straight
+     * away. A running time-lapse is replaced.
+     *
+     * @param intervalSeconds The time between snapshots in seconds.
+     * @throws IllegalStateException If no snapshot directory is set.
+     */
+    public void startTimeLapse(double intervalSeconds) {
+        NativeBindings.startTimeLapse(intervalSeconds);
+    }
+
+    /**
+     * Stops the time-lapse.
+     */
+    public void stopTimeLapse() {
+        NativeBindings.stopTimeLapse();
+    }
+
+    /**
+     * @return true while a time-lapse is running.
+     */
+    public boolean isTimeLapseRunning() {
+        return NativeBindings.isTimeLapseRunning();
+    }
+
+    /**
+     * Saves a snapshot whenever one of the given buttons is pressed.
+     * Button monitoring must be running. Call with no buttons to turn
+     * this off.
+     *
+     * @param buttons The buttons, e.g. BUTTON_A.
+     */
+    public void setSnapshotButtons(int... buttons) {
+        int mask = 0;
+        for (int button : buttons) {
+            if (button < BUTTON_A || button > BUTTON_Y) {
+                throw new IllegalArgumentException("Invalid button: " + button);
+            }
+            mask |= 1 << button;
+        }
+        NativeBindings.setSnapshotButtons(mask);
+    }
+
+    /**
+     * Saves a snapshot whenever a distance reading comes within the given
+     * distance after being further away. Only the application's own
+     * readings are checked; the sensor is not polled.
+     *
+     * @param zoneCm The distance in centimeters, or 0 to turn this off.
+     */
+    public void setSnapshotObstacleZone(double zoneCm) {
+        NativeBindings.setSnapshotObstacleZone(zoneCm);
+    }
+
+    /**
+     * Gets why the last time-lapse or triggered snapshot failed, and
+     * clears it.
+     *
+     * @return The error message, or null if none has failed.
+     */
+    public String getSnapshotError() {
+        return NativeBindings.getSnapshotError();
+    }
+
+    /***********************************************************************
+     *                      FRAME DECODING METHODS                         *
+     ***********************************************************************
+     * Methods to decode camera frames to RGB or grayscale pixels in the
+     * Rust library, so simple vision needs no image library. Only MJPG and
+     * YUYV frames can be decoded.
+     ***********************************************************************/
+
+    /**
+     * Decodes the next frame from the continuous capture.
+     *
+     * @param format The pixel format, PIXEL_RGB or PIXEL_GRAY.
+     * @param maxWidth The largest width wanted, or 0 for no limit. The frame
+     *                 is shrunk to fit, keeping its aspect ratio, but never
+     *                 enlarged.
+     * @param maxHeight The largest height wanted, or 0 for no limit.
+     * @param afterSequence Only a frame newer than this sequence number is
+     *                      decoded, or -1 for any frame.
+     * @param timeoutMs How long to wait for such a frame, or 0 to return at
+     *                  once.
+     * @return The decoded frame, or null if none arrived in time.
+     * @throws IllegalStateException If the capture is not running.
+     */
+    public DecodedFrame getDecodedFrame(int format, int maxWidth, int maxHeight, long afterSequence, int timeoutMs) {
+        return NativeBindings.getDecodedFrame(format, maxWidth, maxHeight, afterSequence, timeoutMs);
+    }
+
+    /**
+     * Decodes a JPEG image, such as one returned by captureImage.
+     *
+     * @param data The JPEG image.
+     * @param format The pixel format, PIXEL_RGB or PIXEL_GRAY.
+     * @param maxWidth The largest width wanted, or 0 for no limit.
+     * @param maxHeight The largest height wanted, or 0 for no limit.
+     * @return The decoded image.
+     */
+    public DecodedFrame decodeImage(byte[] data, int format, int maxWidth, int maxHeight) {
+        return NativeBindings.decodeImage(data, format, maxWidth, maxHeight);
+    }
 
     /**
      * Captures an image from the SwiftBot's camera.
@@ -0,0 +1,27 @@
+package bisocm.swiftbot.lib;
+
+/**
+ * Positions of the six underlights around the SwiftBot. The IDs match the
+ * light IDs used by the underlight methods.
+ */
+public enum UnderlightPosition {
+    FRONT_RIGHT(0),
+    FRONT_LEFT(1),
+    MIDDLE_LEFT(2),
+    REAR_LEFT(3),
+    REAR_RIGHT(4),
+    MIDDLE_RIGHT(5);
+
+    private final int id;
+
+    UnderlightPosition(int id) {
+        this.id = id;
+    }
+
+    /**
+     * @return The light ID of this position (0 to 5).
+     */
+    public int getId() {
+        return id;
+    }
+}
@@ -0,0 +1,317 @@
+use crate::color::Color;
+use crate::config::*;
+use crate::sn3218::UnderlightLeds;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Time between rendered frames (50 fps).
+const FRAME_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Underlight positions in order around the robot, used by the travelling effects.
+const RING: [u8; NUM_UNDERLIGHTS] = [
+    LIGHT_FRONT_RIGHT,
+    LIGHT_FRONT_LEFT,
+    LIGHT_MIDDLE_LEFT,
+    LIGHT_REAR_LEFT,
+    LIGHT_REAR_RIGHT,
+    LIGHT_MIDDLE_RIGHT,
+];
+
+/// Color of every underlight, indexed by light ID.
+pub type Frame = [Color; NUM_UNDERLIGHTS];
+
+/// One step of a keyframe sequence.
+#[derive(Clone, Debug)]
+pub struct Keyframe {
+    pub frame: Frame,
+    pub duration: Duration,
+    pub fade: bool, //Fade in from the previous keyframe instead of switching at once
+}
+
+/// An underlight animation.
+#[derive(Clone, Debug)]
+pub enum Effect {
+    Breathe { color: Color, period: Duration }, //Fades all lights in and out
+    Blink { color: Color, period: Duration },   //Switches all lights on and off
+    Chase { color: Color, period: Duration },   //One light travelling around the robot per period
+    Rainbow { period: Duration },             //Hues rotating around the robot
+    Police { period: Duration },              //Red on the left alternating with blue on the right
+    Comet { color: Color, period: Duration },   //A travelling light with a fading tail
+    Sequence { keyframes: Vec<Keyframe>, looping: bool },
+}
+
+impl Effect {
+    /// Builds one of the built-in effects from the integer IDs used across JNI
+    /// (0 = breathe, 1 = blink, 2 = chase, 3 = rainbow, 4 = police, 5 = comet).
+    pub fn from_id(id: i32, color: Color, period: Duration) -> Result<Self, Box<dyn Error>> {
+        if period.is_zero() {
+            return Err("Effect period must be greater than zero.".into());
+        }
+        match id {
+            0 => Ok(Effect::Breathe { color, period }),
+            1 => Ok(Effect::Blink { color, period }),
+            2 => Ok(Effect::Chase { color, period }),
+            3 => Ok(Effect::Rainbow { period }),
+            4 => Ok(Effect::Police { period }),
+            5 => Ok(Effect::Comet { color, period }),
+            _ => Err(format!("Invalid effect ID: {}. Must be between 0 and 5.", id).into()),
+        }
+    }
+
+    /// Renders the frame shown `elapsed` after the effect started, or `None` once a
+    /// non-looping sequence has finished.
+    pub fn render(&self, elapsed: Duration) -> Option<Frame> {
+        let mut frame = [Color::BLACK; NUM_UNDERLIGHTS];
+        match self {
+            Effect::Breathe { color, period } => {
+                let level = (1.0 - (2.0 * PI * phase(elapsed, *period)).cos()) / 2.0;
+                frame.fill(color.scale(level));
+            }
+            Effect::Blink { color, period } => {
+                if phase(elapsed, *period) < 0.5 {
+                    frame.fill(*color);
+                }
+            }
+            Effect::Chase { color, period } => {
+                let head = (phase(elapsed, *period) * NUM_UNDERLIGHTS as f64) as usize;
+                frame[RING[head] as usize] = *color;
+            }
+            Effect::Rainbow { period } => {
+                let offset = phase(elapsed, *period);
+                for (i, light) in RING.iter().enumerate() {
+                    let hue = (offset + i as f64 / NUM_UNDERLIGHTS as f64).fract() * 360.0;
+                    frame[*light as usize] = Color::from_hsv(hue, 1.0, 1.0);
+                }
+            }
+            Effect::Police { period } => {
+                let (lights, color) = if phase(elapsed, *period) < 0.5 {
+                    ([LIGHT_FRONT_LEFT, LIGHT_MIDDLE_LEFT, LIGHT_REAR_LEFT], Color::rgb(255, 0, 0))
+                } else {
+                    ([LIGHT_FRONT_RIGHT, LIGHT_MIDDLE_RIGHT, LIGHT_REAR_RIGHT], Color::rgb(0, 0, 255))
+                };
+                for light in lights {
+                    frame[light as usize] = color;
+                }
+            }
+            Effect::Comet { color, period } => {
+                let position = phase(elapsed, *period) * NUM_UNDERLIGHTS as f64;
+                for (i, light) in RING.iter().enumerate() {
+                    //Distance behind the head, wrapping around the ring
+                    let behind = (position - i as f64).rem_euclid(NUM_UNDERLIGHTS as f64);
+                    let level = (1.0 - behind / 3.0).max(0.0);
+                    frame[*light as usize] = color.scale(level * level);
+                }
+            }
+            Effect::Sequence { keyframes, looping } => {
+                let total: Duration = keyframes.iter().map(|k| k.duration).sum();
+                if keyframes.is_empty() || total.is_zero() {
+                    return None;
+                }
+                if !looping && elapsed >= total {
+                    return None;
+                }
+
+                let mut remaining = Duration::from_secs_f64(phase(elapsed, total) * total.as_secs_f64());
+                frame = keyframes[keyframes.len() - 1].frame;
+                for (i, keyframe) in keyframes.iter().enumerate() {
+                    if remaining >= keyframe.duration {
+                        remaining -= keyframe.duration;
+                        continue;
+                    }
+                    let previous = match i {
+                        0 if *looping => &keyframes[keyframes.len() - 1],
+                        0 => keyframe,
+                        _ => &keyframes[i - 1],
+                    };
+                    frame = if keyframe.fade {
+                        let t = remaining.as_secs_f64() / keyframe.duration.as_secs_f64();
+                        blend(&previous.frame, &keyframe.frame, t)
+                    } else {
+                        keyframe.frame
+                    };
+                    break;
+                }
+            }
+        }
+        Some(frame)
+    }
+}
+
+/// Fraction of the current period that has elapsed, from 0.0 to 1.0.
+fn phase(elapsed: Duration, period: Duration) -> f64 {
+    (elapsed.as_secs_f64() / period.as_secs_f64()).fract()
+}
+
+fn blend(from: &Frame, to: &Frame, t: f64) -> Frame {
+    let mut frame = *to;
+    for (i, light) in frame.iter_mut().enumerate() {
+        *light = from[i].blend(to[i], t);
+    }
+    frame
+}
+
+/// Shows the frames of `effect` until it finishes, `stop` is set, or `show` fails.
+fn play(effect: &Effect, stop: &AtomicBool, mut show: impl FnMut(&Frame) -> Result<(), Box<dyn Error>>) {
+    let started = Instant::now();
+    while !stop.load(Ordering::Relaxed) {
+        let frame = match effect.render(started.elapsed()) {
+            Some(frame) => frame,
+            None => break,
+        };
+        if show(&frame).is_err() {
+            break;
+        }
+        thread::sleep(FRAME_INTERVAL);
+    }
+}
+
+/// Plays underlight effects on a background thread. Starting an effect replaces the one playing.
+#[derive(Default)]
+pub struct AnimationEngine {
+    running: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+    effect: Option<Effect>, //The effect started last, kept so it can be restarted
+}
+
+impl AnimationEngine {
+    /// Starts playing `effect` on `leds`, stopping any effect already playing.
+    pub fn start(&mut self, leds: &'static Mutex<UnderlightLeds>, effect: Effect) {
+        self.stop();
+
+        self.effect = Some(effect.clone());
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            play(&effect, &stop_flag, |frame| leds.lock().unwrap().set_frame(frame));
+        });
+
+        self.running = Some((stop, handle));
+    }
+
+    /// Stops the effect playing, if any. The underlights keep their last frame.
+    pub fn stop(&mut self) {
+        if let Some((stop, handle)) = self.running.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+        self.effect = None;
+    }
+
+    /// Returns the effect playing, if any.
+    pub fn effect(&self) -> Option<&Effect> {
+        self.effect.as_ref().filter(|_| self.is_running())
+    }
+
+    /// Returns `true` while an effect is playing.
+    pub fn is_running(&self) -> bool {
+        self.running.as_ref().is_some_and(|(_, handle)| !handle.is_finished())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color = Color::rgb(255, 0, 0);
+    const BLUE: Color = Color::rgb(0, 0, 255);
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn keyframe(color: Color, millis: u64, fade: bool) -> Keyframe {
+        Keyframe { frame: [color; NUM_UNDERLIGHTS], duration: ms(millis), fade }
+    }
+
+    #[test]
+    fn breathe_and_blink_follow_the_period() {
+        let breathe = Effect::Breathe { color: RED, period: ms(1000) };
+        assert_eq!(breathe.render(ms(0)).unwrap(), [Color::BLACK; NUM_UNDERLIGHTS]);
+        let half = breathe.render(ms(250)).unwrap();
+        assert!(half.iter().all(|color| (127..=128).contains(&color.r) && color.g == 0));
+        assert_eq!(breathe.render(ms(1500)).unwrap(), [RED; NUM_UNDERLIGHTS]);
+
+        let blink = Effect::Blink { color: BLUE, period: ms(1000) };
+        assert_eq!(blink.render(ms(499)).unwrap(), [BLUE; NUM_UNDERLIGHTS]);
+        assert_eq!(blink.render(ms(500)).unwrap(), [Color::BLACK; NUM_UNDERLIGHTS]);
+    }
+
+    #[test]
+    fn travelling_effects_move_around_the_ring() {
+        let chase = Effect::Chase { color: RED, period: ms(600) };
+        for (step, light) in RING.iter().enumerate() {
+            let frame = chase.render(ms(step as u64 * 100 + 50)).unwrap();
+            for (i, color) in frame.iter().enumerate() {
+                assert_eq!(*color, if i == *light as usize { RED } else { Color::BLACK });
+            }
+        }
+
+        //The comet's head is at full brightness, with a dimmer tail behind it
+        let comet = Effect::Comet { color: RED, period: ms(600) };
+        let frame = comet.render(ms(200)).unwrap();
+        assert_eq!(frame[RING[2] as usize], RED);
+        assert!(frame[RING[1] as usize].r < RED.r && frame[RING[1] as usize].r > frame[RING[0] as usize].r);
+        assert_eq!(frame[RING[3] as usize], Color::BLACK);
+
+        let rainbow = Effect::Rainbow { period: ms(600) };
+        let frame = rainbow.render(ms(0)).unwrap();
+        assert_eq!(frame[RING[0] as usize], RED);
+        assert_eq!(frame[RING[2] as usize], Color::rgb(0, 255, 0));
+        assert_eq!(rainbow.render(ms(100)).unwrap()[RING[0] as usize], frame[RING[1] as usize]);
+    }
+
+    #[test]
+    fn police_alternates_sides() {
+        let police = Effect::Police { period: ms(1000) };
+        let left = police.render(ms(100)).unwrap();
+        assert_eq!(left[LIGHT_FRONT_LEFT as usize], RED);
+        assert_eq!(left[LIGHT_FRONT_RIGHT as usize], Color::BLACK);
+        let right = police.render(ms(600)).unwrap();
+        assert_eq!(right[LIGHT_MIDDLE_RIGHT as usize], BLUE);
+        assert_eq!(right[LIGHT_MIDDLE_LEFT as usize], Color::BLACK);
+    }
+
+    #[test]
+    fn sequences_advance_fade_and_loop() {
+        let keyframes = vec![keyframe(RED, 100, false), keyframe(BLUE, 100, true)];
+        let once = Effect::Sequence { keyframes: keyframes.clone(), looping: false };
+        assert_eq!(once.render(ms(50)).unwrap(), [RED; NUM_UNDERLIGHTS]);
+        assert_eq!(once.render(ms(150)).unwrap(), [Color::rgb(128, 0, 128); NUM_UNDERLIGHTS]);
+        assert!(once.render(ms(200)).is_none());
+
+        let looping = Effect::Sequence { keyframes, looping: true };
+        assert_eq!(looping.render(ms(250)).unwrap(), [RED; NUM_UNDERLIGHTS]);
+        assert_eq!(looping.render(ms(350)).unwrap(), [Color::rgb(128, 0, 128); NUM_UNDERLIGHTS]);
+
+        assert!(Effect::Sequence { keyframes: Vec::new(), looping: true }.render(ms(0)).is_none());
+    }
+
+    #[test]
+    fn play_stops_when_a_sequence_ends_or_showing_fails() {
+        let effect = Effect::Sequence { keyframes: vec![keyframe(RED, 60, false), keyframe(BLUE, 60, false)], looping: false };
+        let mut frames = Vec::new();
+        play(&effect, &AtomicBool::new(false), |frame| {
+            frames.push(frame[0]);
+            Ok(())
+        });
+        assert_eq!(frames.first(), Some(&RED));
+        assert_eq!(frames.last(), Some(&BLUE));
+
+        let mut shown = 0;
+        play(&Effect::Rainbow { period: ms(100) }, &AtomicBool::new(false), |_| {
+            shown += 1;
+            Err("LEDs unavailable".into())
+        });
+        assert_eq!(shown, 1);
+
+        let mut shown = 0;
+        play(&Effect::Rainbow { period: ms(100) }, &AtomicBool::new(true), |_| {
+            shown += 1;
+            Ok(())
+        });
+        assert_eq!(shown, 0);
+    }
+}
@@ -0,0 +1,282 @@
+use crate::buttons::Buttons;
+use crate::config::*;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time between button LED updates (50 fps).
+const FRAME_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A brightness effect played on a single button LED. Levels are from 0.0 to 1.0.
+#[derive(Clone, Debug)]
+pub enum LedEffect {
+    Fade { level: f64, duration: Duration },              //Fades from the current level to `level`, then holds it
+    Blink { level: f64, period: Duration, duty: f64 },   //On at `level` for `duty` of each period, then off
+    Breathe { level: f64, period: Duration },            //Fades between off and `level`
+    Pulse { level: f64, duration: Duration },             //Rises to `level` and falls back to the current level once
+}
+
+impl LedEffect {
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let (level, time) = match self {
+            LedEffect::Fade { level, duration } | LedEffect::Pulse { level, duration } => (*level, *duration),
+            LedEffect::Blink { level, period, duty } => {
+                if !(0.0..=1.0).contains(duty) {
+                    return Err(format!("Invalid duty cycle: {}. Must be between 0.0 and 1.0.", duty).into());
+                }
+                (*level, *period)
+            }
+            LedEffect::Breathe { level, period } => (*level, *period),
+        };
+        if !(0.0..=1.0).contains(&level) {
+            return Err(format!("Invalid LED level: {}. Must be between 0.0 and 1.0.", level).into());
+        }
+        if time.is_zero() && !matches!(self, LedEffect::Fade { .. }) {
+            return Err("Effect period must be greater than zero.".into());
+        }
+        Ok(())
+    }
+
+    /// Returns the level `elapsed` after the effect started from level `base`, or `None`
+    /// once a one-shot effect has finished.
+    fn level(&self, elapsed: Duration, base: f64) -> Option<f64> {
+        match self {
+            LedEffect::Fade { level, duration } => {
+                if elapsed >= *duration {
+                    return None;
+                }
+                let t = elapsed.as_secs_f64() / duration.as_secs_f64();
+                Some(base + (level - base) * t)
+            }
+            LedEffect::Blink { level, period, duty } => {
+                Some(if phase(elapsed, *period) < *duty { *level } else { 0.0 })
+            }
+            LedEffect::Breathe { level, period } => {
+                Some(level * (1.0 - (2.0 * PI * phase(elapsed, *period)).cos()) / 2.0)
+            }
+            LedEffect::Pulse { level, duration } => {
+                if elapsed >= *duration {
+                    return None;
+                }
+                let t = elapsed.as_secs_f64() / duration.as_secs_f64();
+                Some(base + (level - base) * (PI * t).sin())
+            }
+        }
+    }
+
+    /// Level left on the LED when a one-shot effect finishes.
+    fn final_level(&self, base: f64) -> f64 {
+        match self {
+            LedEffect::Fade { level, .. } => *level,
+            _ => base,
+        }
+    }
+}
+
+/// Fraction of the current period that has elapsed, from 0.0 to 1.0.
+fn phase(elapsed: Duration, period: Duration) -> f64 {
+    (elapsed.as_secs_f64() / period.as_secs_f64()).fract()
+}
+
+struct ActiveEffect {
+    effect: LedEffect,
+    started: Instant,
+    base: f64, //LED level when the effect started
+}
+
+struct State {
+    effects: [Option<ActiveEffect>; NUM_BUTTONS],
+    worker_running: bool,
+}
+
+/// Plays effects on the button LEDs from a single background thread, which only runs
+/// while at least one effect is playing. Each LED plays one effect at a time.
+pub struct ButtonLedEffects {
+    state: Arc<Mutex<State>>,
+    held: [Option<(f64, Option<LedEffect>)>; NUM_BUTTONS], //Level and effect to restore after `hold`
+}
+
+impl Default for ButtonLedEffects {
+    fn default() -> Self {
+        ButtonLedEffects {
+            state: Arc::new(Mutex::new(State {
+                effects: [None, None, None, None],
+                worker_running: false,
+            })),
+            held: [None, None, None, None],
+        }
+    }
+}
+
+impl ButtonLedEffects {
+    /// Starts playing `effect` on a button LED, replacing any effect already playing on it.
+    pub fn start(&mut self, leds: &'static Mutex<Buttons>, button_led: u8, effect: LedEffect) -> Result<(), Box<dyn Error>> {
+        if button_led >= NUM_BUTTONS as u8 {
+            return Err("Invalid button LED index".into());
+        }
+        effect.validate()?;
+
+        let base = leds.lock().unwrap().button_led(button_led)?;
+        let mut state = self.state.lock().unwrap();
+        state.effects[button_led as usize] = Some(ActiveEffect {
+            effect,
+            started: Instant::now(),
+            base,
+        });
+
+        if !state.worker_running {
+            state.worker_running = true;
+            let state = Arc::clone(&self.state);
+            thread::spawn(move || run(&state, leds));
+        }
+        Ok(())
+    }
+
+    /// Lights a button LED at `level` until `release`, which puts back its level and effect
+    /// from before. Changes made in between are replaced on release.
+    pub fn hold(&mut self, leds: &'static Mutex<Buttons>, button_led: u8, level: f64) -> Result<(), Box<dyn Error>> {
+        let previous = leds.lock().unwrap().button_led(button_led)?;
+        let effect = self.effect(button_led);
+        self.stop(button_led);
+
+        //A repeated press keeps what was saved by the first
+        let held = &mut self.held[button_led as usize];
+        if held.is_none() {
+            *held = Some((previous, effect));
+        }
+        leds.lock().unwrap().set_button_led(button_led, level)
+    }
+
+    /// Puts back the level and effect a button LED had before `hold`. Does nothing if it is
+    /// not held.
+    pub fn release(&mut self, leds: &'static Mutex<Buttons>, button_led: u8) -> Result<(), Box<dyn Error>> {
+        let Some((level, effect)) = self.held.get_mut(button_led as usize).and_then(Option::take) else {
+            return Ok(());
+        };
+        leds.lock().unwrap().set_button_led(button_led, level)?;
+        match effect {
+            Some(effect) => self.start(leds, button_led, effect),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops the effect playing on a button LED, if any. The LED keeps its current level.
+    pub fn stop(&mut self, button_led: u8) {
+        if let Some(effect) = self.state.lock().unwrap().effects.get_mut(button_led as usize) {
+            *effect = None;
+        }
+    }
+
+    /// Returns the effect playing on a button LED, if any.
+    pub fn effect(&self, button_led: u8) -> Option<LedEffect> {
+        self.state.lock().unwrap().effects
+            .get(button_led as usize)
+            .and_then(|effect| effect.as_ref().map(|active| active.effect.clone()))
+    }
+
+    /// Returns `true` while an effect is playing on a button LED.
+    pub fn is_running(&self, button_led: u8) -> bool {
+        self.state.lock().unwrap().effects
+            .get(button_led as usize)
+            .is_some_and(|effect| effect.is_some())
+    }
+}
+
+fn run(state: &Mutex<State>, leds: &Mutex<Buttons>) {
+    loop {
+        {
+            let mut state = state.lock().unwrap();
+            if state.effects.iter().all(|effect| effect.is_none()) {
+                //Cleared under the lock, so `start` spawns a new worker for the next effect
+                state.worker_running = false;
+                return;
+            }
+
+            let mut leds = leds.lock().unwrap();
+            for (i, slot) in state.effects.iter_mut().enumerate() {
+                let Some(active) = slot else { continue };
+                let level = match active.effect.level(active.started.elapsed(), active.base) {
+                    Some(level) => level,
+                    None => {
+                        let level = active.effect.final_level(active.base);
+                        *slot = None;
+                        level
+                    }
+                };
+                if leds.set_button_led(i as u8, level).is_err() {
+                    *slot = None;
+                }
+            }
+        }
+        thread::sleep(FRAME_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn assert_level(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("effect has finished");
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn fade_moves_from_the_base_level_and_holds_the_new_one() {
+        let fade = LedEffect::Fade { level: 1.0, duration: ms(1000) };
+        assert_level(fade.level(ms(0), 0.2), 0.2);
+        assert_level(fade.level(ms(500), 0.2), 0.6);
+        assert_eq!(fade.level(ms(1000), 0.2), None);
+        assert_eq!(fade.final_level(0.2), 1.0);
+
+        //A zero-length fade jumps straight to the new level
+        let jump = LedEffect::Fade { level: 0.5, duration: Duration::ZERO };
+        assert_eq!(jump.level(ms(0), 0.0), None);
+        assert_eq!(jump.final_level(0.0), 0.5);
+    }
+
+    #[test]
+    fn blink_and_breathe_repeat_every_period() {
+        let blink = LedEffect::Blink { level: 0.8, period: ms(1000), duty: 0.25 };
+        assert_level(blink.level(ms(0), 0.0), 0.8);
+        assert_level(blink.level(ms(249), 0.0), 0.8);
+        assert_level(blink.level(ms(250), 0.0), 0.0);
+        assert_level(blink.level(ms(1100), 0.0), 0.8);
+
+        let breathe = LedEffect::Breathe { level: 0.6, period: ms(1000) };
+        assert_level(breathe.level(ms(0), 0.0), 0.0);
+        assert_level(breathe.level(ms(250), 0.0), 0.3);
+        assert_level(breathe.level(ms(500), 0.0), 0.6);
+        assert_level(breathe.level(ms(1500), 0.0), 0.6);
+    }
+
+    #[test]
+    fn pulse_rises_once_and_returns_to_the_base_level() {
+        let pulse = LedEffect::Pulse { level: 1.0, duration: ms(200) };
+        assert_level(pulse.level(ms(0), 0.4), 0.4);
+        assert_level(pulse.level(ms(100), 0.4), 1.0);
+        assert_eq!(pulse.level(ms(200), 0.4), None);
+        assert_eq!(pulse.final_level(0.4), 0.4);
+        assert_eq!(LedEffect::Blink { level: 1.0, period: ms(100), duty: 0.5 }.final_level(0.3), 0.3);
+    }
+
+    #[test]
+    fn validate_rejects_bad_levels_periods_and_duty_cycles() {
+        assert!(LedEffect::Breathe { level: 1.0, period: ms(100) }.validate().is_ok());
+        assert!(LedEffect::Fade { level: 0.0, duration: Duration::ZERO }.validate().is_ok());
+
+        assert!(LedEffect::Breathe { level: 1.5, period: ms(100) }.validate().is_err());
+        assert!(LedEffect::Pulse { level: -0.1, duration: ms(100) }.validate().is_err());
+        assert!(LedEffect::Fade { level: f64::NAN, duration: ms(100) }.validate().is_err());
+        assert!(LedEffect::Breathe { level: 1.0, period: Duration::ZERO }.validate().is_err());
+        assert!(LedEffect::Pulse { level: 1.0, duration: Duration::ZERO }.validate().is_err());
+        assert!(LedEffect::Blink { level: 1.0, period: Duration::ZERO, duty: 0.5 }.validate().is_err());
+        assert!(LedEffect::Blink { level: 1.0, period: ms(100), duty: 1.5 }.validate().is_err());
+    }
+}
@@ -1,19 +1,68 @@
+use crate::button_effects::LedEffect;
 use crate::config::*;
+use crate::power::{LedGroup, SharedPowerBudget};
 use crate::utils::clamp;
 use jni::sys::jint;
 use jni::JavaVM;
 use rppal::gpio::{Gpio, OutputPin};
 use std::error::Error;
 
+/// How a button's LED reacts to the button by itself, without a round trip through Java.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ButtonFeedback {
+    None,                  //The LED is only controlled by the application
+    Held { level: f64 },   //Lit at `level` while the button is held, then back as it was
+    Flash { level: f64 },  //Flashes up to `level` when the button is pressed
+}
+
+impl ButtonFeedback {
+    /// Maps the integer IDs used across JNI (0 = none, 1 = lit while held, 2 = flash on press).
+    pub fn from_id(id: i32, level: f64) -> Result<Self, Box<dyn Error>> {
+        if !(0.0..=1.0).contains(&level) {
+            return Err(format!("Invalid LED level: {}. Must be between 0.0 and 1.0.", level).into());
+        }
+        match id {
+            0 => Ok(ButtonFeedback::None),
+            1 => Ok(ButtonFeedback::Held { level }),
+            2 => Ok(ButtonFeedback::Flash { level }),
+            _ => Err(format!("Invalid feedback mode: {}. Must be between 0 and 2.", id).into()),
+        }
+    }
+
+    /// Returns what the LED should do when the button is pressed or released, if anything.
+    pub fn action(self, pressed: bool) -> Option<FeedbackAction> {
+        match self {
+            ButtonFeedback::None => None,
+            ButtonFeedback::Held { level } if pressed => Some(FeedbackAction::Hold(level)),
+            ButtonFeedback::Held { .. } => Some(FeedbackAction::Restore),
+            ButtonFeedback::Flash { level } => {
+                pressed.then_some(FeedbackAction::Play(LedEffect::Pulse { level, duration: BUTTON_FLASH_DURATION }))
+            }
+        }
+    }
+}
+
+/// A change to a button's LED made by its feedback.
+#[derive(Clone, Debug)]
+pub enum FeedbackAction {
+    Hold(f64),       //Save the LED's level and effect, then light it at this level
+    Restore,         //Put back the level and effect saved by `Hold`
+    Play(LedEffect), //Play a one-shot effect, which ends at the LED's level
+}
+
 /// Struct to manage LED control and underlighting using the SN3218 LED driver
 pub struct Buttons {
     pub led_pins: [OutputPin; NUM_BUTTONS], //GPIO pins for button LEDs
     pub led_frequency: f64, //PWM frequency for the button LEDs
+    levels: [f64; NUM_BUTTONS], //Current brightness of each button LED
+    feedback: [ButtonFeedback; NUM_BUTTONS], //How each LED reacts to its button
+    power: SharedPowerBudget, //Scales the LEDs down when they draw too much current
+    scale: f64, //Power budget scale the LEDs were last written with
 }
 
 impl Buttons {
     /// Initialize the Leds struct, set up GPIO pins, and configure SN3218
-    pub fn new(gpio: &Gpio) -> Result<Self, Box<dyn Error>> {
+    pub fn new(gpio: &Gpio, power: SharedPowerBudget) -> Result<Self, Box<dyn Error>> {
         //Initialize button LEDs as GPIO output pins
         let mut led_a = gpio.get(LED_A_PIN)?.into_output();
         let mut led_b = gpio.get(LED_B_PIN)?.into_output();
@@ -31,6 +80,10 @@ impl Buttons {
         Ok(Buttons {
             led_pins,
             led_frequency,
+            levels: [0.0; NUM_BUTTONS],
+            feedback: [ButtonFeedback::None; NUM_BUTTONS],
+            power,
+            scale: 1.0,
         })
     }
 
@@ -40,15 +93,59 @@ impl Buttons {
             return Err("Invalid button LED index".into());
         }
 
-        //Clamp brightness value between 0.0 and 1.0
+        //Clamp brightness value between 0.0 and 1.0, which is also the duty cycle range
         let brightness = clamp(value, 0.0, 1.0);
-        let duty_cycle = brightness * 100.0; //Convert to percentage
+        self.levels[button_led as usize] = brightness;
+
+        //The other LEDs only need rewriting if the power budget scale has changed
+        if self.update_scale() {
+            return self.refresh();
+        }
 
         //Set PWM frequency with calculated duty cycle for selected LED
-        self.led_pins[button_led as usize].set_pwm_frequency(self.led_frequency, duty_cycle)?;
+        self.led_pins[button_led as usize].set_pwm_frequency(self.led_frequency, brightness * self.scale)?;
+
+        Ok(())
+    }
 
+    /// Rewrite every button LED, e.g. after the power budget has changed
+    pub fn refresh(&mut self) -> Result<(), Box<dyn Error>> {
+        self.update_scale();
+        for (led, level) in self.led_pins.iter_mut().zip(self.levels) {
+            led.set_pwm_frequency(self.led_frequency, level * self.scale)?;
+        }
         Ok(())
     }
+
+    /// Recalculate the power budget scale, returning `true` if it has changed
+    fn update_scale(&mut self) -> bool {
+        let demand = self.levels.iter().sum::<f64>() * BUTTON_LED_MA;
+        let scale = self.power.lock().unwrap().scale(LedGroup::Buttons, demand);
+        let changed = scale != self.scale;
+        self.scale = scale;
+        changed
+    }
+
+    /// Set how a specific button LED reacts to its button
+    pub fn set_feedback(&mut self, button: u8, feedback: ButtonFeedback) -> Result<(), Box<dyn Error>> {
+        if button >= NUM_BUTTONS as u8 {
+            return Err("Invalid button index".into());
+        }
+        self.feedback[button as usize] = feedback;
+        Ok(())
+    }
+
+    /// Get how a specific button LED reacts to its button
+    pub fn feedback(&self, button: u8) -> ButtonFeedback {
+        self.feedback.get(button as usize).copied().unwrap_or(ButtonFeedback::None)
+    }
+
+    /// Get the current brightness of a specific button LED
+    pub fn button_led(&self, button_led: u8) -> Result<f64, Box<dyn Error>> {
+        self.levels.get(button_led as usize)
+            .copied()
+            .ok_or_else(|| "Invalid button LED index".into())
+    }
 }
 
 // Function to notify Java of a button press event
@@ -66,3 +163,25 @@ pub(crate) fn notify_button_released(jvm: &JavaVM, button_id: u8) {
     env.call_static_method(button_class, "onButtonReleased", "(I)V", &[jint::from(button_id).into()])
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feedback_maps_presses_and_releases_to_actions() {
+        assert!(ButtonFeedback::None.action(true).is_none());
+        assert!(ButtonFeedback::None.action(false).is_none());
+
+        let held = ButtonFeedback::Held { level: 0.7 };
+        assert!(matches!(held.action(true), Some(FeedbackAction::Hold(level)) if level == 0.7));
+        assert!(matches!(held.action(false), Some(FeedbackAction::Restore)));
+
+        let flash = ButtonFeedback::Flash { level: 0.5 };
+        assert!(matches!(
+            flash.action(true),
+            Some(FeedbackAction::Play(LedEffect::Pulse { level, duration })) if level == 0.5 && duration == BUTTON_FLASH_DURATION
+        ));
+        assert!(flash.action(false).is_none());
+    }
+}
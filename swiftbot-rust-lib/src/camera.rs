@@ -1,44 +1,530 @@
-use rscam::{Camera, Config};
+use crate::capture::{Frame, FrameSource};
+use crate::config::*;
+use crate::replay::ReplayCamera;
+use rscam::{Camera, Config, Control, CtrlData, IntervalInfo, ResolutionInfo};
 use std::error::Error;
+use std::fs;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Camera device and capture mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraConfig {
+    pub device: String,         //V4L2 device path, e.g. "/dev/video0", or "replay:" and images to replay (see `replay_path`)
+    pub resolution: (u32, u32), //Width and height in pixels
+    pub format: [u8; 4],        //FourCC pixel format, e.g. *b"MJPG"
+    pub interval: (u32, u32),   //Time between frames as a fraction of a second, e.g. (1, 30) for 30 fps
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            device: CAMERA_DEVICE.to_string(),
+            resolution: CAMERA_RESOLUTION,
+            format: CAMERA_FORMAT,
+            interval: CAMERA_INTERVAL,
+        }
+    }
+}
+
+impl CameraConfig {
+    /// Parses a FourCC pixel format code such as "MJPG" or "YUYV". Case matters.
+    pub fn fourcc(format: &str) -> Result<[u8; 4], Box<dyn Error>> {
+        let bytes = format.as_bytes();
+        if bytes.len() != 4 || !bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            return Err(format!("Invalid pixel format: \"{}\". Must be a four character code such as MJPG.", format).into());
+        }
+        Ok([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    /// Returns the path after the `replay:` prefix, if the device is a JPEG file, a directory
+    /// of JPEG files, or an MJPEG or AVI file to replay in place of the camera. See
+    /// `ReplayCamera`.
+    pub fn replay_path(&self) -> Option<&str> {
+        self.device.strip_prefix(CAMERA_REPLAY_PREFIX)
+    }
+
+    /// Returns `true` if the device is images to replay rather than a camera.
+    pub fn is_replay(&self) -> bool {
+        self.replay_path().is_some()
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.resolution.0 == 0 || self.resolution.1 == 0 {
+            return Err(format!("Invalid resolution: {}x{}.", self.resolution.0, self.resolution.1).into());
+        }
+        if self.interval.0 == 0 || self.interval.1 == 0 {
+            return Err(format!("Invalid frame interval: {}/{} s.", self.interval.0, self.interval.1).into());
+        }
+        if self.is_replay() && !matches!(&self.format, b"MJPG" | b"JPEG") {
+            return Err(format!(
+                "Cannot replay {} as {}. Replayed images are MJPG.",
+                self.device,
+                String::from_utf8_lossy(&self.format),
+            ).into());
+        }
+        Ok(())
+    }
+}
+
+/// A capture mode supported by a camera.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraMode {
+    pub format: [u8; 4],        //FourCC pixel format
+    pub description: String,    //Format description from the driver, e.g. "Motion-JPEG"
+    pub resolution: (u32, u32), //Width and height in pixels
+    pub interval: (u32, u32),   //Time between frames as a fraction of a second
+}
+
+/// A V4L2 capture device and the modes it supports.
+#[derive(Clone, Debug)]
+pub struct CameraDevice {
+    pub path: String,          //Device path, e.g. "/dev/video0"
+    pub name: String,          //Name reported by the driver, or empty if unknown
+    pub modes: Vec<CameraMode>,
+}
+
+/// Lists the V4L2 devices under /dev that can capture video, in device number order.
+/// Devices that cannot be opened or queried, or support no capture modes, are left out.
+pub fn discover_cameras() -> Vec<CameraDevice> {
+    let mut numbers: Vec<u32> = match fs::read_dir("/dev") {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str()?.strip_prefix("video")?.parse().ok())
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    numbers.sort_unstable();
+
+    numbers
+        .into_iter()
+        .filter_map(|number| query_camera(&format!("/dev/video{}", number)).ok())
+        .filter(|device| !device.modes.is_empty())
+        .collect()
+}
+
+/// Lists every format, resolution and frame interval combination a camera supports.
+/// Continuous ranges are reported by their smallest and largest values.
+pub fn query_camera(path: &str) -> Result<CameraDevice, Box<dyn Error>> {
+    let camera = Camera::new(path).map_err(|e| format!("Failed to open camera {}: {}", path, e))?;
+
+    //The driver name is only exposed through sysfs
+    let name = path
+        .rsplit('/')
+        .next()
+        .and_then(|node| fs::read_to_string(format!("/sys/class/video4linux/{}/name", node)).ok())
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default();
+
+    let mut modes = Vec::new();
+    for format in camera.formats() {
+        let format = format?;
+        for resolution in sizes(camera.resolutions(&format.format)?) {
+            let intervals = match camera.intervals(&format.format, resolution)? {
+                IntervalInfo::Discretes(intervals) => intervals,
+                IntervalInfo::Stepwise { min, max, .. } => vec![min, max],
+            };
+            for interval in intervals {
+                modes.push(CameraMode {
+                    format: format.format,
+                    description: format.description.clone(),
+                    resolution,
+                    interval,
+                });
+            }
+        }
+    }
+
+    Ok(CameraDevice { path: path.to_string(), name, modes })
+}
+
+/// Flattens a resolution range into its smallest and largest sizes.
+fn sizes(info: ResolutionInfo) -> Vec<(u32, u32)> {
+    match info {
+        ResolutionInfo::Discretes(resolutions) => resolutions,
+        ResolutionInfo::Stepwise { min, max, .. } => vec![min, max],
+    }
+}
+
+/// Kind of value a camera control takes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlType {
+    Integer, //A number from `minimum` to `maximum` in steps of `step`
+    Boolean, //0 or 1
+    Menu,    //The index of one of the menu items
+    Button,  //Performs an action when set, with any value
+    Other,   //Cannot be read or set as a number
+}
+
+impl ControlType {
+    /// The integer ID used across JNI, matching the order of `CameraControl.Type` in Java.
+    pub fn id(self) -> i32 {
+        match self {
+            ControlType::Integer => 0,
+            ControlType::Boolean => 1,
+            ControlType::Menu => 2,
+            ControlType::Button => 3,
+            ControlType::Other => 4,
+        }
+    }
+}
+
+/// A V4L2 image control such as exposure or white balance, and its current value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraControl {
+    pub id: u32,                  //V4L2 control ID
+    pub key: String,              //Short name used to look the control up, e.g. "exposure_auto"
+    pub name: String,             //Name reported by the driver, e.g. "Auto Exposure"
+    pub kind: ControlType,
+    pub value: i64,
+    pub default: i64,
+    pub minimum: i64,
+    pub maximum: i64,
+    pub step: i64,
+    pub menu: Vec<(u32, String)>, //Index and name of each menu item
+    pub read_only: bool,
+    pub inactive: bool,           //Currently has no effect, e.g. manual exposure while auto exposure is on
+}
+
+//Keys for the common controls, so they are the same whatever the driver calls them
+const CONTROL_KEYS: [(u32, &str); 19] = [
+    (rscam::CID_BRIGHTNESS, "brightness"),
+    (rscam::CID_CONTRAST, "contrast"),
+    (rscam::CID_SATURATION, "saturation"),
+    (rscam::CID_SHARPNESS, "sharpness"),
+    (rscam::CID_GAIN, "gain"),
+    (rscam::CID_AUTOGAIN, "gain_auto"),
+    (rscam::CID_EXPOSURE, "exposure"),
+    (rscam::CID_EXPOSURE_AUTO, "exposure_auto"),
+    (rscam::CID_EXPOSURE_ABSOLUTE, "exposure_absolute"),
+    (rscam::CID_AUTO_EXPOSURE_BIAS, "exposure_bias"),
+    (rscam::CID_ISO_SENSITIVITY, "iso"),
+    (rscam::CID_AUTO_WHITE_BALANCE, "white_balance_auto"),
+    (rscam::CID_WHITE_BALANCE_TEMPERATURE, "white_balance_temperature"),
+    (rscam::CID_HFLIP, "horizontal_flip"),
+    (rscam::CID_VFLIP, "vertical_flip"),
+    (rscam::CID_ROTATE, "rotate"),
+    (rscam::CID_FOCUS_AUTO, "focus_auto"),
+    (rscam::CID_FOCUS_ABSOLUTE, "focus_absolute"),
+    (rscam::CID_POWER_LINE_FREQUENCY, "power_line_frequency"),
+];
+
+/// Lower-case name with runs of other characters replaced by `_`, e.g. "Exposure, Auto" to
+/// "exposure_auto".
+fn normalize_control_name(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+impl CameraControl {
+    /// Converts a control reported by the driver. Control class headings are left out.
+    fn from_v4l2(control: Control) -> Option<Self> {
+        let key = CONTROL_KEYS
+            .iter()
+            .find(|(id, _)| *id == control.id)
+            .map_or_else(|| normalize_control_name(&control.name), |(_, key)| key.to_string());
+        let (kind, value, default, minimum, maximum, step, menu) = match control.data {
+            CtrlData::Integer { value, default, minimum, maximum, step } => {
+                (ControlType::Integer, value as i64, default as i64, minimum as i64, maximum as i64, step as i64, Vec::new())
+            }
+            CtrlData::Integer64 { value, default, minimum, maximum, step } => {
+                (ControlType::Integer, value, default, minimum, maximum, step, Vec::new())
+            }
+            CtrlData::Boolean { value, default } => (ControlType::Boolean, value as i64, default as i64, 0, 1, 1, Vec::new()),
+            CtrlData::Menu { value, default, items } => {
+                let menu: Vec<(u32, String)> = items.into_iter().map(|item| (item.index, item.name)).collect();
+                let (minimum, maximum) = menu_range(&menu);
+                (ControlType::Menu, value as i64, default as i64, minimum, maximum, 1, menu)
+            }
+            CtrlData::IntegerMenu { value, default, items } => {
+                let menu: Vec<(u32, String)> = items.into_iter().map(|item| (item.index, item.value.to_string())).collect();
+                let (minimum, maximum) = menu_range(&menu);
+                (ControlType::Menu, value as i64, default as i64, minimum, maximum, 1, menu)
+            }
+            CtrlData::Bitmask { value, default, maximum } => {
+                (ControlType::Integer, value as i64, default as i64, 0, maximum as i64, 1, Vec::new())
+            }
+            CtrlData::Button => (ControlType::Button, 0, 0, 0, 0, 0, Vec::new()),
+            CtrlData::String { .. } | CtrlData::Unknown => (ControlType::Other, 0, 0, 0, 0, 0, Vec::new()),
+            CtrlData::CtrlClass => return None,
+        };
+
+        Some(CameraControl {
+            id: control.id,
+            key,
+            name: control.name,
+            kind,
+            value,
+            default,
+            minimum,
+            maximum,
+            step,
+            menu,
+            read_only: control.flags & (rscam::FLAG_READ_ONLY | rscam::FLAG_DISABLED) != 0,
+            inactive: control.flags & rscam::FLAG_INACTIVE != 0,
+        })
+    }
+
+    /// Returns `true` if `name` is the control's key or, ignoring case and punctuation, its
+    /// driver name.
+    pub fn matches(&self, name: &str) -> bool {
+        let name = normalize_control_name(name);
+        self.key == name || normalize_control_name(&self.name) == name
+    }
+
+    /// Checks that the control can be set to `value`.
+    pub fn check_value(&self, value: i64) -> Result<(), Box<dyn Error>> {
+        if self.read_only {
+            return Err(format!("The {} control is read-only.", self.name).into());
+        }
+        match self.kind {
+            ControlType::Button => Ok(()),
+            ControlType::Other => Err(format!("The {} control cannot be set to a number.", self.name).into()),
+            ControlType::Menu if !self.menu.iter().any(|(index, _)| *index as i64 == value) => {
+                let items: Vec<String> = self.menu.iter().map(|(index, name)| format!("{} = {}", index, name)).collect();
+                Err(format!("Invalid value {} for {}. Must be one of: {}.", value, self.name, items.join(", ")).into())
+            }
+            ControlType::Integer | ControlType::Boolean if value < self.minimum || value > self.maximum => Err(format!(
+                "Invalid value {} for {}. Must be between {} and {}.",
+                value, self.name, self.minimum, self.maximum,
+            ).into()),
+            ControlType::Integer if self.step > 1 && (value - self.minimum) % self.step != 0 => Err(format!(
+                "Invalid value {} for {}. Must be {} plus a multiple of {}.",
+                value, self.name, self.minimum, self.step,
+            ).into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn menu_range(menu: &[(u32, String)]) -> (i64, i64) {
+    let indices = menu.iter().map(|(index, _)| *index as i64);
+    (indices.clone().min().unwrap_or(0), indices.max().unwrap_or(0))
+}
+
+/// Where the frames come from.
+enum Backend {
+    V4l2(Camera),
+    Replay(ReplayCamera),
+}
 
 pub struct CameraController {
-    camera: Camera,
+    backend: Backend,
+    config: CameraConfig,
 }
 
 impl CameraController {
-    /// Initializes the camera with the specified resolution and format.
-    pub fn new() -> Result<Self, Box<dyn Error>> {
-        let mut camera = Camera::new("/dev/video0")?;
+    /// Opens the camera and starts capturing in the given mode. Replayed images are paced
+    /// at the configured frame rate, and keep their own size whatever the resolution.
+    pub fn new(config: CameraConfig) -> Result<Self, Box<dyn Error>> {
+        config.validate()?;
+        let backend = if let Some(path) = config.replay_path() {
+            let interval = Duration::from_secs_f64(config.interval.0 as f64 / config.interval.1 as f64);
+            Backend::Replay(ReplayCamera::open(path, interval)?)
+        } else {
+            Backend::V4l2(Self::open(&config)?)
+        };
+        Ok(CameraController { backend, config })
+    }
+
+    fn open(config: &CameraConfig) -> Result<Camera, Box<dyn Error>> {
+        let mut camera = Camera::new(&config.device)
+            .map_err(|e| format!("Failed to open camera {}: {}", config.device, e))?;
 
-        //Configure the camera (adjust resolution and format as needed)
         camera.start(&Config {
-            interval: (1, 30), // 30 fps
-            resolution: (640, 480),
-            format: b"MJPG",
+            interval: config.interval,
+            resolution: config.resolution,
+            format: &config.format,
             ..Default::default()
-        })?;
+        }).map_err(|e| format!(
+            "Camera {} cannot capture {}x{} {} at {}/{} s: {}",
+            config.device,
+            config.resolution.0,
+            config.resolution.1,
+            String::from_utf8_lossy(&config.format),
+            config.interval.0,
+            config.interval.1,
+            e,
+        ))?;
+
+        Ok(camera)
+    }
+
+    /// Returns the mode the camera is capturing in.
+    pub fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+
+    /// Lists the FourCC pixel formats the camera supports.
+    pub fn formats(&self) -> Result<Vec<[u8; 4]>, Box<dyn Error>> {
+        let camera = match &self.backend {
+            Backend::V4l2(camera) => camera,
+            Backend::Replay(_) => return Ok(vec![*b"MJPG"]),
+        };
+        let mut formats = Vec::new();
+        for format in camera.formats() {
+            formats.push(format?.format);
+        }
+        Ok(formats)
+    }
+
+    /// Lists the resolutions the camera supports for a pixel format. Cameras with a continuous
+    /// range of sizes report only the smallest and largest. Replayed images report the size of
+    /// the first image.
+    pub fn resolutions(&self, format: &[u8; 4]) -> Result<Vec<(u32, u32)>, Box<dyn Error>> {
+        match &self.backend {
+            Backend::V4l2(camera) => Ok(sizes(camera.resolutions(format)?)),
+            Backend::Replay(_) if !matches!(format, b"MJPG" | b"JPEG") => Ok(Vec::new()),
+            Backend::Replay(replay) => Ok(vec![replay.resolution()?]),
+        }
+    }
+
+    /// Lists the image controls the camera supports, with their current values. Replayed
+    /// images have none.
+    pub fn controls(&self) -> Result<Vec<CameraControl>, Box<dyn Error>> {
+        let camera = match &self.backend {
+            Backend::V4l2(camera) => camera,
+            Backend::Replay(_) => return Ok(Vec::new()),
+        };
+        let mut controls = Vec::new();
+        for control in camera.controls() {
+            controls.extend(CameraControl::from_v4l2(control?));
+        }
+        Ok(controls)
+    }
+
+    /// Looks up a control by key (see `CameraControl::key`) or driver name.
+    pub fn control(&self, name: &str) -> Result<CameraControl, Box<dyn Error>> {
+        self.controls()?
+            .into_iter()
+            .find(|control| control.matches(name))
+            .ok_or_else(|| format!("Camera {} has no {} control.", self.config.device, name).into())
+    }
+
+    /// Sets a control, looked up by key or driver name, and returns it with its new value.
+    /// Setting an inactive control is allowed, but has no effect until it becomes active.
+    pub fn set_control(&self, name: &str, value: i64) -> Result<CameraControl, Box<dyn Error>> {
+        let control = self.control(name)?;
+        control.check_value(value)?;
+        let Backend::V4l2(camera) = &self.backend else {
+            return Err("Replayed images have no controls.".into());
+        };
+        camera.set_control(control.id, &value)
+            .map_err(|e| format!("Failed to set {} to {}: {}", control.name, value, e))?;
 
-        Ok(CameraController { camera })
+        let control = camera.get_control(control.id)?;
+        Ok(CameraControl::from_v4l2(control).ok_or("The control disappeared")?)
     }
 
     /// Captures a single image from the camera.
     ///
     /// # Returns
     ///
-    /// A `Vec<u8>` containing the JPEG image data.
+    /// A `Vec<u8>` containing the image data in the configured pixel format (JPEG for MJPG).
     pub fn capture_image(&mut self) -> Result<Vec<u8>, Box<dyn Error>> {
-        //Capture a frame
-        let frame = self.camera.capture()?;
-
-        //The frame data is in MJPEG format (JPEG)
-        let image_data = frame.to_vec();
+        Ok(self.capture_frame()?.data)
+    }
 
-        Ok(image_data)
+    /// Captures a single frame, with its size and pixel format. The sequence number and
+    /// timestamp are left at zero.
+    pub fn capture_frame(&mut self) -> Result<Frame, Box<dyn Error>> {
+        let camera = match &self.backend {
+            Backend::V4l2(camera) => camera,
+            Backend::Replay(replay) => return replay.next_frame(),
+        };
+        let frame = camera.capture()?;
+        Ok(Frame {
+            sequence: 0,
+            timestamp_us: 0,
+            resolution: frame.resolution,
+            format: frame.format,
+            data: frame.to_vec(),
+        })
     }
 
     /// Stops the camera and releases resources.
     pub fn stop(&mut self) -> Result<(), Box<dyn Error>> {
-        self.camera.stop()?;
+        if let Backend::V4l2(camera) = &mut self.backend {
+            camera.stop()?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Captures from the camera in the slot, failing once it is emptied, so closing the camera
+/// ends the capture rather than opening the camera again.
+impl FrameSource for Mutex<Option<CameraController>> {
+    fn next_frame(&self) -> Result<Frame, Box<dyn Error>> {
+        match self.lock().unwrap().as_mut() {
+            Some(camera) => camera.capture_frame(),
+            None => Err("Camera is closed".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(kind: ControlType, minimum: i64, maximum: i64, step: i64) -> CameraControl {
+        CameraControl {
+            id: rscam::CID_EXPOSURE_AUTO,
+            key: "exposure_auto".to_string(),
+            name: "Auto Exposure".to_string(),
+            kind,
+            value: minimum,
+            default: minimum,
+            minimum,
+            maximum,
+            step,
+            menu: vec![(1, "Manual Mode".to_string()), (3, "Aperture Priority Mode".to_string())],
+            read_only: false,
+            inactive: false,
+        }
+    }
+
+    #[test]
+    fn only_prefixed_devices_are_replayed() {
+        let mut config = CameraConfig::default();
+        assert!(!config.is_replay());
+        config.device = "/home/pi/images".to_string();
+        assert_eq!(config.replay_path(), None);
+        config.device = "replay:/home/pi/images".to_string();
+        assert_eq!(config.replay_path(), Some("/home/pi/images"));
+    }
+
+    #[test]
+    fn closed_camera_is_not_reopened_by_the_capture() {
+        let camera: Mutex<Option<CameraController>> = Mutex::new(None);
+        assert_eq!(camera.next_frame().unwrap_err().to_string(), "Camera is closed");
+        assert!(camera.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn controls_match_by_key_or_driver_name() {
+        let control = control(ControlType::Menu, 1, 3, 1);
+        assert!(control.matches("exposure_auto"));
+        assert!(control.matches("Auto Exposure"));
+        assert!(control.matches("AUTO-EXPOSURE"));
+        assert!(!control.matches("exposure"));
+        assert_eq!(normalize_control_name("White Balance Temperature, Auto"), "white_balance_temperature_auto");
+    }
+
+    #[test]
+    fn values_are_checked_against_the_control() {
+        let menu = control(ControlType::Menu, 1, 3, 1);
+        assert!(menu.check_value(3).is_ok());
+        assert!(menu.check_value(2).is_err());
+
+        let integer = control(ControlType::Integer, 10, 100, 10);
+        assert!(integer.check_value(50).is_ok());
+        assert!(integer.check_value(55).is_err());
+        assert!(integer.check_value(110).is_err());
+
+        let read_only = CameraControl { read_only: true, ..control(ControlType::Boolean, 0, 1, 1) };
+        assert!(read_only.check_value(1).is_err());
+    }
+}
@@ -0,0 +1,520 @@
+use crate::capture::{Frame, FrameCapture};
+use crate::config::*;
+use crate::sensors::{DistanceReading, DistanceStatus, DistanceUnit};
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+//How often the time-lapse thread checks whether it has been stopped between snapshots
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Why a snapshot was taken. Part of the file name and the sidecar.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnapshotTrigger {
+    Manual,
+    Interval,
+    Button(u8),
+    ObstacleZone,
+}
+
+impl SnapshotTrigger {
+    pub fn name(self) -> &'static str {
+        match self {
+            SnapshotTrigger::Manual => "manual",
+            SnapshotTrigger::Interval => "interval",
+            SnapshotTrigger::Button(0) => "button_a",
+            SnapshotTrigger::Button(1) => "button_b",
+            SnapshotTrigger::Button(2) => "button_x",
+            SnapshotTrigger::Button(3) => "button_y",
+            SnapshotTrigger::Button(_) => "button",
+            SnapshotTrigger::ObstacleZone => "obstacle",
+        }
+    }
+}
+
+/// State of the robot recorded next to each snapshot. Parts that are unknown, or busy when
+/// the snapshot is taken, are left out.
+#[derive(Clone, Debug, Default)]
+pub struct RobotState {
+    pub motor_speeds: Option<[f64; NUM_MOTORS]>, //Last speeds set, -1.0 to 1.0
+    pub distance: Option<DistanceReading>,        //Latest ultrasonic reading
+    pub orientation: Option<[f64; 3]>,            //Latest heading, pitch and roll in degrees
+    pub statuses: Vec<&'static str>,              //Statuses shown on the status indicator
+}
+
+/// Gathers the robot state when a snapshot is taken.
+pub type StateProvider = Arc<dyn Fn() -> RobotState + Send + Sync>;
+
+struct Settings {
+    directory: Option<PathBuf>,
+    time_lapse: Option<Arc<AtomicBool>>, //Stop flag of the running time-lapse thread
+    button_mask: u8,                     //Buttons that take a snapshot when pressed, bit 0 = A
+    obstacle_zone_cm: Option<f64>,
+    in_obstacle_zone: bool,
+}
+
+struct Shared {
+    capture: FrameCapture,
+    state: StateProvider,
+    settings: Mutex<Settings>,
+    last_error: Mutex<Option<String>>,
+}
+
+/// Saves frames from the continuous capture to a directory, on a schedule or when a button
+/// is pressed or an obstacle comes within range. Each JPEG is written with a JSON sidecar of
+/// the robot state. Clones share the same settings.
+#[derive(Clone)]
+pub struct CameraSnapshots {
+    shared: Arc<Shared>,
+}
+
+impl CameraSnapshots {
+    pub fn new(capture: FrameCapture, state: StateProvider) -> Self {
+        CameraSnapshots {
+            shared: Arc::new(Shared {
+                capture,
+                state,
+                settings: Mutex::new(Settings {
+                    directory: None,
+                    time_lapse: None,
+                    button_mask: 0,
+                    obstacle_zone_cm: None,
+                    in_obstacle_zone: false,
+                }),
+                last_error: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Sets the directory snapshots are saved to, creating it if needed.
+    pub fn set_directory(&self, directory: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory).map_err(|e| format!("Failed to create {}: {}", directory.display(), e))?;
+        self.shared.settings.lock().unwrap().directory = Some(directory.to_path_buf());
+        Ok(())
+    }
+
+    /// Saves the next frame captured, so the snapshot is not older than the call. Returns
+    /// the path of the JPEG.
+    pub fn take(&self, trigger: SnapshotTrigger) -> Result<PathBuf, Box<dyn Error>> {
+        self.take_with_distance(trigger, None)
+    }
+
+    /// Like `take`, but records `distance` in the sidecar instead of the latest reading.
+    fn take_with_distance(&self, trigger: SnapshotTrigger, distance: Option<DistanceReading>) -> Result<PathBuf, Box<dyn Error>> {
+        let directory = self.shared.settings.lock().unwrap().directory.clone()
+            .ok_or("No snapshot directory set.")?;
+        let latest = self.shared.capture.latest().map(|frame| frame.sequence);
+        let frame = self.shared.capture.wait_for_frame(latest, CAMERA_FRAME_TIMEOUT)
+            .ok_or("No camera frame available. Is the capture running?")?;
+        if !matches!(&frame.format, b"MJPG" | b"JPEG") {
+            return Err(format!("Only MJPG frames can be saved, not {}.", String::from_utf8_lossy(&frame.format)).into());
+        }
+        let mut state = (self.shared.state)();
+        if distance.is_some() {
+            state.distance = distance;
+        }
+        save(&directory, &frame, trigger, &state)
+    }
+
+    /// Takes a snapshot on a thread of its own, so the caller is not held up. Failures are
+    /// kept for `last_error`.
+    pub fn trigger(&self, trigger: SnapshotTrigger) {
+        self.trigger_with_distance(trigger, None);
+    }
+
+    fn trigger_with_distance(&self, trigger: SnapshotTrigger, distance: Option<DistanceReading>) {
+        let snapshots = self.clone();
+        thread::spawn(move || snapshots.take_logged(trigger, distance));
+    }
+
+    fn take_logged(&self, trigger: SnapshotTrigger, distance: Option<DistanceReading>) {
+        if let Err(e) = self.take_with_distance(trigger, distance) {
+            *self.shared.last_error.lock().unwrap() = Some(e.to_string());
+        }
+    }
+
+    /// Returns why the last scheduled or triggered snapshot failed, if one has, and clears it.
+    pub fn last_error(&self) -> Option<String> {
+        self.shared.last_error.lock().unwrap().take()
+    }
+
+    /// Takes a snapshot every `interval` until stopped, replacing any running time-lapse.
+    /// The first is taken straight away. The directory must be set first.
+    pub fn start_time_lapse(&self, interval: Duration) -> Result<(), Box<dyn Error>> {
+        if interval.is_zero() {
+            return Err("The time-lapse interval must be greater than zero.".into());
+        }
+        let stopped = Arc::new(AtomicBool::new(false));
+        {
+            let mut settings = self.shared.settings.lock().unwrap();
+            if settings.directory.is_none() {
+                return Err("No snapshot directory set.".into());
+            }
+            if let Some(running) = settings.time_lapse.replace(Arc::clone(&stopped)) {
+                running.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let snapshots = self.clone();
+        thread::spawn(move || {
+            let mut next = Instant::now();
+            while !stopped.load(Ordering::SeqCst) {
+                let now = Instant::now();
+                if now < next {
+                    thread::sleep((next - now).min(STOP_POLL_INTERVAL));
+                    continue;
+                }
+                snapshots.take_logged(SnapshotTrigger::Interval, None);
+                //Snapshots missed while saving are skipped rather than taken late
+                while next <= Instant::now() {
+                    next += interval;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Stops the time-lapse, if running.
+    pub fn stop_time_lapse(&self) {
+        if let Some(running) = self.shared.settings.lock().unwrap().time_lapse.take() {
+            running.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns `true` while a time-lapse is running.
+    pub fn is_time_lapse_running(&self) -> bool {
+        self.shared.settings.lock().unwrap().time_lapse.is_some()
+    }
+
+    /// Selects the buttons that take a snapshot when pressed, as a bit mask (bit 0 = A,
+    /// 1 = B, 2 = X, 3 = Y).
+    pub fn set_button_triggers(&self, mask: u8) {
+        self.shared.settings.lock().unwrap().button_mask = mask;
+    }
+
+    /// Takes a snapshot if the button is selected.
+    pub fn button_pressed(&self, button: u8) {
+        let mask = self.shared.settings.lock().unwrap().button_mask;
+        if mask & (1 << button) != 0 {
+            self.trigger(SnapshotTrigger::Button(button));
+        }
+    }
+
+    /// Takes a snapshot whenever a distance reading comes within `zone_cm` after being
+    /// outside it, or never if `None`.
+    pub fn set_obstacle_zone(&self, zone_cm: Option<f64>) -> Result<(), Box<dyn Error>> {
+        if let Some(zone) = zone_cm.filter(|zone| !(*zone > 0.0 && zone.is_finite())) {
+            return Err(format!("Invalid obstacle zone: {} cm. Must be greater than zero.", zone).into());
+        }
+        let mut settings = self.shared.settings.lock().unwrap();
+        settings.obstacle_zone_cm = zone_cm;
+        settings.in_obstacle_zone = false;
+        Ok(())
+    }
+
+    /// Checks an ultrasonic reading against the obstacle zone. Readings the sensor could not
+    /// make are ignored. A reading entering the zone is recorded in the snapshot's sidecar.
+    pub fn distance_measured(&self, reading: &DistanceReading) {
+        let mut settings = self.shared.settings.lock().unwrap();
+        let Some(zone) = settings.obstacle_zone_cm else { return };
+        let in_zone = match reading.status {
+            DistanceStatus::Valid => reading.value / reading.unit.convert_cm(1.0) <= zone,
+            DistanceStatus::TooClose => true,
+            DistanceStatus::NoEcho => false,
+            DistanceStatus::Timeout => return,
+        };
+        let entered = in_zone && !settings.in_obstacle_zone;
+        settings.in_obstacle_zone = in_zone;
+        drop(settings);
+
+        if entered {
+            self.trigger_with_distance(SnapshotTrigger::ObstacleZone, Some(*reading));
+        }
+    }
+}
+
+/// Writes the frame as `<UTC time>_<sequence>_<trigger>.jpg`, with a `.json` sidecar of the
+/// same name.
+fn save(directory: &Path, frame: &Frame, trigger: SnapshotTrigger, state: &RobotState) -> Result<PathBuf, Box<dyn Error>> {
+    let name = format!("{}_{}_{}", utc_file_time(frame.timestamp_us), frame.sequence, trigger.name());
+    let image = directory.join(format!("{}.jpg", name));
+    let sidecar = directory.join(format!("{}.json", name));
+
+    fs::write(&image, &frame.data).map_err(|e| format!("Failed to write {}: {}", image.display(), e))?;
+    fs::write(&sidecar, sidecar_json(&format!("{}.jpg", name), frame, trigger, state))
+        .map_err(|e| format!("Failed to write {}: {}", sidecar.display(), e))?;
+    Ok(image)
+}
+
+/// Formats a time in microseconds since the Unix epoch as UTC, e.g. 20240131-235959.123.
+fn utc_file_time(timestamp_us: u64) -> String {
+    let seconds = timestamp_us / 1_000_000;
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    //Civil date from days since 1970-01-01, after Howard Hinnant's days_from_civil inverse
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}.{:03}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        timestamp_us / 1000 % 1000,
+    )
+}
+
+fn sidecar_json(image: &str, frame: &Frame, trigger: SnapshotTrigger, state: &RobotState) -> String {
+    let saved_at_us = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_micros() as u64);
+    let mut json = String::from("{\n");
+    let _ = writeln!(json, "  \"image\": {},", json_string(image));
+    let _ = writeln!(json, "  \"trigger\": \"{}\",", trigger.name());
+    let _ = writeln!(json, "  \"saved_at_us\": {},", saved_at_us);
+    let _ = writeln!(
+        json,
+        "  \"frame\": {{\"sequence\": {}, \"timestamp_us\": {}, \"width\": {}, \"height\": {}, \"format\": {}}},",
+        frame.sequence,
+        frame.timestamp_us,
+        frame.resolution.0,
+        frame.resolution.1,
+        json_string(&String::from_utf8_lossy(&frame.format)),
+    );
+
+    json.push_str("  \"robot\": {\n    \"motor_speeds\": ");
+    match state.motor_speeds {
+        Some([left, right]) => {
+            let _ = write!(json, "{{\"left\": {}, \"right\": {}}}", json_number(left), json_number(right));
+        }
+        None => json.push_str("null"),
+    }
+    json.push_str(",\n    \"distance\": ");
+    match &state.distance {
+        Some(reading) => {
+            let unit = match reading.unit {
+                DistanceUnit::Centimeters => "cm",
+                DistanceUnit::Millimeters => "mm",
+                DistanceUnit::Inches => "in",
+            };
+            let status = match reading.status {
+                DistanceStatus::Valid => "valid",
+                DistanceStatus::TooClose => "too_close",
+                DistanceStatus::NoEcho => "no_echo",
+                DistanceStatus::Timeout => "timeout",
+            };
+            let timestamp_us = reading.timestamp.duration_since(UNIX_EPOCH).map_or(0, |time| time.as_micros() as u64);
+            let _ = write!(
+                json,
+                "{{\"value\": {}, \"unit\": \"{}\", \"status\": \"{}\", \"timestamp_us\": {}}}",
+                json_number(reading.value),
+                unit,
+                status,
+                timestamp_us,
+            );
+        }
+        None => json.push_str("null"),
+    }
+    json.push_str(",\n    \"orientation\": ");
+    match state.orientation {
+        Some([heading, pitch, roll]) => {
+            let _ = write!(
+                json,
+                "{{\"heading\": {}, \"pitch\": {}, \"roll\": {}}}",
+                json_number(heading),
+                json_number(pitch),
+                json_number(roll),
+            );
+        }
+        None => json.push_str("null"),
+    }
+    let statuses: Vec<String> = state.statuses.iter().map(|status| json_string(status)).collect();
+    let _ = write!(json, ",\n    \"statuses\": [{}]\n  }}\n}}\n", statuses.join(", "));
+    json
+}
+
+/// JSON has no NaN or infinity, so they are written as null.
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::fake::FakeCamera;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("swiftbot-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn snapshots(directory: &Path) -> (FrameCapture, CameraSnapshots) {
+        let capture = FrameCapture::new(2);
+        capture.start(Arc::new(FakeCamera::new(vec![b"\xFF\xD8jpeg\xFF\xD9".to_vec()], Duration::from_millis(5))));
+        let state = Arc::new(|| RobotState {
+            motor_speeds: Some([0.5, -0.25]),
+            distance: None,
+            orientation: Some([90.0, f64::NAN, 0.0]),
+            statuses: vec!["moving"],
+        });
+        let snapshots = CameraSnapshots::new(capture.clone(), state);
+        snapshots.set_directory(directory).unwrap();
+        (capture, snapshots)
+    }
+
+    fn reading(value: f64, status: DistanceStatus) -> DistanceReading {
+        DistanceReading {
+            value,
+            unit: DistanceUnit::Millimeters,
+            timestamp: SystemTime::now(),
+            status,
+            pulse_width: Duration::ZERO,
+        }
+    }
+
+    fn files(directory: &Path, extension: &str) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn frame_sequence(json: &str) -> u64 {
+        let start = json.find("\"sequence\": ").unwrap() + "\"sequence\": ".len();
+        let end = start + json[start..].find(',').unwrap();
+        json[start..end].parse().unwrap()
+    }
+
+    #[test]
+    fn triggers_are_named_by_button() {
+        let names: Vec<&str> = (0..5).map(|button| SnapshotTrigger::Button(button).name()).collect();
+        assert_eq!(names, vec!["button_a", "button_b", "button_x", "button_y", "button"]);
+        assert_eq!(SnapshotTrigger::ObstacleZone.name(), "obstacle");
+    }
+
+    #[test]
+    fn snapshot_waits_for_a_frame_newer_than_the_call() {
+        let directory = directory("fresh");
+        let capture = FrameCapture::new(2);
+        capture.start(Arc::new(FakeCamera::new(vec![b"\xFF\xD8jpeg\xFF\xD9".to_vec()], Duration::from_millis(50))));
+        let snapshots = CameraSnapshots::new(capture.clone(), Arc::new(RobotState::default));
+        snapshots.set_directory(&directory).unwrap();
+
+        let buffered = capture.wait_for_frame(None, Duration::from_secs(1)).unwrap().sequence;
+        let image = snapshots.take(SnapshotTrigger::Manual).unwrap();
+        capture.stop();
+        let json = fs::read_to_string(image.with_extension("json")).unwrap();
+        assert!(frame_sequence(&json) > buffered);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn file_times_are_utc() {
+        assert_eq!(utc_file_time(0), "19700101-000000.000");
+        assert_eq!(utc_file_time(951_827_696_789_000), "20000229-123456.789");
+        assert_eq!(utc_file_time(1_704_067_199_999_999), "20231231-235959.999");
+    }
+
+    #[test]
+    fn snapshot_is_saved_with_a_sidecar() {
+        let directory = directory("snapshot");
+        let (capture, snapshots) = snapshots(&directory);
+
+        let image = snapshots.take(SnapshotTrigger::Button(1)).unwrap();
+        capture.stop();
+        assert!(image.file_name().unwrap().to_str().unwrap().ends_with("_button_b.jpg"));
+        assert_eq!(fs::read(&image).unwrap(), b"\xFF\xD8jpeg\xFF\xD9");
+
+        let json = fs::read_to_string(image.with_extension("json")).unwrap();
+        assert!(json.contains("\"trigger\": \"button_b\""));
+        assert!(json.contains("\"motor_speeds\": {\"left\": 0.5, \"right\": -0.25}"));
+        assert!(json.contains("\"orientation\": {\"heading\": 90, \"pitch\": null, \"roll\": 0}"));
+        assert!(json.contains("\"statuses\": [\"moving\"]"));
+        assert!(json.contains("\"distance\": null"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn obstacle_zone_triggers_once_on_entry() {
+        let directory = directory("obstacle");
+        let (capture, snapshots) = snapshots(&directory);
+        snapshots.set_obstacle_zone(Some(20.0)).unwrap();
+
+        //Readings are in mm: outside, inside twice, outside, inside again
+        for value in [300.0, 150.0, 100.0, 400.0] {
+            snapshots.distance_measured(&reading(value, DistanceStatus::Valid));
+            thread::sleep(Duration::from_millis(50));
+        }
+        snapshots.distance_measured(&reading(f64::NAN, DistanceStatus::TooClose));
+        thread::sleep(Duration::from_millis(100));
+        capture.stop();
+
+        assert_eq!(files(&directory, "jpg").len(), 2);
+        assert!(snapshots.last_error().is_none());
+
+        //Each sidecar holds the reading that entered the zone
+        let sidecars: Vec<String> = files(&directory, "json").iter().map(|path| fs::read_to_string(path).unwrap()).collect();
+        assert_eq!(sidecars.len(), 2);
+        assert_eq!(sidecars.iter().filter(|json| json.contains("{\"value\": 150, \"unit\": \"mm\", \"status\": \"valid\"")).count(), 1);
+        assert_eq!(sidecars.iter().filter(|json| json.contains("\"status\": \"too_close\"")).count(), 1);
+        assert!(snapshots.set_obstacle_zone(Some(0.0)).is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn time_lapse_saves_until_stopped() {
+        let directory = directory("timelapse");
+        let (capture, snapshots) = snapshots(&directory);
+
+        snapshots.start_time_lapse(Duration::from_millis(60)).unwrap();
+        assert!(snapshots.is_time_lapse_running());
+        thread::sleep(Duration::from_millis(200));
+        snapshots.stop_time_lapse();
+        assert!(!snapshots.is_time_lapse_running());
+        thread::sleep(Duration::from_millis(150));
+        let saved = files(&directory, "jpg").len();
+        capture.stop();
+
+        assert!((2..=5).contains(&saved), "{} snapshots", saved);
+        assert!(files(&directory, "jpg").iter().all(|path| path.to_str().unwrap().ends_with("_interval.jpg")));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
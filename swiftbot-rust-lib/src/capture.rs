@@ -0,0 +1,319 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A frame captured from the camera.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub sequence: u64,          //Increases by one for each frame captured, across restarts
+    pub timestamp_us: u64,      //Capture time in microseconds since the Unix epoch
+    pub resolution: (u32, u32), //Width and height in pixels
+    pub format: [u8; 4],        //FourCC pixel format, e.g. *b"MJPG"
+    pub data: Vec<u8>,
+}
+
+/// Anything frames can be captured from, so the capture thread can run against a fake
+/// camera in tests.
+pub trait FrameSource: Send + Sync {
+    /// Blocks until the next frame is available. The sequence number and timestamp are
+    /// filled in by the capture thread.
+    fn next_frame(&self) -> Result<Frame, Box<dyn Error>>;
+}
+
+/// Called on the capture thread with each new frame. Must return quickly, or frames are
+/// delayed for every other subscriber.
+pub type FrameCallback = Arc<dyn Fn(&Arc<Frame>) + Send + Sync>;
+
+struct State {
+    frames: VecDeque<Arc<Frame>>, //Latest frames, oldest first
+    next_sequence: u64,
+    generation: u64,              //Incremented on each start and stop, so a stale thread can tell it should exit
+    running: bool,
+    error: Option<String>,        //Why the capture last stopped by itself
+}
+
+struct Subscribers {
+    callbacks: BTreeMap<u32, FrameCallback>,
+    next_id: u32,
+}
+
+struct Shared {
+    capacity: usize,
+    state: Mutex<State>,
+    new_frame: Condvar,
+    subscribers: Mutex<Subscribers>,
+}
+
+/// Captures frames continuously on a background thread, keeping the latest few in a ring
+/// buffer and passing each to the subscribers as it arrives. Clones share the same capture.
+#[derive(Clone)]
+pub struct FrameCapture {
+    shared: Arc<Shared>,
+}
+
+impl FrameCapture {
+    /// Creates a stopped capture keeping the latest `capacity` frames.
+    pub fn new(capacity: usize) -> Self {
+        FrameCapture {
+            shared: Arc::new(Shared {
+                capacity: capacity.max(1),
+                state: Mutex::new(State {
+                    frames: VecDeque::new(),
+                    next_sequence: 0,
+                    generation: 0,
+                    running: false,
+                    error: None,
+                }),
+                new_frame: Condvar::new(),
+                subscribers: Mutex::new(Subscribers {
+                    callbacks: BTreeMap::new(),
+                    next_id: 0,
+                }),
+            }),
+        }
+    }
+
+    /// Starts capturing from `source`, dropping the frames buffered by an earlier capture.
+    /// Does nothing if already running.
+    pub fn start(&self, source: Arc<dyn FrameSource>) {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.running {
+            return;
+        }
+        state.frames.clear();
+        state.running = true;
+        state.generation += 1;
+        state.error = None;
+
+        let generation = state.generation;
+        let shared = Arc::clone(&self.shared);
+        thread::spawn(move || run(&shared, source.as_ref(), generation));
+    }
+
+    /// Stops capturing. The buffered frames are kept until the next start. A frame being
+    /// captured when this is called is dropped.
+    pub fn stop(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.running {
+            state.running = false;
+            state.generation += 1;
+        }
+        self.shared.new_frame.notify_all();
+    }
+
+    /// Returns `true` while the capture thread is running.
+    pub fn is_running(&self) -> bool {
+        self.shared.state.lock().unwrap().running
+    }
+
+    /// Returns the error that stopped the capture, if it stopped by itself.
+    pub fn error(&self) -> Option<String> {
+        self.shared.state.lock().unwrap().error.clone()
+    }
+
+    /// Returns the latest frame without waiting, if any has been captured.
+    pub fn latest(&self) -> Option<Arc<Frame>> {
+        self.shared.state.lock().unwrap().frames.back().cloned()
+    }
+
+    /// Returns the buffered frames, oldest first.
+    pub fn frames(&self) -> Vec<Arc<Frame>> {
+        self.shared.state.lock().unwrap().frames.iter().cloned().collect()
+    }
+
+    /// Waits for a frame newer than `after` (a sequence number), or for any frame if `None`.
+    /// Returns the latest frame, or `None` if the capture stops or `timeout` elapses first.
+    pub fn wait_for_frame(&self, after: Option<u64>, timeout: Duration) -> Option<Arc<Frame>> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(frame) = state.frames.back() {
+                if after.is_none_or(|after| frame.sequence > after) {
+                    return Some(Arc::clone(frame));
+                }
+            }
+            let now = Instant::now();
+            if !state.running || now >= deadline {
+                return None;
+            }
+            state = self.shared.new_frame.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Calls `callback` with each new frame until unsubscribed, and returns the subscription ID.
+    pub fn subscribe(&self, callback: impl Fn(&Arc<Frame>) + Send + Sync + 'static) -> u32 {
+        let mut subscribers = self.shared.subscribers.lock().unwrap();
+        let id = subscribers.next_id;
+        subscribers.next_id += 1;
+        subscribers.callbacks.insert(id, Arc::new(callback));
+        id
+    }
+
+    /// Removes a subscription. Returns `false` if there is no subscription with that ID.
+    pub fn unsubscribe(&self, id: u32) -> bool {
+        self.shared.subscribers.lock().unwrap().callbacks.remove(&id).is_some()
+    }
+}
+
+fn run(shared: &Shared, source: &dyn FrameSource, generation: u64) {
+    loop {
+        let result = source.next_frame();
+
+        let frame = {
+            let mut state = shared.state.lock().unwrap();
+            if state.generation != generation {
+                return;
+            }
+            match result {
+                Ok(mut frame) => {
+                    frame.sequence = state.next_sequence;
+                    frame.timestamp_us = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |time| time.as_micros() as u64);
+                    state.next_sequence += 1;
+
+                    let frame = Arc::new(frame);
+                    if state.frames.len() == shared.capacity {
+                        state.frames.pop_front();
+                    }
+                    state.frames.push_back(Arc::clone(&frame));
+                    frame
+                }
+                Err(e) => {
+                    state.running = false;
+                    state.error = Some(e.to_string());
+                    shared.new_frame.notify_all();
+                    return;
+                }
+            }
+        };
+        shared.new_frame.notify_all();
+
+        //Callbacks are called without the lock held, so they can unsubscribe themselves
+        let callbacks: Vec<FrameCallback> = shared.subscribers.lock().unwrap().callbacks.values().cloned().collect();
+        for callback in callbacks {
+            callback(&frame);
+        }
+    }
+}
+
+/// Fake camera cycling through a fixed set of frames at a steady rate.
+#[cfg(test)]
+pub mod fake {
+    use super::{Frame, FrameSource};
+    use std::error::Error;
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+
+    pub struct FakeCamera {
+        images: Vec<Vec<u8>>,
+        interval: Duration,
+        next: Mutex<usize>,
+    }
+
+    impl FakeCamera {
+        pub fn new(images: Vec<Vec<u8>>, interval: Duration) -> Self {
+            FakeCamera { images, interval, next: Mutex::new(0) }
+        }
+    }
+
+    impl FrameSource for FakeCamera {
+        fn next_frame(&self) -> Result<Frame, Box<dyn Error>> {
+            thread::sleep(self.interval);
+            let mut next = self.next.lock().unwrap();
+            if self.images.is_empty() {
+                return Err("No images".into());
+            }
+            let data = self.images[*next % self.images.len()].clone();
+            *next += 1;
+            Ok(Frame { sequence: 0, timestamp_us: 0, resolution: (2, 2), format: *b"MJPG", data })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::FakeCamera;
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn camera() -> Arc<dyn FrameSource> {
+        Arc::new(FakeCamera::new(vec![vec![1], vec![2], vec![3]], Duration::from_millis(2)))
+    }
+
+    #[test]
+    fn ring_buffer_keeps_the_latest_frames() {
+        let capture = FrameCapture::new(4);
+        assert!(capture.latest().is_none());
+
+        capture.start(camera());
+        let mut last = None;
+        while last.as_ref().is_none_or(|frame: &Arc<Frame>| frame.sequence < 10) {
+            last = capture.wait_for_frame(last.map(|frame| frame.sequence), Duration::from_secs(1));
+            assert!(last.is_some());
+        }
+        capture.stop();
+        assert!(!capture.is_running());
+
+        let frames = capture.frames();
+        assert_eq!(frames.len(), 4);
+        for pair in frames.windows(2) {
+            assert_eq!(pair[1].sequence, pair[0].sequence + 1);
+            assert!(pair[1].timestamp_us >= pair[0].timestamp_us);
+        }
+        assert_eq!(capture.latest().unwrap().sequence, frames[3].sequence);
+    }
+
+    #[test]
+    fn subscribers_get_every_frame_until_unsubscribed() {
+        let capture = FrameCapture::new(2);
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&count);
+        let id = capture.subscribe(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        capture.start(camera());
+        let mut last = None;
+        for _ in 0..5 {
+            last = capture.wait_for_frame(last, Duration::from_secs(1)).map(|frame| frame.sequence);
+        }
+        assert!(capture.unsubscribe(id));
+        assert!(!capture.unsubscribe(id));
+        let seen = count.load(Ordering::SeqCst);
+        assert!(seen >= 4);
+
+        //A frame already being delivered when unsubscribing may still arrive
+        thread::sleep(Duration::from_millis(20));
+        capture.stop();
+        assert!(count.load(Ordering::SeqCst) <= seen + 1);
+    }
+
+    #[test]
+    fn restarting_drops_the_old_frames() {
+        let capture = FrameCapture::new(4);
+        capture.start(camera());
+        let first = capture.wait_for_frame(None, Duration::from_secs(1)).unwrap();
+        capture.stop();
+        assert!(capture.latest().is_some());
+
+        capture.start(Arc::new(FakeCamera::new(vec![vec![9]], Duration::from_millis(50))));
+        assert!(capture.latest().is_none());
+        let frame = capture.wait_for_frame(None, Duration::from_secs(1)).unwrap();
+        capture.stop();
+        assert_eq!(frame.data, vec![9]);
+        assert!(frame.sequence > first.sequence);
+    }
+
+    #[test]
+    fn capture_stops_on_source_error() {
+        let capture = FrameCapture::new(2);
+        capture.start(Arc::new(FakeCamera::new(Vec::new(), Duration::ZERO)));
+        assert!(capture.wait_for_frame(None, Duration::from_secs(1)).is_none());
+        assert!(!capture.is_running());
+        assert_eq!(capture.error().as_deref(), Some("No images"));
+    }
+}
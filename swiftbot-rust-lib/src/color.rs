@@ -0,0 +1,123 @@
+use std::error::Error;
+
+/// An 8-bit RGB color.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+
+    /// Unpacks a color stored as `0xRRGGBB`.
+    pub fn from_packed(value: u32) -> Self {
+        Color::rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)
+    }
+
+    /// Packs the color as `0xRRGGBB`.
+    pub fn packed(self) -> u32 {
+        (self.r as u32) << 16 | (self.g as u32) << 8 | self.b as u32
+    }
+
+    /// Creates a color from hue (degrees), saturation (0.0 to 1.0) and value (0.0 to 1.0).
+    pub fn from_hsv(hue: f64, saturation: f64, value: f64) -> Self {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let value = value.clamp(0.0, 1.0);
+        let chroma = value * saturation;
+        Self::from_chroma(hue, chroma, value - chroma)
+    }
+
+    /// Parses `#RRGGBB`, `RRGGBB`, `#RGB` or `RGB`.
+    pub fn from_hex(hex: &str) -> Result<Self, Box<dyn Error>> {
+        let digits = hex.trim().trim_start_matches('#');
+        let invalid = || format!("Invalid hex color: '{}'. Expected #RRGGBB or #RGB.", hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid().into());
+        }
+
+        match digits.len() {
+            6 => Ok(Color::from_packed(u32::from_str_radix(digits, 16)?)),
+            3 => {
+                //Each digit is repeated, so #F80 is #FF8800
+                let value = u32::from_str_radix(digits, 16)?;
+                let expand = |nibble: u32| (nibble & 0xF) as u8 * 0x11;
+                Ok(Color::rgb(expand(value >> 8), expand(value >> 4), expand(value)))
+            }
+            _ => Err(invalid().into()),
+        }
+    }
+
+    /// Scales every component by `level` (0.0 to 1.0).
+    pub fn scale(self, level: f64) -> Self {
+        let level = level.clamp(0.0, 1.0);
+        let channel = |value: u8| (value as f64 * level).round() as u8;
+        Color::rgb(channel(self.r), channel(self.g), channel(self.b))
+    }
+
+    /// Mixes towards `other` by `t` (0.0 gives `self`, 1.0 gives `other`).
+    pub fn blend(self, other: Color, t: f64) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let channel = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Color::rgb(channel(self.r, other.r), channel(self.g, other.g), channel(self.b, other.b))
+    }
+
+    /// Builds a color from its chroma and the amount added to every component.
+    fn from_chroma(hue: f64, chroma: f64, offset: f64) -> Self {
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let channel = |value: f64| ((value + offset) * 255.0).round().clamp(0.0, 255.0) as u8;
+        Color::rgb(channel(r), channel(g), channel(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors_parse_with_and_without_hash() {
+        assert_eq!(Color::from_hex("#FF8000").unwrap(), Color::rgb(255, 128, 0));
+        assert_eq!(Color::from_hex("ff8000").unwrap(), Color::rgb(255, 128, 0));
+        assert_eq!(Color::from_hex(" #F80 ").unwrap(), Color::rgb(255, 136, 0));
+        assert_eq!(Color::from_hex("0a5").unwrap(), Color::rgb(0, 170, 85));
+
+        for invalid in ["", "#", "#FF80", "#FF80001", "#GG0000", "+F80", "#-F0"] {
+            assert!(Color::from_hex(invalid).is_err(), "{} should be rejected", invalid);
+        }
+    }
+
+    #[test]
+    fn hsv_hits_the_primaries_at_sector_boundaries() {
+        let expected = [
+            (0.0, Color::rgb(255, 0, 0)),
+            (60.0, Color::rgb(255, 255, 0)),
+            (120.0, Color::rgb(0, 255, 0)),
+            (180.0, Color::rgb(0, 255, 255)),
+            (240.0, Color::rgb(0, 0, 255)),
+            (300.0, Color::rgb(255, 0, 255)),
+            (360.0, Color::rgb(255, 0, 0)),
+            (-60.0, Color::rgb(255, 0, 255)),
+        ];
+        for (hue, color) in expected {
+            assert_eq!(Color::from_hsv(hue, 1.0, 1.0), color, "hue {}", hue);
+        }
+
+        assert_eq!(Color::from_hsv(30.0, 1.0, 1.0), Color::rgb(255, 128, 0));
+        assert_eq!(Color::from_hsv(90.0, 0.0, 0.5), Color::rgb(128, 128, 128));
+        assert_eq!(Color::from_hsv(200.0, 2.0, -1.0), Color::BLACK);
+    }
+}
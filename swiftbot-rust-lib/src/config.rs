@@ -1,3 +1,8 @@
+use crate::imu::ImuConfig;
+use crate::sensor_registry::{DigitalSensorConfig, I2cDeviceConfig};
+use crate::sn3218::{ChannelOrder, LedLayout};
+use std::time::Duration;
+
 //Button IDs
 pub const BUTTON_A: u8 = 0;
 pub const BUTTON_B: u8 = 1;
@@ -14,6 +19,17 @@ pub const LIGHT_REAR_RIGHT: u8 = 4;
 pub const LIGHT_MIDDLE_RIGHT: u8 = 5;
 pub const NUM_UNDERLIGHTS: usize = 6;
 
+//SN3218 channels driving each underlight, indexed by the IDs above. Adjust for board
+//revisions wired differently, e.g. LedLayout { first_channel: 3, order: ChannelOrder::Grb }
+pub const UNDERLIGHT_LAYOUT: [LedLayout; NUM_UNDERLIGHTS] = [
+    LedLayout { first_channel: 0, order: ChannelOrder::Rgb },  //Front right
+    LedLayout { first_channel: 3, order: ChannelOrder::Rgb },  //Front left
+    LedLayout { first_channel: 6, order: ChannelOrder::Rgb },  //Middle left
+    LedLayout { first_channel: 9, order: ChannelOrder::Rgb },  //Rear left
+    LedLayout { first_channel: 12, order: ChannelOrder::Rgb }, //Rear right
+    LedLayout { first_channel: 15, order: ChannelOrder::Rgb }, //Middle right
+];
+
 //Motor names
 pub const MOTOR_LEFT: u8 = 0;
 pub const MOTOR_RIGHT: u8 = 1;
@@ -38,9 +54,67 @@ pub const MOTOR_LEFT_N: u8 = 11;
 pub const MOTOR_RIGHT_P: u8 = 10;
 pub const MOTOR_RIGHT_N: u8 = 9;
 
+//I2C bus pins, shared by the SN3218 LED driver and the IMU
+pub const I2C_SDA_PIN: u8 = 2;
+pub const I2C_SCL_PIN: u8 = 3;
+
 //HC-SR04 Ultrasound pins
 pub const ULTRA_TRIG_PIN: u8 = 13;
 pub const ULTRA_ECHO_PIN: u8 = 25;
 
 //Speed of sound in cm/ns
-pub const SPEED_OF_SOUND_CM_NS: f64 = 343.0 * 100.0 / 1e9;
\ No newline at end of file
+pub const SPEED_OF_SOUND_CM_NS: f64 = 343.0 * 100.0 / 1e9;
+
+//Valid HC-SR04 measurement range in cm
+pub const ULTRA_MIN_DISTANCE_CM: f64 = 2.0;
+pub const ULTRA_MAX_DISTANCE_CM: f64 = 400.0;
+
+//Additional sensors registered at startup, e.g.
+//DigitalSensorConfig { name: "bump_left", pin: 4, pull: InputPull::Up, active_low: true },
+//I2cDeviceConfig { name: "imu", bus: 1, address: 0x68 },
+pub const EXTRA_DIGITAL_SENSORS: &[DigitalSensorConfig] = &[];
+pub const EXTRA_I2C_DEVICES: &[I2cDeviceConfig] = &[];
+
+//Optional IMU used for heading and tilt, e.g.
+//Some(ImuConfig { kind: ImuKind::Mpu6050, bus: 1, address: 0x68 })
+pub const IMU_DEVICE: Option<ImuConfig> = None;
+
+//Approximate turn rate on the spot at full speed, used to time turns when no IMU is present
+pub const TURN_RATE_DEG_PER_S: f64 = 300.0;
+
+//Underlight color correction: gamma (1.0 leaves values as set), per-component white balance gains and
+//overall brightness limit (0.0 to 1.0)
+pub const UNDERLIGHT_GAMMA: f64 = 1.0;
+pub const UNDERLIGHT_WHITE_BALANCE: [f64; 3] = [1.0, 1.0, 1.0];
+pub const UNDERLIGHT_BRIGHTNESS_LIMIT: f64 = 1.0;
+
+//Duration of the button LED flash shown by the flash-on-press feedback mode
+pub const BUTTON_FLASH_DURATION: Duration = Duration::from_millis(150);
+
+//Estimated LED current at full brightness, used by the LED power budget
+pub const UNDERLIGHT_CHANNEL_MA: f64 = 20.0;
+pub const BUTTON_LED_MA: f64 = 10.0;
+
+//How long the LED acceleration power limit stays on after a motor speeds up
+pub const MOTOR_ACCELERATION_WINDOW: Duration = Duration::from_millis(500);
+
+//Camera opened when no other configuration is given: device, resolution, FourCC pixel format
+//and frame interval in seconds, e.g. (1, 30) for 30 fps
+pub const CAMERA_DEVICE: &str = "/dev/video0";
+pub const CAMERA_RESOLUTION: (u32, u32) = (640, 480);
+pub const CAMERA_FORMAT: [u8; 4] = *b"MJPG";
+pub const CAMERA_INTERVAL: (u32, u32) = (1, 30);
+
+//Camera device prefix that replays images from the path after it instead of opening a camera
+pub const CAMERA_REPLAY_PREFIX: &str = "replay:";
+
+//Frames kept by the continuous camera capture, and how long to wait for a frame before giving up
+pub const CAMERA_BUFFER_FRAMES: usize = 8;
+pub const CAMERA_FRAME_TIMEOUT: Duration = Duration::from_secs(2);
+
+//Largest video recording file; AVI files cannot exceed 4 GiB and many players stop at 1 GiB
+pub const RECORDING_MAX_FILE_BYTES: u64 = 1 << 30;
+
+//Most viewers watching the HTTP camera stream at once, each holding a connection and a thread
+#[cfg(feature = "http-stream")]
+pub const CAMERA_STREAM_MAX_CLIENTS: usize = 4;
@@ -0,0 +1,256 @@
+use crate::capture::Frame;
+use jpeg_decoder::{Decoder, PixelFormat as JpegFormat};
+use std::error::Error;
+
+/// Pixel layout of a decoded frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelFormat {
+    Rgb24, //Three bytes per pixel: red, green, blue
+    Gray8, //One byte per pixel: luma
+}
+
+impl PixelFormat {
+    /// Maps the integer IDs used across JNI (0 = RGB24, 1 = 8-bit grayscale).
+    pub fn from_id(id: i32) -> Result<Self, Box<dyn Error>> {
+        match id {
+            0 => Ok(PixelFormat::Rgb24),
+            1 => Ok(PixelFormat::Gray8),
+            _ => Err(format!("Invalid pixel format ID: {}. Must be 0 (RGB) or 1 (grayscale).", id).into()),
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb24 => 3,
+            PixelFormat::Gray8 => 1,
+        }
+    }
+}
+
+/// A frame decoded to pixels. Rows are `stride` bytes apart, top row first.
+#[derive(Clone, Debug)]
+pub struct DecodedFrame {
+    pub sequence: u64,     //Sequence number of the frame it was decoded from
+    pub timestamp_us: u64, //Capture time in microseconds since the Unix epoch
+    pub width: u32,
+    pub height: u32,
+    pub stride: usize,
+    pub format: PixelFormat,
+    pub pixels: Vec<u8>,
+}
+
+/// An RGB24 image before scaling and conversion.
+struct RgbImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    target: Option<(u32, u32)>, //Size to shrink it to, if smaller
+}
+
+/// Decodes an MJPG or YUYV frame, optionally shrinking it to fit within `max_size` while
+/// keeping its aspect ratio. A zero width or height in `max_size` means no limit on it.
+/// The size of a JPEG is read from the image itself.
+pub fn decode_frame(frame: &Frame, format: PixelFormat, max_size: Option<(u32, u32)>) -> Result<DecodedFrame, Box<dyn Error>> {
+    let image = match &frame.format {
+        b"MJPG" | b"JPEG" => decode_jpeg(&frame.data, max_size)?,
+        b"YUYV" => {
+            let (width, height) = frame.resolution;
+            let target = max_size.map(|(max_width, max_height)| fit((width, height), max_width, max_height));
+            RgbImage { width, height, pixels: yuyv_to_rgb(&frame.data, width, height)?, target }
+        }
+        other => {
+            return Err(format!("Cannot decode {} frames. Use the MJPG or YUYV format.", String::from_utf8_lossy(other)).into());
+        }
+    };
+
+    let RgbImage { width, height, pixels: rgb, target } = image;
+    let (width, height, rgb) = match target {
+        Some((target_width, target_height)) if (target_width, target_height) != (width, height) => {
+            (target_width, target_height, downscale(&rgb, width, height, target_width, target_height))
+        }
+        _ => (width, height, rgb),
+    };
+
+    let pixels = match format {
+        PixelFormat::Rgb24 => rgb,
+        PixelFormat::Gray8 => rgb.chunks_exact(3).map(|pixel| luma(pixel[0], pixel[1], pixel[2])).collect(),
+    };
+
+    Ok(DecodedFrame {
+        sequence: frame.sequence,
+        timestamp_us: frame.timestamp_us,
+        width,
+        height,
+        stride: width as usize * format.bytes_per_pixel(),
+        format,
+        pixels,
+    })
+}
+
+/// Largest size within the limits with the same aspect ratio, never larger than the frame.
+fn fit((width, height): (u32, u32), max_width: u32, max_height: u32) -> (u32, u32) {
+    let mut scale: f64 = 1.0;
+    if max_width > 0 {
+        scale = scale.min(max_width as f64 / width as f64);
+    }
+    if max_height > 0 {
+        scale = scale.min(max_height as f64 / height as f64);
+    }
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// Decodes a JPEG to RGB24, along with the size it should be shrunk to, if any.
+/// When a smaller size is wanted, the decoder's own scaling (down to 1/8) is used first,
+/// as it is much cheaper than decoding at full size.
+fn decode_jpeg(data: &[u8], max_size: Option<(u32, u32)>) -> Result<RgbImage, Box<dyn Error>> {
+    let mut decoder = Decoder::new(data);
+    decoder.read_info().map_err(|e| format!("Failed to decode JPEG frame: {}", e))?;
+    let info = decoder.info().ok_or("Failed to decode JPEG frame: no image information")?;
+
+    let target = max_size.map(|(max_width, max_height)| fit((info.width as u32, info.height as u32), max_width, max_height));
+    if let Some((width, height)) = target {
+        //Both fit in u16, as they are no larger than the JPEG
+        decoder.scale(width as u16, height as u16)
+            .map_err(|e| format!("Failed to decode JPEG frame: {}", e))?;
+    }
+    let pixels = decoder.decode().map_err(|e| format!("Failed to decode JPEG frame: {}", e))?;
+    let info = decoder.info().ok_or("Failed to decode JPEG frame: no image information")?;
+
+    let rgb = match info.pixel_format {
+        JpegFormat::RGB24 => pixels,
+        JpegFormat::L8 => pixels.iter().flat_map(|value| [*value; 3]).collect(),
+        other => return Err(format!("Unsupported JPEG pixel format: {:?}", other).into()),
+    };
+    Ok(RgbImage { width: info.width as u32, height: info.height as u32, pixels: rgb, target })
+}
+
+/// Converts packed YUYV 4:2:2 (two pixels in Y0 U Y1 V) to RGB24, with BT.601 coefficients.
+fn yuyv_to_rgb(data: &[u8], width: u32, height: u32) -> Result<Vec<u8>, Box<dyn Error>> {
+    let expected = width as usize * height as usize * 2;
+    if data.len() < expected || !width.is_multiple_of(2) {
+        return Err(format!("Invalid YUYV frame: {} bytes for {}x{}.", data.len(), width, height).into());
+    }
+
+    let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+    for chunk in data[..expected].chunks_exact(4) {
+        let (u, v) = (chunk[1] as f32 - 128.0, chunk[3] as f32 - 128.0);
+        for y in [chunk[0], chunk[2]] {
+            let y = y as f32;
+            rgb.push((y + 1.402 * v).round().clamp(0.0, 255.0) as u8);
+            rgb.push((y - 0.344 * u - 0.714 * v).round().clamp(0.0, 255.0) as u8);
+            rgb.push((y + 1.772 * u).round().clamp(0.0, 255.0) as u8);
+        }
+    }
+    Ok(rgb)
+}
+
+/// Shrinks an RGB24 image by averaging the source pixels covered by each output pixel.
+fn downscale(rgb: &[u8], width: u32, height: u32, new_width: u32, new_height: u32) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let (new_width, new_height) = (new_width as usize, new_height as usize);
+    let mut output = Vec::with_capacity(new_width * new_height * 3);
+
+    for y in 0..new_height {
+        let (top, bottom) = (y * height / new_height, ((y + 1) * height / new_height).max(y * height / new_height + 1));
+        for x in 0..new_width {
+            let (left, right) = (x * width / new_width, ((x + 1) * width / new_width).max(x * width / new_width + 1));
+            let mut sum = [0u32; 3];
+            for row in top..bottom {
+                for column in left..right {
+                    let i = (row * width + column) * 3;
+                    for (total, value) in sum.iter_mut().zip(&rgb[i..i + 3]) {
+                        *total += *value as u32;
+                    }
+                }
+            }
+            let count = ((bottom - top) * (right - left)) as u32;
+            output.extend(sum.iter().map(|total| ((total + count / 2) / count) as u8));
+        }
+    }
+    output
+}
+
+/// BT.601 luma, in integer arithmetic.
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((77 * r as u32 + 150 * g as u32 + 29 * b as u32 + 128) >> 8) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jpeg_encoder::{ColorType, Encoder};
+
+    fn jpeg(width: u16, height: u16, color: [u8; 3]) -> Vec<u8> {
+        let pixels: Vec<u8> = (0..width as usize * height as usize).flat_map(|_| color).collect();
+        let mut data = Vec::new();
+        Encoder::new(&mut data, 100).encode(&pixels, width, height, ColorType::Rgb).unwrap();
+        data
+    }
+
+    fn frame(format: &[u8; 4], resolution: (u32, u32), data: Vec<u8>) -> Frame {
+        Frame { sequence: 7, timestamp_us: 1234, resolution, format: *format, data }
+    }
+
+    fn assert_close(actual: &[u8], expected: &[u8]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((*a as i32 - *e as i32).abs() <= 3, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn jpeg_frames_decode_to_rgb_and_gray() {
+        let frame = frame(b"MJPG", (32, 16), jpeg(32, 16, [200, 40, 80]));
+
+        let rgb = decode_frame(&frame, PixelFormat::Rgb24, None).unwrap();
+        assert_eq!((rgb.width, rgb.height, rgb.stride), (32, 16, 96));
+        assert_eq!((rgb.sequence, rgb.timestamp_us), (7, 1234));
+        assert_eq!(rgb.pixels.len(), 32 * 16 * 3);
+        assert_close(&rgb.pixels[..3], &[200, 40, 80]);
+
+        let gray = decode_frame(&frame, PixelFormat::Gray8, None).unwrap();
+        assert_eq!((gray.width, gray.height, gray.stride), (32, 16, 32));
+        assert_close(&gray.pixels[..1], &[luma(200, 40, 80)]);
+    }
+
+    #[test]
+    fn frames_are_downscaled_keeping_the_aspect_ratio() {
+        let frame = frame(b"MJPG", (64, 32), jpeg(64, 32, [10, 200, 30]));
+
+        let small = decode_frame(&frame, PixelFormat::Rgb24, Some((20, 0))).unwrap();
+        assert_eq!((small.width, small.height), (20, 10));
+        assert_eq!(small.pixels.len(), 20 * 10 * 3);
+        assert_close(&small.pixels[..3], &[10, 200, 30]);
+
+        //Never scaled up
+        let same = decode_frame(&frame, PixelFormat::Gray8, Some((640, 480))).unwrap();
+        assert_eq!((same.width, same.height), (64, 32));
+
+        //The size of a JPEG comes from the image, not the frame
+        let unsized_frame = Frame { resolution: (0, 0), ..frame };
+        let small = decode_frame(&unsized_frame, PixelFormat::Rgb24, Some((0, 8))).unwrap();
+        assert_eq!((small.width, small.height), (16, 8));
+    }
+
+    #[test]
+    fn yuyv_frames_are_converted() {
+        //Two pixels: white and black, with neutral chroma
+        let frame = frame(b"YUYV", (2, 1), vec![255, 128, 0, 128]);
+        let rgb = decode_frame(&frame, PixelFormat::Rgb24, None).unwrap();
+        assert_eq!(rgb.pixels, vec![255, 255, 255, 0, 0, 0]);
+
+        let short = Frame { data: vec![0; 3], ..frame.clone() };
+        assert!(decode_frame(&short, PixelFormat::Rgb24, None).is_err());
+        let unsupported = Frame { format: *b"H264", ..frame };
+        assert!(decode_frame(&unsupported, PixelFormat::Rgb24, None).is_err());
+    }
+
+    #[test]
+    fn downscale_averages_pixels() {
+        let rgb = [0, 0, 0, 255, 255, 255, 100, 100, 100, 100, 100, 100];
+        assert_eq!(downscale(&rgb, 2, 2, 1, 1), vec![114, 114, 114]);
+        assert_eq!(downscale(&rgb, 2, 2, 2, 1), vec![50, 50, 50, 178, 178, 178]);
+    }
+}
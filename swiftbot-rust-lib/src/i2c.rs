@@ -0,0 +1,99 @@
+use rppal::i2c::I2c;
+use std::error::Error;
+
+/// Minimal I2C transport used by the device drivers, so they can run against a fake bus in tests.
+pub trait I2cBus: Send {
+    /// Writes `data` to the device in a single transaction.
+    fn write(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>>;
+
+    /// Writes `data`, then reads `buffer.len()` bytes back using a repeated start.
+    fn write_read(&mut self, data: &[u8], buffer: &mut [u8]) -> Result<(), Box<dyn Error>>;
+}
+
+impl I2cBus for I2c {
+    fn write(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        I2c::write(self, data)?;
+        Ok(())
+    }
+
+    fn write_read(&mut self, data: &[u8], buffer: &mut [u8]) -> Result<(), Box<dyn Error>> {
+        I2c::write_read(self, data, buffer)?;
+        Ok(())
+    }
+}
+
+/// Register-level fake of an I2C device with an auto-incrementing register pointer.
+#[cfg(test)]
+pub mod fake {
+    use super::I2cBus;
+    use std::error::Error;
+    use std::sync::{Arc, Mutex};
+
+    struct State {
+        registers: [u8; 256],
+        writes: Vec<Vec<u8>>, //Every write transaction, in order
+    }
+
+    /// Clones share the same register file, so a test can keep a handle after
+    /// moving the bus into a driver.
+    #[derive(Clone)]
+    pub struct FakeI2c {
+        state: Arc<Mutex<State>>,
+    }
+
+    impl FakeI2c {
+        pub fn new() -> Self {
+            FakeI2c {
+                state: Arc::new(Mutex::new(State {
+                    registers: [0u8; 256],
+                    writes: Vec::new(),
+                })),
+            }
+        }
+
+        /// Returns the value of a register.
+        pub fn register(&self, register: u8) -> u8 {
+            self.state.lock().unwrap().registers[register as usize]
+        }
+
+        /// Returns every write transaction so far, in order.
+        pub fn writes(&self) -> Vec<Vec<u8>> {
+            self.state.lock().unwrap().writes.clone()
+        }
+
+        /// Forgets the write transactions so far.
+        pub fn clear_writes(&self) {
+            self.state.lock().unwrap().writes.clear();
+        }
+
+        /// Presets consecutive registers, starting at `register`.
+        pub fn set_registers(&self, register: u8, values: &[u8]) {
+            let mut state = self.state.lock().unwrap();
+            for (i, value) in values.iter().enumerate() {
+                state.registers[(register as usize + i) % 256] = *value;
+            }
+        }
+    }
+
+    impl I2cBus for FakeI2c {
+        fn write(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+            let mut state = self.state.lock().unwrap();
+            state.writes.push(data.to_vec());
+            if let Some((&register, values)) = data.split_first() {
+                for (i, value) in values.iter().enumerate() {
+                    state.registers[(register as usize + i) % 256] = *value;
+                }
+            }
+            Ok(())
+        }
+
+        fn write_read(&mut self, data: &[u8], buffer: &mut [u8]) -> Result<(), Box<dyn Error>> {
+            let register = *data.first().ok_or("Register address missing")? as usize;
+            let state = self.state.lock().unwrap();
+            for (i, value) in buffer.iter_mut().enumerate() {
+                *value = state.registers[(register + i) % 256];
+            }
+            Ok(())
+        }
+    }
+}
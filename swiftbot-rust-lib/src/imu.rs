@@ -0,0 +1,363 @@
+use crate::i2c::I2cBus;
+use rppal::i2c::I2c;
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const STANDARD_GRAVITY: f64 = 9.80665;
+const COMPLEMENTARY_ALPHA: f64 = 0.98; //Weight of the gyro in the pitch/roll filter
+
+//MPU-6050 registers and scale factors
+const MPU6050_REG_CONFIG: u8 = 0x1A;
+const MPU6050_REG_GYRO_CONFIG: u8 = 0x1B;
+const MPU6050_REG_ACCEL_CONFIG: u8 = 0x1C;
+const MPU6050_REG_ACCEL_XOUT_H: u8 = 0x3B;
+const MPU6050_REG_PWR_MGMT_1: u8 = 0x6B;
+const MPU6050_REG_WHO_AM_I: u8 = 0x75;
+const MPU6050_WHO_AM_I: u8 = 0x68;
+const MPU6050_ACCEL_LSB_PER_G: f64 = 16384.0; //±2 g range
+const MPU6050_GYRO_LSB_PER_DPS: f64 = 131.0;  //±250 °/s range
+
+//BNO055 registers and scale factors
+const BNO055_REG_CHIP_ID: u8 = 0x00;
+const BNO055_REG_ACC_DATA: u8 = 0x08;
+const BNO055_REG_GYR_DATA: u8 = 0x14;
+const BNO055_REG_EUL_DATA: u8 = 0x1A;
+const BNO055_REG_UNIT_SEL: u8 = 0x3B;
+const BNO055_REG_OPR_MODE: u8 = 0x3D;
+const BNO055_CHIP_ID: u8 = 0xA0;
+const BNO055_MODE_CONFIG: u8 = 0x00;
+const BNO055_MODE_NDOF: u8 = 0x0C;
+const BNO055_ACCEL_LSB_PER_MS2: f64 = 100.0;
+const BNO055_GYRO_LSB_PER_DPS: f64 = 16.0;
+const BNO055_EULER_LSB_PER_DEG: f64 = 16.0;
+
+/// Supported IMU chips.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImuKind {
+    Mpu6050, //6-axis accelerometer/gyro, fused in software
+    Bno055,  //9-axis with on-chip fusion
+}
+
+impl ImuKind {
+    /// Maps the integer IDs used across JNI (0 = MPU-6050, 1 = BNO055).
+    pub fn from_id(id: i32) -> Result<Self, Box<dyn Error>> {
+        match id {
+            0 => Ok(ImuKind::Mpu6050),
+            1 => Ok(ImuKind::Bno055),
+            _ => Err(format!("Invalid IMU type: {}. Must be between 0 and 1.", id).into()),
+        }
+    }
+}
+
+/// Declares an IMU on an I2C bus.
+pub struct ImuConfig {
+    pub kind: ImuKind,
+    pub bus: u8,
+    pub address: u16,
+}
+
+/// Latest IMU measurement and fused orientation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ImuReading {
+    pub accel: [f64; 3], //Acceleration in m/s² (x, y, z)
+    pub gyro: [f64; 3],  //Angular rate in °/s (x, y, z)
+    pub heading: f64,    //Heading in degrees, clockwise, 0 to 360
+    pub pitch: f64,      //Pitch in degrees
+    pub roll: f64,       //Roll in degrees
+}
+
+/// Driver for an I2C IMU providing acceleration, angular rate and fused heading/tilt.
+pub struct Imu {
+    bus: Box<dyn I2cBus>,
+    kind: ImuKind,
+    gyro_bias: [f64; 3],  //Gyro offset measured at rest, subtracted from every sample
+    heading_offset: f64,  //Raw heading that is reported as 0
+    reading: ImuReading,
+    last_update: Option<Instant>,
+}
+
+impl Imu {
+    /// Opens the IMU described by `config` on its I2C bus.
+    pub fn open(config: &ImuConfig) -> Result<Self, Box<dyn Error>> {
+        let mut i2c = I2c::with_bus(config.bus)?;
+        i2c.set_slave_address(config.address)?;
+        Self::new(Box::new(i2c), config.kind)
+    }
+
+    /// Checks the chip ID and configures the IMU on the given bus.
+    pub fn new(bus: Box<dyn I2cBus>, kind: ImuKind) -> Result<Self, Box<dyn Error>> {
+        let mut imu = Imu {
+            bus,
+            kind,
+            gyro_bias: [0.0; 3],
+            heading_offset: 0.0,
+            reading: ImuReading::default(),
+            last_update: None,
+        };
+
+        match kind {
+            ImuKind::Mpu6050 => {
+                if imu.read_register(MPU6050_REG_WHO_AM_I)? != MPU6050_WHO_AM_I {
+                    return Err("No MPU-6050 found at the given address.".into());
+                }
+                imu.bus.write(&[MPU6050_REG_PWR_MGMT_1, 0x01])?;   //Wake up, clock from the X gyro PLL
+                imu.bus.write(&[MPU6050_REG_CONFIG, 0x03])?;       //44 Hz low-pass filter
+                imu.bus.write(&[MPU6050_REG_GYRO_CONFIG, 0x00])?;  //±250 °/s
+                imu.bus.write(&[MPU6050_REG_ACCEL_CONFIG, 0x00])?; //±2 g
+            }
+            ImuKind::Bno055 => {
+                if imu.read_register(BNO055_REG_CHIP_ID)? != BNO055_CHIP_ID {
+                    return Err("No BNO055 found at the given address.".into());
+                }
+                imu.bus.write(&[BNO055_REG_OPR_MODE, BNO055_MODE_CONFIG])?;
+                thread::sleep(Duration::from_millis(25));
+                imu.bus.write(&[BNO055_REG_UNIT_SEL, 0x00])?; //m/s², °/s, degrees
+                imu.bus.write(&[BNO055_REG_OPR_MODE, BNO055_MODE_NDOF])?;
+                thread::sleep(Duration::from_millis(20));
+            }
+        }
+
+        Ok(imu)
+    }
+
+    /// Measures the gyro offset while the robot is at rest. The BNO055 calibrates itself, so
+    /// this only affects the MPU-6050.
+    pub fn calibrate_gyro(&mut self, samples: usize) -> Result<(), Box<dyn Error>> {
+        if self.kind != ImuKind::Mpu6050 || samples == 0 {
+            return Ok(());
+        }
+
+        let mut sum = [0.0; 3];
+        for _ in 0..samples {
+            let (_, gyro) = self.read_mpu6050_raw()?;
+            for axis in 0..3 {
+                sum[axis] += gyro[axis];
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        self.gyro_bias = sum.map(|total| total / samples as f64);
+        Ok(())
+    }
+
+    /// Makes the current heading read as 0°.
+    pub fn reset_heading(&mut self) {
+        match self.kind {
+            ImuKind::Mpu6050 => self.reading.heading = 0.0,
+            ImuKind::Bno055 => {
+                self.heading_offset = wrap_heading(self.heading_offset + self.reading.heading);
+                self.reading.heading = 0.0;
+            }
+        }
+    }
+
+    /// Returns the reading from the last `update`, or `None` if the sensor has not been read.
+    pub fn last_reading(&self) -> Option<ImuReading> {
+        self.last_update.map(|_| self.reading)
+    }
+
+    /// Reads the sensor and updates the fused orientation.
+    pub fn update(&mut self) -> Result<ImuReading, Box<dyn Error>> {
+        let now = Instant::now();
+        let dt = self.last_update.map(|last| now.duration_since(last).as_secs_f64());
+        self.last_update = Some(now);
+        self.update_with_dt(dt)
+    }
+
+    /// Updates the orientation assuming `dt` seconds since the previous sample (`None` on the first).
+    fn update_with_dt(&mut self, dt: Option<f64>) -> Result<ImuReading, Box<dyn Error>> {
+        match self.kind {
+            ImuKind::Mpu6050 => {
+                let (accel, raw_gyro) = self.read_mpu6050_raw()?;
+                let gyro = [
+                    raw_gyro[0] - self.gyro_bias[0],
+                    raw_gyro[1] - self.gyro_bias[1],
+                    raw_gyro[2] - self.gyro_bias[2],
+                ];
+
+                //Tilt from gravity, smoothed with the integrated gyro rate
+                let accel_roll = accel[1].atan2(accel[2]).to_degrees();
+                let accel_pitch = (-accel[0]).atan2(accel[1].hypot(accel[2])).to_degrees();
+
+                let reading = &mut self.reading;
+                match dt {
+                    Some(dt) => {
+                        reading.roll = COMPLEMENTARY_ALPHA * (reading.roll + gyro[0] * dt)
+                            + (1.0 - COMPLEMENTARY_ALPHA) * accel_roll;
+                        reading.pitch = COMPLEMENTARY_ALPHA * (reading.pitch + gyro[1] * dt)
+                            + (1.0 - COMPLEMENTARY_ALPHA) * accel_pitch;
+                        //A positive Z rate is counter-clockwise seen from above
+                        reading.heading = wrap_heading(reading.heading - gyro[2] * dt);
+                    }
+                    None => {
+                        reading.roll = accel_roll;
+                        reading.pitch = accel_pitch;
+                    }
+                }
+                reading.accel = accel;
+                reading.gyro = gyro;
+            }
+            ImuKind::Bno055 => {
+                let mut data = [0u8; 6];
+                self.bus.write_read(&[BNO055_REG_ACC_DATA], &mut data)?;
+                let accel = le_triplet(&data).map(|v| v / BNO055_ACCEL_LSB_PER_MS2);
+                self.bus.write_read(&[BNO055_REG_GYR_DATA], &mut data)?;
+                let gyro = le_triplet(&data).map(|v| v / BNO055_GYRO_LSB_PER_DPS);
+                self.bus.write_read(&[BNO055_REG_EUL_DATA], &mut data)?;
+                let euler = le_triplet(&data).map(|v| v / BNO055_EULER_LSB_PER_DEG);
+
+                self.reading = ImuReading {
+                    accel,
+                    gyro,
+                    heading: wrap_heading(euler[0] - self.heading_offset),
+                    roll: euler[1],
+                    pitch: euler[2],
+                };
+            }
+        }
+        Ok(self.reading)
+    }
+
+    /// Reads acceleration (m/s²) and uncorrected angular rate (°/s) from the MPU-6050.
+    fn read_mpu6050_raw(&mut self) -> Result<([f64; 3], [f64; 3]), Box<dyn Error>> {
+        let mut data = [0u8; 14];
+        self.bus.write_read(&[MPU6050_REG_ACCEL_XOUT_H], &mut data)?;
+        let value = |i: usize| i16::from_be_bytes([data[i], data[i + 1]]) as f64;
+
+        let accel = [value(0), value(2), value(4)]
+            .map(|v| v / MPU6050_ACCEL_LSB_PER_G * STANDARD_GRAVITY);
+        //Bytes 6 and 7 hold the temperature
+        let gyro = [value(8), value(10), value(12)].map(|v| v / MPU6050_GYRO_LSB_PER_DPS);
+        Ok((accel, gyro))
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, Box<dyn Error>> {
+        let mut value = [0u8; 1];
+        self.bus.write_read(&[register], &mut value)?;
+        Ok(value[0])
+    }
+}
+
+/// Decodes three consecutive little-endian 16-bit values.
+fn le_triplet(data: &[u8; 6]) -> [f64; 3] {
+    [0, 2, 4].map(|i| i16::from_le_bytes([data[i], data[i + 1]]) as f64)
+}
+
+/// Wraps a heading into the range 0 to 360 degrees.
+fn wrap_heading(heading: f64) -> f64 {
+    heading.rem_euclid(360.0)
+}
+
+/// Returns the signed change from heading `from` to heading `to`, in the range -180 to 180 degrees.
+pub fn heading_difference(to: f64, from: f64) -> f64 {
+    let difference = (to - from).rem_euclid(360.0);
+    if difference > 180.0 {
+        difference - 360.0
+    } else {
+        difference
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i2c::fake::FakeI2c;
+
+    fn mpu6050() -> (Imu, FakeI2c) {
+        let bus = FakeI2c::new();
+        bus.set_registers(MPU6050_REG_WHO_AM_I, &[MPU6050_WHO_AM_I]);
+        let imu = Imu::new(Box::new(bus.clone()), ImuKind::Mpu6050).unwrap();
+        (imu, bus)
+    }
+
+    fn set_mpu6050_sample(bus: &FakeI2c, accel: [i16; 3], gyro: [i16; 3]) {
+        let mut data = Vec::new();
+        for value in accel.iter().chain([0i16].iter()).chain(gyro.iter()) {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        bus.set_registers(MPU6050_REG_ACCEL_XOUT_H, &data);
+    }
+
+    #[test]
+    fn mpu6050_rejects_wrong_chip() {
+        let bus = FakeI2c::new();
+        assert!(Imu::new(Box::new(bus), ImuKind::Mpu6050).is_err());
+    }
+
+    #[test]
+    fn mpu6050_is_woken_and_configured() {
+        let (_, bus) = mpu6050();
+        assert_eq!(bus.register(MPU6050_REG_PWR_MGMT_1), 0x01);
+        assert_eq!(bus.register(MPU6050_REG_CONFIG), 0x03);
+        assert_eq!(bus.register(MPU6050_REG_GYRO_CONFIG), 0x00);
+        assert_eq!(bus.register(MPU6050_REG_ACCEL_CONFIG), 0x00);
+    }
+
+    #[test]
+    fn mpu6050_scales_samples() {
+        let (mut imu, bus) = mpu6050();
+        set_mpu6050_sample(&bus, [0, 0, 16384], [131, -262, 0]);
+
+        let reading = imu.update_with_dt(None).unwrap();
+        assert!((reading.accel[2] - STANDARD_GRAVITY).abs() < 1e-9);
+        assert_eq!(reading.gyro, [1.0, -2.0, 0.0]);
+        assert!(reading.pitch.abs() < 1e-9);
+        assert!(reading.roll.abs() < 1e-9);
+    }
+
+    #[test]
+    fn mpu6050_integrates_heading_clockwise() {
+        let (mut imu, bus) = mpu6050();
+        //-90 °/s about Z is a clockwise turn
+        set_mpu6050_sample(&bus, [0, 0, 16384], [0, 0, -90 * 131]);
+
+        imu.update_with_dt(None).unwrap();
+        let reading = imu.update_with_dt(Some(0.5)).unwrap();
+        assert!((reading.heading - 45.0).abs() < 1e-9);
+
+        imu.reset_heading();
+        assert_eq!(imu.reading.heading, 0.0);
+    }
+
+    #[test]
+    fn mpu6050_gyro_calibration_removes_bias() {
+        let (mut imu, bus) = mpu6050();
+        set_mpu6050_sample(&bus, [0, 0, 16384], [0, 0, 131]);
+
+        imu.calibrate_gyro(3).unwrap();
+        let reading = imu.update_with_dt(Some(1.0)).unwrap();
+        assert_eq!(reading.gyro, [0.0, 0.0, 0.0]);
+        assert_eq!(reading.heading, 0.0);
+    }
+
+    #[test]
+    fn bno055_reads_fused_orientation() {
+        let bus = FakeI2c::new();
+        bus.set_registers(BNO055_REG_CHIP_ID, &[BNO055_CHIP_ID]);
+        let mut imu = Imu::new(Box::new(bus.clone()), ImuKind::Bno055).unwrap();
+        assert_eq!(bus.register(BNO055_REG_OPR_MODE), BNO055_MODE_NDOF);
+
+        let mut euler = Vec::new();
+        for value in [90i16 * 16, -10 * 16, 5 * 16] {
+            euler.extend_from_slice(&value.to_le_bytes());
+        }
+        bus.set_registers(BNO055_REG_EUL_DATA, &euler);
+        bus.set_registers(BNO055_REG_ACC_DATA, &[0, 0, 0, 0, 0xD4, 0x03]); //9.8 m/s² on Z
+
+        let reading = imu.update().unwrap();
+        assert_eq!(reading.heading, 90.0);
+        assert_eq!(reading.roll, -10.0);
+        assert_eq!(reading.pitch, 5.0);
+        assert!((reading.accel[2] - 9.8).abs() < 1e-9);
+
+        imu.reset_heading();
+        assert_eq!(imu.update().unwrap().heading, 0.0);
+    }
+
+    #[test]
+    fn heading_difference_takes_shortest_path() {
+        assert_eq!(heading_difference(10.0, 350.0), 20.0);
+        assert_eq!(heading_difference(350.0, 10.0), -20.0);
+        assert_eq!(heading_difference(180.0, 0.0), 180.0);
+    }
+}
@@ -7,20 +7,54 @@ mod sensors;
 mod utils;
 mod camera;
 mod sn3218;
+mod sensor_registry;
+mod i2c;
+mod imu;
+mod line_sensor;
+mod animation;
+mod color;
+mod button_effects;
+mod status;
+mod snapshot;
+mod power;
+mod capture;
+mod decode;
+mod recorder;
+mod camera_snapshots;
+mod replay;
+#[cfg(feature = "http-stream")]
+mod stream;
 
-use crate::sn3218::UnderlightLeds;
+use crate::sn3218::{check_brightness_limit, check_gamma, check_white_balance, ChannelOrder, LedLayout, UnderlightLeds};
 use crate::motors::Motors;
-use crate::sensors::Sensors;
-use crate::buttons::{notify_button_pressed, notify_button_released, Buttons};
-use crate::camera::CameraController;
-use crate::config::{BUTTON_A_PIN, BUTTON_B_PIN, BUTTON_X_PIN, BUTTON_Y_PIN};
+use crate::sensors::{DistanceStatus, DistanceUnit, Sensors};
+use crate::buttons::{notify_button_pressed, notify_button_released, ButtonFeedback, Buttons, FeedbackAction};
+use crate::camera::{discover_cameras, CameraConfig, CameraControl, CameraController, CameraDevice};
+use crate::sensor_registry::{check_i2c_address, check_pin, check_read_length, check_register, InputPull, SensorRegistry};
+use crate::imu::{Imu, ImuConfig, ImuKind};
+use crate::line_sensor::LineSensorArray;
+use crate::animation::{AnimationEngine, Effect, Keyframe};
+use crate::color::Color;
+use crate::button_effects::{ButtonLedEffects, LedEffect};
+use crate::status::{StatusManager, SystemStatus};
+use crate::snapshot::{LedSnapshot, SnapshotStack};
+use crate::power::{PowerBudget, SharedPowerBudget};
+use crate::capture::{Frame, FrameCapture};
+use crate::decode::{decode_frame, DecodedFrame, PixelFormat};
+use crate::recorder::{RecordingLimits, RecordingSummary, VideoRecorder};
+use crate::camera_snapshots::{CameraSnapshots, RobotState, SnapshotTrigger};
+#[cfg(feature = "http-stream")]
+use crate::stream::StreamServer;
+use crate::config::{CAMERA_BUFFER_FRAMES, CAMERA_FRAME_TIMEOUT, BUTTON_A_PIN, BUTTON_B_PIN, BUTTON_X_PIN, BUTTON_Y_PIN, EXTRA_DIGITAL_SENSORS, EXTRA_I2C_DEVICES, IMU_DEVICE, NUM_UNDERLIGHTS};
 
-use jni::objects::JClass;
-use jni::sys::{jboolean, jbyteArray, jdouble, jint};
+use jni::objects::{GlobalRef, JByteArray, JByteBuffer, JClass, JDoubleArray, JIntArray, JLongArray, JObject, JString};
+use jni::sys::{jboolean, jbyteArray, jdouble, jint, jintArray, jlong, jobject, jobjectArray, jstring};
 use jni::JNIEnv;
-use std::sync::{mpsc, Arc, Mutex};
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, UNIX_EPOCH};
 use rppal::gpio::{Gpio, InputPin};
 
 //Use lazy_static to create static instances accessible across JNI calls
@@ -28,22 +62,65 @@ lazy_static! {
     /// Global GPIO instance used throughout the application.
     static ref GPIO: Gpio = Gpio::new().unwrap();
 
+    /// LED power budget shared by the button LEDs, underlights and motors. Disabled until a limit is set.
+    static ref POWER_BUDGET: SharedPowerBudget = PowerBudget::shared(Arc::new(refresh_leds));
+
     /// Shared instance of the `Leds` struct,protected by a `Mutex` for thread safety.
     /// Used for control of the LEDs at the top of the robot.
-    static ref BUTTONLEDS: Mutex<Buttons> = Mutex::new(Buttons::new(&GPIO).unwrap());
+    static ref BUTTONLEDS: Mutex<Buttons> = Mutex::new(Buttons::new(&GPIO, Arc::clone(&POWER_BUDGET)).unwrap());
+
+    /// Scheduler playing effects on the button LEDs, protected by a `Mutex` for thread safety.
+    static ref BUTTON_LED_EFFECTS: Mutex<ButtonLedEffects> = Mutex::new(ButtonLedEffects::default());
+
+    /// Status indicator driving the underlights and button LEDs, protected by a `Mutex` for thread safety.
+    static ref STATUS_MANAGER: Mutex<StatusManager> = Mutex::new(StatusManager::new(
+        &SN3218LEDS,
+        &UNDERLIGHT_ANIMATION,
+        &BUTTONLEDS,
+        &BUTTON_LED_EFFECTS,
+    ));
+
+    /// Stack of LED states pushed by the application, protected by a `Mutex` for thread safety.
+    static ref LED_SNAPSHOTS: Mutex<SnapshotStack> = Mutex::new(SnapshotStack::default());
 
     /// Shared instance of the `UnderlightLeds` struct, protected by a `Mutex` for thread safety.
     /// Used for control of the SN3218 underlight LEDs.
-    static ref SN3218LEDS: Mutex<UnderlightLeds> = Mutex::new(UnderlightLeds::new().unwrap());
+    static ref SN3218LEDS: Mutex<UnderlightLeds> = Mutex::new(UnderlightLeds::new(Arc::clone(&POWER_BUDGET)).unwrap());
+
+    /// Engine playing animated effects on the underlights, protected by a `Mutex` for thread safety.
+    static ref UNDERLIGHT_ANIMATION: Mutex<AnimationEngine> = Mutex::new(AnimationEngine::default());
 
     /// Shared instance of the `Motors` struct, protected by a `Mutex` for thread safety.
-    static ref MOTORS: Mutex<Motors> = Mutex::new(Motors::new(&GPIO).unwrap());
+    static ref MOTORS: Mutex<Motors> = Mutex::new(Motors::new(&GPIO, Arc::clone(&POWER_BUDGET)).unwrap());
 
     /// Shared instance of the `Sensors` struct, protected by a `Mutex` for thread safety.
     static ref SENSORS: Mutex<Sensors> = Mutex::new(Sensors::new(&GPIO).unwrap());
 
+    /// Registry of additional sensors declared in `config.rs` or registered through JNI,
+    /// protected by a `Mutex` for thread safety.
+    static ref SENSOR_REGISTRY: Mutex<SensorRegistry> = Mutex::new(
+        SensorRegistry::new(&GPIO, EXTRA_DIGITAL_SENSORS, EXTRA_I2C_DEVICES).unwrap()
+    );
+
+    /// Optional IMU, opened from `config.rs` or through JNI, protected by a `Mutex` for thread safety.
+    /// `None` when no IMU is configured or it could not be found.
+    static ref IMU: Mutex<Option<Imu>> = Mutex::new(IMU_DEVICE.as_ref().and_then(|config| Imu::open(config).ok()));
+
+    /// Optional line sensor array, set up through JNI, protected by a `Mutex` for thread safety.
+    static ref LINE_SENSOR: Mutex<Option<LineSensorArray>> = Mutex::new(None);
+
     /// Shared instance of the `CameraController` struct, protected by a `Mutex` for thread safety.
-    static ref CAMERA: Mutex<CameraController> = Mutex::new(CameraController::new().unwrap());
+    /// Opened with the default configuration on first use, or by `configureCamera`.
+    static ref CAMERA: Arc<Mutex<Option<CameraController>>> = Arc::new(Mutex::new(None));
+
+    /// Continuous capture from `CAMERA`, keeping the latest frames for non-blocking access.
+    static ref FRAME_CAPTURE: FrameCapture = FrameCapture::new(CAMERA_BUFFER_FRAMES);
+
+    //Video recording from `FRAME_CAPTURE`, until stopped
+    static ref RECORDER: Mutex<Option<VideoRecorder>> = Mutex::new(None);
+
+    //Time-lapse and triggered snapshots from `FRAME_CAPTURE`
+    static ref CAMERA_SNAPSHOTS: CameraSnapshots = CameraSnapshots::new(FRAME_CAPTURE.clone(), Arc::new(robot_state));
 
     //Button pins
     static ref BUTTON_A_INPUT: InputPin = GPIO.get(BUTTON_A_PIN).unwrap().into_input_pulldown();
@@ -52,7 +129,17 @@ lazy_static! {
     static ref BUTTON_Y_INPUT: InputPin = GPIO.get(BUTTON_Y_PIN).unwrap().into_input_pulldown();
 }
 
-/// Stops the robot by setting motor speeds to zero.
+//Set by `stop` to end a `turnDegrees` in progress, which holds `MOTORS` until it ends
+static TURN_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "http-stream")]
+lazy_static! {
+    //HTTP server streaming the continuous capture, while running
+    static ref STREAM_SERVER: Mutex<Option<StreamServer>> = Mutex::new(None);
+}
+
+/// Stops the robot by setting motor speeds to zero. A `turnDegrees` in progress on another
+/// thread is ended first.
 ///
 /// # Safety
 ///
@@ -72,6 +159,7 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stop(
     mut env: JNIEnv,
     _class: JClass,
 ) {
+    TURN_CANCELLED.store(true, Ordering::SeqCst);
     let mut motors = MOTORS.lock().unwrap();
     if let Err(e) = motors.set_motor_speeds(0.0, 0.0) {
         let _ = env.throw_new("java/lang/Exception", format!("{}", e));
@@ -235,11 +323,52 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setMotorSpeeds(
     }
 }
 
+/// Turns the robot on the spot by the given angle and stops.
+///
+/// Uses the IMU heading when an IMU is available, otherwise the turn is timed from the
+/// configured turn rate.
+///
+/// # Arguments
+///
+/// * `degrees` - The angle to turn, positive to the right and negative to the left.
+/// * `speed` - The speed at which to turn (0.0 to 1.0).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+/// It blocks until the turn has completed, or `stop` is called from another thread.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is an error driving the motors or reading the IMU.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void turnDegrees(double degrees, double speed);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_turnDegrees(
+    mut env: JNIEnv,
+    _class: JClass,
+    degrees: jdouble,
+    speed: jdouble,
+) {
+    //Cleared before waiting for the motors, so a stop made while waiting still ends the turn
+    TURN_CANCELLED.store(false, Ordering::SeqCst);
+    let mut motors = MOTORS.lock().unwrap();
+    let mut imu = IMU.lock().unwrap();
+    if let Err(e) = motors.turn_degrees(degrees, speed, imu.as_mut(), &TURN_CANCELLED) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
 /// Reads the distance from the ultrasonic sensor.
 ///
 /// # Returns
 ///
-/// The distance measured by the ultrasonic sensor in centimeters.
+/// The distance measured by the ultrasonic sensor, in the unit selected with `setDistanceUnit`
+/// (centimeters by default), or `-1.0` if nothing was detected within the sensor's range.
 ///
 /// # Safety
 ///
@@ -247,7 +376,7 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setMotorSpeeds(
 ///
 /// # Errors
 ///
-/// Throws a Java `Exception` if there is an error reading the sensor.
+/// Throws a Java `Exception` if the sensor does not respond.
 ///
 /// # JNI Signature
 ///
@@ -259,44 +388,135 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_readDistance(
     mut env: JNIEnv,
     _class: JClass,
 ) -> jdouble {
-    let mut sensors = SENSORS.lock().unwrap();
-    match sensors.read_distance(50) {
-        Ok(distance) => distance as jdouble,
-        Err(e) => {
-            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
-            -1.0
+    let reading = SENSORS.lock().unwrap().read_distance(50);
+    CAMERA_SNAPSHOTS.distance_measured(&reading);
+    if reading.is_valid() {
+        reading.value as jdouble
+    } else {
+        if reading.status == DistanceStatus::Timeout {
+            let _ = env.throw_new("java/lang/Exception", "Timeout waiting for the ultrasonic sensor");
         }
+        -1.0
     }
 }
 
-//
-//
-//                        BUTTONS
-//
-//
+/// Reads the distance from the ultrasonic sensor, including the status of the measurement.
+///
+/// # Returns
+///
+/// A `bisocm.swiftbot.lib.DistanceReading` holding the distance and its unit, the time of the measurement,
+/// its status and the raw echo pulse width.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Returns `null` with a pending Java exception if the reading object cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native DistanceReading readDistanceReading();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_readDistanceReading(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobject {
+    let reading = SENSORS.lock().unwrap().read_distance(50);
+    CAMERA_SNAPSHOTS.distance_measured(&reading);
+    let timestamp_ms = reading
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as jlong)
+        .unwrap_or(0);
 
-/// Reads the state of a button pin.
+    match env.new_object(
+        "bisocm/swiftbot/lib/DistanceReading",
+        "(DIJIJ)V",
+        &[
+            reading.value.into(),
+            reading.unit.id().into(),
+            timestamp_ms.into(),
+            reading.status.id().into(),
+            (reading.pulse_width.as_micros() as jlong).into(),
+        ],
+    ) {
+        Ok(obj) => obj.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Sets the speed of sound used to convert echo times into distances.
 ///
 /// # Arguments
 ///
-/// * `pin` - The GPIO pin.
+/// * `metres_per_second` - The speed of sound in metres per second.
 ///
-/// # Returns
+/// # Safety
 ///
-/// `true` if the button is pressed, `false` otherwise.
-fn read_button_state(pin: &InputPin) -> bool {
-    pin.is_high()
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the speed is not a positive value.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setSpeedOfSound(double metresPerSecond);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setSpeedOfSound(
+    mut env: JNIEnv,
+    _class: JClass,
+    metres_per_second: jdouble,
+) {
+    let mut sensors = SENSORS.lock().unwrap();
+    if let Err(e) = sensors.set_speed_of_sound(metres_per_second) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+    }
 }
 
-/// Reads the state of the specified button.
+/// Corrects distance readings for the ambient air temperature.
 ///
 /// # Arguments
 ///
-/// * `button_id` - The ID of the button to read (0 for A, 1 for B, 2 for X, 3 for Y).
+/// * `temperature` - The ambient temperature in degrees Celsius.
 ///
-/// # Returns
+/// # Safety
 ///
-/// `true` if the button is pressed, `false` otherwise.
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the temperature is outside the sensor's operating range.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setAmbientTemperature(double temperature);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setAmbientTemperature(
+    mut env: JNIEnv,
+    _class: JClass,
+    temperature: jdouble,
+) {
+    let mut sensors = SENSORS.lock().unwrap();
+    if let Err(e) = sensors.set_ambient_conditions(temperature, None) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+    }
+}
+
+/// Corrects distance readings for the ambient air temperature and relative humidity.
+///
+/// # Arguments
+///
+/// * `temperature` - The ambient temperature in degrees Celsius.
+/// * `humidity` - The relative humidity in percent (0 to 100).
 ///
 /// # Safety
 ///
@@ -304,38 +524,31 @@ fn read_button_state(pin: &InputPin) -> bool {
 ///
 /// # Errors
 ///
-/// Throws a Java `IllegalArgumentException` if the button ID is invalid.
+/// Throws a Java `IllegalArgumentException` if either value is out of range.
 ///
 /// # JNI Signature
 ///
 /// ```java
-/// public static native boolean readButton(int buttonId);
+/// public static native void setAmbientConditions(double temperature, double humidity);
 /// ```
 #[no_mangle]
-pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_readButton(
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setAmbientConditions(
     mut env: JNIEnv,
     _class: JClass,
-    button_id: jint,
-) -> jboolean {
-    let state = match button_id {
-        0 => read_button_state(&BUTTON_A_INPUT),
-        1 => read_button_state(&BUTTON_B_INPUT),
-        2 => read_button_state(&BUTTON_X_INPUT),
-        3 => read_button_state(&BUTTON_Y_INPUT),
-        _ => {
-            let _ = env.throw_new("java/lang/IllegalArgumentException", "Invalid button ID");
-            return 0;
-        }
-    };
-    state as jboolean
+    temperature: jdouble,
+    humidity: jdouble,
+) {
+    let mut sensors = SENSORS.lock().unwrap();
+    if let Err(e) = sensors.set_ambient_conditions(temperature, Some(humidity)) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+    }
 }
 
-/// Sets the brightness of the specified button LED.
+/// Selects the unit used for distance readings.
 ///
 /// # Arguments
 ///
-/// * `button_led_id` - The ID of the button LED to control (0 for A, 1 for B, 2 for X, 3 for Y).
-/// * `value` - Brightness value (0.0 for off, 1.0 for full brightness).
+/// * `unit` - The unit ID (0 for centimeters, 1 for millimeters, 2 for inches).
 ///
 /// # Safety
 ///
@@ -343,34 +556,46 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_readButton(
 ///
 /// # Errors
 ///
-/// Throws a Java `Exception` if there is an error controlling the LED.
+/// Throws a Java `IllegalArgumentException` if the unit ID is invalid.
 ///
 /// # JNI Signature
 ///
 /// ```java
-/// public static native void setButtonLed(int buttonLedId, double value);
+/// public static native void setDistanceUnit(int unit);
 /// ```
 #[no_mangle]
-pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setButtonLed(
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setDistanceUnit(
     mut env: JNIEnv,
     _class: JClass,
-    button_led_id: jint,
-    value: jdouble,
+    unit: jint,
 ) {
-    let mut leds = BUTTONLEDS.lock().unwrap();
-    if let Err(e) = leds.set_button_led(button_led_id as u8, value as f64) {
-        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    match DistanceUnit::from_id(unit) {
+        Ok(unit) => SENSORS.lock().unwrap().set_distance_unit(unit),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+        }
     }
 }
 
-/// Sets the color of a specific underlight.
+/// Converts a Java string argument into a Rust `String`.
+fn get_string(env: &mut JNIEnv, value: &JString) -> Result<String, Box<dyn Error>> {
+    Ok(env.get_string(value)?.into())
+}
+
+//
+//
+//                        SENSOR REGISTRY
+//
+//
+
+/// Registers a digital input (line sensor, bump switch, ...) on a spare GPIO pin.
 ///
 /// # Arguments
 ///
-/// * `light_id` - The ID of the underlight to control (0 to 5).
-/// * `red` - Red color component (0 to 255).
-/// * `green` - Green color component (0 to 255).
-/// * `blue` - Blue color component (0 to 255).
+/// * `name` - The name the sensor is read by.
+/// * `pin` - The BCM GPIO pin number (0 to 27).
+/// * `pull` - The internal pull resistor (0 for none, 1 for pull-up, 2 for pull-down).
+/// * `active_low` - `true` if the sensor reads as active when the pin is low.
 ///
 /// # Safety
 ///
@@ -378,42 +603,46 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setButtonLed(
 ///
 /// # Errors
 ///
-/// Throws a Java `Exception` if there is an error setting the underlight.
+/// Throws a Java `IllegalArgumentException` if the pin is not on the header, or an `Exception`
+/// if the name is taken, or the pin is reserved or unavailable.
 ///
 /// # JNI Signature
 ///
 /// ```java
-/// public static native void setUnderlight(int lightId, int red, int green, int blue);
+/// public static native void registerDigitalSensor(String name, int pin, int pull, boolean activeLow);
 /// ```
 #[no_mangle]
-pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlight(
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_registerDigitalSensor(
     mut env: JNIEnv,
     _class: JClass,
-    light_id: jint,
-    red: jint,
-    green: jint,
-    blue: jint,
+    name: JString,
+    pin: jint,
+    pull: jint,
+    active_low: jboolean,
 ) {
-    let mut leds = SN3218LEDS.lock().unwrap();
-    if let Err(e) = leds.set_rgb(
-        light_id as u8,
-        red as u8,
-        green as u8,
-        blue as u8,
-    ) {
-        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
-    } else if let Err(e) = leds.update_underlighting() {
+    let pin = match check_pin(pin) {
+        Ok(pin) => pin,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+    let result = get_string(&mut env, &name).and_then(|name| {
+        let pull = InputPull::from_id(pull)?;
+        SENSOR_REGISTRY.lock().unwrap().register_digital(&GPIO, &name, pin, pull, active_low != 0)
+    });
+    if let Err(e) = result {
         let _ = env.throw_new("java/lang/Exception", format!("{}", e));
     }
 }
 
-/// Fills all underlights with the specified color.
+/// Registers a device on an I2C bus.
 ///
 /// # Arguments
 ///
-/// * `red` - Red color component (0 to 255).
-/// * `green` - Green color component (0 to 255).
-/// * `blue` - Blue color component (0 to 255).
+/// * `name` - The name the device is addressed by.
+/// * `bus` - The I2C bus number (1 on the Raspberry Pi header).
+/// * `address` - The 7-bit device address (0x03 to 0x77).
 ///
 /// # Safety
 ///
@@ -421,28 +650,42 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlight(
 ///
 /// # Errors
 ///
-/// Throws a Java `Exception` if there is an error setting the underlights.
+/// Throws a Java `IllegalArgumentException` if the address is out of range, or an `Exception`
+/// if the name is taken or the bus cannot be opened.
 ///
 /// # JNI Signature
 ///
 /// ```java
-/// public static native void fillUnderlighting(int red, int green, int blue);
+/// public static native void registerI2cDevice(String name, int bus, int address);
 /// ```
 #[no_mangle]
-pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_fillUnderlighting(
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_registerI2cDevice(
     mut env: JNIEnv,
     _class: JClass,
-    red: jint,
-    green: jint,
-    blue: jint,
+    name: JString,
+    bus: jint,
+    address: jint,
 ) {
-    let mut leds = SN3218LEDS.lock().unwrap();
-    if let Err(e) = leds.fill_underlight((red as u8, green as u8, blue as u8)) {
+    let address = match check_i2c_address(address) {
+        Ok(address) => address,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+    let result = get_string(&mut env, &name).and_then(|name| {
+        SENSOR_REGISTRY.lock().unwrap().register_i2c(&name, bus as u8, address)
+    });
+    if let Err(e) = result {
         let _ = env.throw_new("java/lang/Exception", format!("{}", e));
     }
 }
 
-/// Clears all underlighting by turning off all underlight LEDs.
+/// Removes a sensor from the registry.
+///
+/// # Arguments
+///
+/// * `name` - The name of the sensor.
 ///
 /// # Safety
 ///
@@ -450,29 +693,31 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_fillUnderlighting
 ///
 /// # Errors
 ///
-/// Throws a Java `Exception` if there is an error clearing the underlights.
+/// Throws a Java `Exception` if no sensor with that name is registered.
 ///
 /// # JNI Signature
 ///
 /// ```java
-/// public static native void clearUnderlighting();
+/// public static native void unregisterSensor(String name);
 /// ```
 #[no_mangle]
-pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_clearUnderlighting(
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_unregisterSensor(
     mut env: JNIEnv,
     _class: JClass,
+    name: JString,
 ) {
-    let mut leds = SN3218LEDS.lock().unwrap();
-    if let Err(e) = leds.clear_underlighting() {
+    let result = get_string(&mut env, &name)
+        .and_then(|name| SENSOR_REGISTRY.lock().unwrap().unregister(&name));
+    if let Err(e) = result {
         let _ = env.throw_new("java/lang/Exception", format!("{}", e));
     }
 }
 
-/// Captures an image from the camera and returns it as a byte array.
+/// Lists the names of all registered sensors.
 ///
 /// # Returns
 ///
-/// A Java byte array containing the JPEG image data.
+/// A Java `String[]` with the names of the registered digital sensors and I2C devices.
 ///
 /// # Safety
 ///
@@ -480,33 +725,4031 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_clearUnderlightin
 ///
 /// # Errors
 ///
-/// Throws a Java `Exception` if there is an error capturing the image.
+/// Returns `null` with a pending Java exception if the array cannot be created.
 ///
 /// # JNI Signature
 ///
 /// ```java
-/// public static native byte[] captureImage();
+/// public static native String[] listSensors();
 /// ```
 #[no_mangle]
-pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureImage(
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_listSensors(
     mut env: JNIEnv,
     _class: JClass,
-) -> jbyteArray {
-    let mut camera = CAMERA.lock().unwrap();
-    match camera.capture_image() {
-        Ok(image_data) => {
-            //Convert Rust Vec<u8> to Java byte array
-            let buf = env.byte_array_from_slice(&image_data).unwrap();
-            **buf
+) -> jobjectArray {
+    let names = SENSOR_REGISTRY.lock().unwrap().names();
+    let result = (|| -> Result<jobjectArray, Box<dyn Error>> {
+        let array = env.new_object_array(names.len() as i32, "java/lang/String", JObject::null())?;
+        for (i, name) in names.iter().enumerate() {
+            let name = env.new_string(name)?;
+            env.set_object_array_element(&array, i as i32, name)?;
         }
+        Ok(array.into_raw())
+    })();
+    result.unwrap_or(std::ptr::null_mut())
+}
+
+/// Reads a registered digital sensor.
+///
+/// # Arguments
+///
+/// * `name` - The name of the sensor.
+///
+/// # Returns
+///
+/// `true` if the sensor is active, `false` otherwise.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if no digital sensor with that name is registered.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean readDigitalSensor(String name);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_readDigitalSensor(
+    mut env: JNIEnv,
+    _class: JClass,
+    name: JString,
+) -> jboolean {
+    let result = get_string(&mut env, &name)
+        .and_then(|name| SENSOR_REGISTRY.lock().unwrap().read_digital(&name));
+    match result {
+        Ok(state) => state as jboolean,
         Err(e) => {
             let _ = env.throw_new("java/lang/Exception", format!("{}", e));
-            //Return null in case of error
-            std::ptr::null_mut()
+            0
         }
     }
 }
 
+/// Reads consecutive registers from a registered I2C device.
+///
+/// # Arguments
+///
+/// * `name` - The name of the device.
+/// * `register` - The first register to read (0 to 255).
+/// * `length` - The number of bytes to read (1 to 32).
+///
+/// # Returns
+///
+/// A Java byte array containing the register values.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the register or length is out of range, or an
+/// `Exception` if the device is not registered or the transfer fails.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native byte[] readI2cRegisters(String name, int register, int length);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_readI2cRegisters(
+    mut env: JNIEnv,
+    _class: JClass,
+    name: JString,
+    register: jint,
+    length: jint,
+) -> jbyteArray {
+    let (register, length) = match check_register(register).and_then(|register| Ok((register, check_read_length(length)?))) {
+        Ok(checked) => checked,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let result = get_string(&mut env, &name).and_then(|name| {
+        SENSOR_REGISTRY.lock().unwrap().read_i2c(&name, register, length)
+    });
+    match result {
+        Ok(data) => env.byte_array_from_slice(&data)
+            .map(|array| array.into_raw())
+            .unwrap_or(std::ptr::null_mut()),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Writes consecutive registers on a registered I2C device.
+///
+/// # Arguments
+///
+/// * `name` - The name of the device.
+/// * `register` - The first register to write (0 to 255).
+/// * `data` - The bytes to write.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the register is out of range, or an
+/// `Exception` if the device is not registered or the transfer fails.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void writeI2cRegisters(String name, int register, byte[] data);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_writeI2cRegisters(
+    mut env: JNIEnv,
+    _class: JClass,
+    name: JString,
+    register: jint,
+    data: JByteArray,
+) {
+    let register = match check_register(register) {
+        Ok(register) => register,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+    let result = get_string(&mut env, &name).and_then(|name| {
+        let data = env.convert_byte_array(&data)?;
+        SENSOR_REGISTRY.lock().unwrap().write_i2c(&name, register, &data)
+    });
+    if let Err(e) = result {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+//
+//
+//                        IMU
+//
+//
+
+/// Opens an IMU on an I2C bus, replacing any IMU opened before.
+///
+/// # Arguments
+///
+/// * `kind` - The IMU type (0 for MPU-6050, 1 for BNO055).
+/// * `bus` - The I2C bus number (1 on the Raspberry Pi header).
+/// * `address` - The 7-bit device address.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the IMU type is invalid or the IMU cannot be found.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void initImu(int kind, int bus, int address);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_initImu(
+    mut env: JNIEnv,
+    _class: JClass,
+    kind: jint,
+    bus: jint,
+    address: jint,
+) {
+    let result = ImuKind::from_id(kind).and_then(|kind| {
+        Imu::open(&ImuConfig { kind, bus: bus as u8, address: address as u16 })
+    });
+    match result {
+        Ok(imu) => *IMU.lock().unwrap() = Some(imu),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+        }
+    }
+}
+
+/// Checks whether an IMU is available.
+///
+/// # Returns
+///
+/// `true` if an IMU has been opened, `false` otherwise.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isImuAvailable();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isImuAvailable(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    IMU.lock().unwrap().is_some() as jboolean
+}
+
+/// Measures the gyro offset of the IMU. The robot must be at rest.
+///
+/// # Arguments
+///
+/// * `samples` - The number of samples to average.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if no IMU is available or it cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void calibrateImu(int samples);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_calibrateImu(
+    mut env: JNIEnv,
+    _class: JClass,
+    samples: jint,
+) {
+    let mut imu = IMU.lock().unwrap();
+    let result = match imu.as_mut() {
+        Some(imu) => imu.calibrate_gyro(samples.max(0) as usize),
+        None => Err("No IMU available".into()),
+    };
+    if let Err(e) = result {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Makes the current IMU heading read as 0 degrees.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if no IMU is available.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void resetImuHeading();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_resetImuHeading(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    match IMU.lock().unwrap().as_mut() {
+        Some(imu) => imu.reset_heading(),
+        None => {
+            let _ = env.throw_new("java/lang/Exception", "No IMU available");
+        }
+    }
+}
+
+/// Reads the IMU and updates its fused orientation.
+///
+/// # Returns
+///
+/// A `bisocm.swiftbot.lib.ImuReading` holding acceleration, angular rate, heading, pitch and roll.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if no IMU is available or it cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native ImuReading readImu();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_readImu(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobject {
+    let result = match IMU.lock().unwrap().as_mut() {
+        Some(imu) => imu.update(),
+        None => Err("No IMU available".into()),
+    };
+    match result {
+        Ok(reading) => env
+            .new_object(
+                "bisocm/swiftbot/lib/ImuReading",
+                "(DDDDDDDDD)V",
+                &[
+                    reading.accel[0].into(),
+                    reading.accel[1].into(),
+                    reading.accel[2].into(),
+                    reading.gyro[0].into(),
+                    reading.gyro[1].into(),
+                    reading.gyro[2].into(),
+                    reading.heading.into(),
+                    reading.pitch.into(),
+                    reading.roll.into(),
+                ],
+            )
+            .map(|obj| obj.into_raw())
+            .unwrap_or(std::ptr::null_mut()),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Copies a Java `int[]` argument into a `Vec`.
+fn get_int_array(env: &mut JNIEnv, array: &JIntArray) -> Result<Vec<i32>, Box<dyn Error>> {
+    let mut values = vec![0; env.get_array_length(array)? as usize];
+    env.get_int_array_region(array, 0, &mut values)?;
+    Ok(values)
+}
+
+/// Copies a Java `double[]` argument into a `Vec`.
+fn get_double_array(env: &mut JNIEnv, array: &JDoubleArray) -> Result<Vec<f64>, Box<dyn Error>> {
+    let mut values = vec![0.0; env.get_array_length(array)? as usize];
+    env.get_double_array_region(array, 0, &mut values)?;
+    Ok(values)
+}
+
+//
+//
+//                        LINE SENSORS
+//
+//
+
+/// Sets up a line sensor array of digital sensors on spare GPIO pins.
+///
+/// # Arguments
+///
+/// * `pins` - The BCM GPIO pin of each sensor, ordered from left to right.
+/// * `active_low` - `true` if the sensors pull their output low over the line.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if a pin is reserved or unavailable.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void initLineSensorGpio(int[] pins, boolean activeLow);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_initLineSensorGpio(
+    mut env: JNIEnv,
+    _class: JClass,
+    pins: JIntArray,
+    active_low: jboolean,
+) {
+    let result = get_int_array(&mut env, &pins).and_then(|pins| {
+        //Release the pins of a previous array first, so they can be reused
+        let mut line_sensor = LINE_SENSOR.lock().unwrap();
+        *line_sensor = None;
+        let pins: Vec<u8> = pins.iter().map(|&pin| pin as u8).collect();
+        *line_sensor = Some(LineSensorArray::with_gpio(&GPIO, &pins, active_low != 0)?);
+        Ok(())
+    });
+    if let Err(e) = result {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Sets up a line sensor array of analogue sensors on an ADS7830 I2C ADC.
+///
+/// # Arguments
+///
+/// * `bus` - The I2C bus number (1 on the Raspberry Pi header).
+/// * `address` - The 7-bit address of the ADC.
+/// * `channels` - The number of sensors, wired to ADC inputs 0 (leftmost) upwards.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the channel count is invalid or the bus cannot be opened.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void initLineSensorAdc(int bus, int address, int channels);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_initLineSensorAdc(
+    mut env: JNIEnv,
+    _class: JClass,
+    bus: jint,
+    address: jint,
+    channels: jint,
+) {
+    match LineSensorArray::open_adc(bus as u8, address as u16, channels as u8) {
+        Ok(array) => *LINE_SENSOR.lock().unwrap() = Some(array),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+        }
+    }
+}
+
+/// Calibrates the line sensors while the robot is moved across the line.
+///
+/// # Arguments
+///
+/// * `duration_ms` - How long to sample for, in milliseconds.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+/// It blocks for `duration_ms`.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if no line sensor is set up, or a sensor did not see both the
+/// line and the background.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void calibrateLineSensor(int durationMs);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_calibrateLineSensor(
+    mut env: JNIEnv,
+    _class: JClass,
+    duration_ms: jint,
+) {
+    let result = match LINE_SENSOR.lock().unwrap().as_mut() {
+        Some(array) => array.calibrate(Duration::from_millis(duration_ms.max(0) as u64)),
+        None => Err("No line sensor set up".into()),
+    };
+    if let Err(e) = result {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Sets the raw white (background) and black (line) values for each line sensor.
+///
+/// # Arguments
+///
+/// * `white` - The raw value of each sensor over the background.
+/// * `black` - The raw value of each sensor over the line.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if no line sensor is set up or the thresholds are invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setLineThresholds(double[] white, double[] black);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setLineThresholds(
+    mut env: JNIEnv,
+    _class: JClass,
+    white: JDoubleArray,
+    black: JDoubleArray,
+) {
+    let result = get_double_array(&mut env, &white).and_then(|white| {
+        let black = get_double_array(&mut env, &black)?;
+        match LINE_SENSOR.lock().unwrap().as_mut() {
+            Some(array) => array.set_thresholds(&white, &black),
+            None => Err("No line sensor set up".into()),
+        }
+    });
+    if let Err(e) = result {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Reads the line sensor array.
+///
+/// # Returns
+///
+/// A `bisocm.swiftbot.lib.LineReading` holding the line position, whether the line was lost,
+/// and the normalised value of each sensor.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if no line sensor is set up or it cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native LineReading readLine();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_readLine(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobject {
+    let result = match LINE_SENSOR.lock().unwrap().as_mut() {
+        Some(array) => array.read(),
+        None => Err("No line sensor set up".into()),
+    };
+    let result = result.and_then(|reading| {
+        let values = env.new_double_array(reading.values.len() as i32)?;
+        env.set_double_array_region(&values, 0, &reading.values)?;
+        let obj = env.new_object(
+            "bisocm/swiftbot/lib/LineReading",
+            "(DZ[D)V",
+            &[reading.position.into(), reading.lost.into(), (&values).into()],
+        )?;
+        Ok(obj.into_raw())
+    });
+    match result {
+        Ok(obj) => obj,
+        Err(e) => {
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            }
+            std::ptr::null_mut()
+        }
+    }
+}
+
+//
+//
+//                        BUTTONS
+//
+//
+
+/// Reads the state of a button pin.
+///
+/// # Arguments
+///
+/// * `pin` - The GPIO pin.
+///
+/// # Returns
+///
+/// `true` if the button is pressed, `false` otherwise.
+fn read_button_state(pin: &InputPin) -> bool {
+    pin.is_high()
+}
+
+/// Reads the state of the specified button.
+///
+/// # Arguments
+///
+/// * `button_id` - The ID of the button to read (0 for A, 1 for B, 2 for X, 3 for Y).
+///
+/// # Returns
+///
+/// `true` if the button is pressed, `false` otherwise.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the button ID is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean readButton(int buttonId);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_readButton(
+    mut env: JNIEnv,
+    _class: JClass,
+    button_id: jint,
+) -> jboolean {
+    let state = match button_id {
+        0 => read_button_state(&BUTTON_A_INPUT),
+        1 => read_button_state(&BUTTON_B_INPUT),
+        2 => read_button_state(&BUTTON_X_INPUT),
+        3 => read_button_state(&BUTTON_Y_INPUT),
+        _ => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", "Invalid button ID");
+            return 0;
+        }
+    };
+    state as jboolean
+}
+
+/// Sets the brightness of the specified button LED.
+///
+/// # Arguments
+///
+/// * `button_led_id` - The ID of the button LED to control (0 for A, 1 for B, 2 for X, 3 for Y).
+/// * `value` - Brightness value (0.0 for off, 1.0 for full brightness).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is an error controlling the LED.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setButtonLed(int buttonLedId, double value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setButtonLed(
+    mut env: JNIEnv,
+    _class: JClass,
+    button_led_id: jint,
+    value: jdouble,
+) {
+    let _status = match claim_buttons() {
+        Some(status) => status,
+        None => return,
+    };
+    BUTTON_LED_EFFECTS.lock().unwrap().stop(button_led_id as u8);
+    let mut leds = BUTTONLEDS.lock().unwrap();
+    if let Err(e) = leds.set_button_led(button_led_id as u8, value as f64) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Sets how a button's LED reacts to the button: lit while held, or a flash when pressed.
+/// The feedback is driven from the button monitoring thread, so no Java code is involved.
+/// When a held button is released, its LED goes back to the level and effect it had before
+/// the press. Setting the LED or starting an effect on it still works, but may be overridden
+/// on the next press, and changes made while the button is held are lost on release.
+///
+/// # Arguments
+///
+/// * `button_id` - The ID of the button (0 for A, 1 for B, 2 for X, 3 for Y).
+/// * `mode` - The feedback mode (0 = none, 1 = lit while held, 2 = flash on press).
+/// * `level` - The brightness of the feedback (0.0 to 1.0).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if any argument is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setButtonFeedback(int buttonId, int mode, double level);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setButtonFeedback(
+    mut env: JNIEnv,
+    _class: JClass,
+    button_id: jint,
+    mode: jint,
+    level: jdouble,
+) {
+    let result = ButtonFeedback::from_id(mode, level)
+        .and_then(|feedback| BUTTONLEDS.lock().unwrap().set_feedback(button_id as u8, feedback));
+    if let Err(e) = result {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+    }
+}
+
+/// Starts an effect on a button LED, throwing a Java `IllegalArgumentException` if it is invalid.
+fn start_button_led_effect(env: &mut JNIEnv, button_led_id: jint, effect: LedEffect) {
+    let _status = match claim_buttons() {
+        Some(status) => status,
+        None => return,
+    };
+    if let Err(e) = BUTTON_LED_EFFECTS.lock().unwrap().start(&BUTTONLEDS, button_led_id as u8, effect) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+    }
+}
+
+/// Fades a button LED from its current brightness to a new one, replacing any effect
+/// playing on it. The LED holds the new brightness afterwards.
+///
+/// # Arguments
+///
+/// * `button_led_id` - The ID of the button LED to control (0 for A, 1 for B, 2 for X, 3 for Y).
+/// * `level` - Brightness to fade to (0.0 to 1.0).
+/// * `duration_ms` - Duration of the fade in milliseconds.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the ID or level is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void fadeButtonLed(int buttonLedId, double level, int durationMs);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_fadeButtonLed(
+    mut env: JNIEnv,
+    _class: JClass,
+    button_led_id: jint,
+    level: jdouble,
+    duration_ms: jint,
+) {
+    let duration = Duration::from_millis(duration_ms.max(0) as u64);
+    start_button_led_effect(&mut env, button_led_id, LedEffect::Fade { level, duration });
+}
+
+/// Blinks a button LED until it is set directly or the effect is stopped.
+///
+/// # Arguments
+///
+/// * `button_led_id` - The ID of the button LED to control (0 for A, 1 for B, 2 for X, 3 for Y).
+/// * `level` - Brightness while on (0.0 to 1.0).
+/// * `period_ms` - Duration of one on/off cycle in milliseconds.
+/// * `duty` - Fraction of each cycle the LED is on (0.0 to 1.0).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if any argument is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void blinkButtonLed(int buttonLedId, double level, int periodMs, double duty);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_blinkButtonLed(
+    mut env: JNIEnv,
+    _class: JClass,
+    button_led_id: jint,
+    level: jdouble,
+    period_ms: jint,
+    duty: jdouble,
+) {
+    let period = Duration::from_millis(period_ms.max(0) as u64);
+    start_button_led_effect(&mut env, button_led_id, LedEffect::Blink { level, period, duty });
+}
+
+/// Breathes a button LED (fades it in and out) until it is set directly or the effect is stopped.
+///
+/// # Arguments
+///
+/// * `button_led_id` - The ID of the button LED to control (0 for A, 1 for B, 2 for X, 3 for Y).
+/// * `level` - Peak brightness (0.0 to 1.0).
+/// * `period_ms` - Duration of one breath in milliseconds.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if any argument is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void breatheButtonLed(int buttonLedId, double level, int periodMs);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_breatheButtonLed(
+    mut env: JNIEnv,
+    _class: JClass,
+    button_led_id: jint,
+    level: jdouble,
+    period_ms: jint,
+) {
+    let period = Duration::from_millis(period_ms.max(0) as u64);
+    start_button_led_effect(&mut env, button_led_id, LedEffect::Breathe { level, period });
+}
+
+/// Pulses a button LED once: it rises to a brightness and falls back to its current one.
+///
+/// # Arguments
+///
+/// * `button_led_id` - The ID of the button LED to control (0 for A, 1 for B, 2 for X, 3 for Y).
+/// * `level` - Peak brightness (0.0 to 1.0).
+/// * `duration_ms` - Duration of the pulse in milliseconds.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if any argument is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void pulseButtonLed(int buttonLedId, double level, int durationMs);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_pulseButtonLed(
+    mut env: JNIEnv,
+    _class: JClass,
+    button_led_id: jint,
+    level: jdouble,
+    duration_ms: jint,
+) {
+    let duration = Duration::from_millis(duration_ms.max(0) as u64);
+    start_button_led_effect(&mut env, button_led_id, LedEffect::Pulse { level, duration });
+}
+
+/// Stops the effect playing on a button LED, if any. The LED keeps its current brightness.
+///
+/// # Arguments
+///
+/// * `button_led_id` - The ID of the button LED (0 for A, 1 for B, 2 for X, 3 for Y).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopButtonLedEffect(int buttonLedId);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopButtonLedEffect(
+    _env: JNIEnv,
+    _class: JClass,
+    button_led_id: jint,
+) {
+    if let Some(_status) = claim_buttons() {
+        BUTTON_LED_EFFECTS.lock().unwrap().stop(button_led_id as u8);
+    }
+}
+
+/// Checks whether an effect is playing on a button LED.
+///
+/// # Arguments
+///
+/// * `button_led_id` - The ID of the button LED (0 for A, 1 for B, 2 for X, 3 for Y).
+///
+/// # Returns
+///
+/// `true` while an effect is playing. One-shot effects (fade, pulse) stop by themselves.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isButtonLedEffectRunning(int buttonLedId);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isButtonLedEffectRunning(
+    _env: JNIEnv,
+    _class: JClass,
+    button_led_id: jint,
+) -> jboolean {
+    BUTTON_LED_EFFECTS.lock().unwrap().is_running(button_led_id as u8) as jboolean
+}
+
+/// Sets the color of a specific underlight.
+///
+/// # Arguments
+///
+/// * `light_id` - The ID of the underlight to control (0 to 5).
+/// * `red` - Red color component (0 to 255).
+/// * `green` - Green color component (0 to 255).
+/// * `blue` - Blue color component (0 to 255).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is an error setting the underlight.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlight(int lightId, int red, int green, int blue);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlight(
+    mut env: JNIEnv,
+    _class: JClass,
+    light_id: jint,
+    red: jint,
+    green: jint,
+    blue: jint,
+) {
+    let _status = match claim_underlights() {
+        Some(status) => status,
+        None => return,
+    };
+    UNDERLIGHT_ANIMATION.lock().unwrap().stop();
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.set_rgb(
+        light_id as u8,
+        red as u8,
+        green as u8,
+        blue as u8,
+    ) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    } else if let Err(e) = leds.update_underlighting() {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Fills all underlights with the specified color.
+///
+/// # Arguments
+///
+/// * `red` - Red color component (0 to 255).
+/// * `green` - Green color component (0 to 255).
+/// * `blue` - Blue color component (0 to 255).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is an error setting the underlights.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void fillUnderlighting(int red, int green, int blue);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_fillUnderlighting(
+    mut env: JNIEnv,
+    _class: JClass,
+    red: jint,
+    green: jint,
+    blue: jint,
+) {
+    let _status = match claim_underlights() {
+        Some(status) => status,
+        None => return,
+    };
+    UNDERLIGHT_ANIMATION.lock().unwrap().stop();
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.fill_underlight(Color::rgb(red as u8, green as u8, blue as u8)) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Clears all underlighting by turning off all underlight LEDs.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is an error clearing the underlights.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void clearUnderlighting();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_clearUnderlighting(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    let _status = match claim_underlights() {
+        Some(status) => status,
+        None => return,
+    };
+    UNDERLIGHT_ANIMATION.lock().unwrap().stop();
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.clear_underlighting() {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Sets the color of every underlight at once. The colors are sent to the SN3218 in a
+/// single update, so there is no visible tearing, and nothing is sent if they have not changed.
+///
+/// # Arguments
+///
+/// * `colors` - One packed `0xRRGGBB` color per underlight, indexed by light ID (6 values).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the array does not hold one color per
+/// underlight, or an `Exception` if there is an error setting the underlights.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlightFrame(int[] colors);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlightFrame(
+    mut env: JNIEnv,
+    _class: JClass,
+    colors: JIntArray,
+) {
+    let colors = match get_int_array(&mut env, &colors) {
+        Ok(colors) if colors.len() == NUM_UNDERLIGHTS => colors,
+        Ok(_) => {
+            let _ = env.throw_new(
+                "java/lang/IllegalArgumentException",
+                format!("Expected {} colors, one per underlight.", NUM_UNDERLIGHTS),
+            );
+            return;
+        }
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+
+    let mut frame = [Color::BLACK; NUM_UNDERLIGHTS];
+    for (light, color) in frame.iter_mut().zip(&colors) {
+        *light = Color::from_packed(*color as u32);
+    }
+
+    let _status = match claim_underlights() {
+        Some(status) => status,
+        None => return,
+    };
+    UNDERLIGHT_ANIMATION.lock().unwrap().stop();
+    if let Err(e) = SN3218LEDS.lock().unwrap().set_frame(&frame) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Sets the color of several underlights at once, leaving the others unchanged. The
+/// colors are sent to the SN3218 in a single update.
+///
+/// # Arguments
+///
+/// * `light_ids` - The IDs of the underlights to set (0 to 5).
+/// * `colors` - One packed `0xRRGGBB` color per entry in `light_ids`.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the arrays differ in length, or an
+/// `Exception` if an ID is invalid or there is an error setting the underlights. Nothing
+/// is sent if an ID is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlights(int[] lightIds, int[] colors);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlights(
+    mut env: JNIEnv,
+    _class: JClass,
+    light_ids: JIntArray,
+    colors: JIntArray,
+) {
+    let arrays = get_int_array(&mut env, &light_ids).and_then(|light_ids| {
+        let colors = get_int_array(&mut env, &colors)?;
+        if light_ids.len() != colors.len() {
+            return Err("Expected one color per light ID.".into());
+        }
+        Ok((light_ids, colors))
+    });
+    let (light_ids, colors) = match arrays {
+        Ok(arrays) => arrays,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+
+    let _status = match claim_underlights() {
+        Some(status) => status,
+        None => return,
+    };
+    UNDERLIGHT_ANIMATION.lock().unwrap().stop();
+    let mut leds = SN3218LEDS.lock().unwrap();
+    let mut frame = leds.frame();
+    for (light_id, color) in light_ids.iter().zip(&colors) {
+        match frame.get_mut(*light_id as usize) {
+            Some(light) => *light = Color::from_packed(*color as u32),
+            None => {
+                let _ = env.throw_new(
+                    "java/lang/Exception",
+                    format!("Invalid RGB LED ID: {}. Must be between 0 and 5.", light_id),
+                );
+                return;
+            }
+        }
+    }
+    if let Err(e) = leds.set_frame(&frame) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Sets the color of a specific underlight from hue, saturation and value.
+///
+/// # Arguments
+///
+/// * `light_id` - The ID of the underlight to control (0 to 5).
+/// * `hue` - Hue in degrees (wraps around 360).
+/// * `saturation` - Saturation (0.0 to 1.0).
+/// * `value` - Value, or brightness (0.0 to 1.0).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is an error setting the underlight.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlightHsv(int lightId, double hue, double saturation, double value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlightHsv(
+    mut env: JNIEnv,
+    _class: JClass,
+    light_id: jint,
+    hue: jdouble,
+    saturation: jdouble,
+    value: jdouble,
+) {
+    let _status = match claim_underlights() {
+        Some(status) => status,
+        None => return,
+    };
+    UNDERLIGHT_ANIMATION.lock().unwrap().stop();
+    let color = Color::from_hsv(hue, saturation, value);
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.set_rgb(light_id as u8, color.r, color.g, color.b) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    } else if let Err(e) = leds.update_underlighting() {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Sets the color of a specific underlight from a hex string.
+///
+/// # Arguments
+///
+/// * `light_id` - The ID of the underlight to control (0 to 5).
+/// * `hex` - The color as `#RRGGBB` or `#RGB` (the `#` is optional).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the string is not a valid color, or an
+/// `Exception` if there is an error setting the underlight.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlightHex(int lightId, String hex);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlightHex(
+    mut env: JNIEnv,
+    _class: JClass,
+    light_id: jint,
+    hex: JString,
+) {
+    let color = match get_string(&mut env, &hex).and_then(|hex| Color::from_hex(&hex)) {
+        Ok(color) => color,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+
+    let _status = match claim_underlights() {
+        Some(status) => status,
+        None => return,
+    };
+    UNDERLIGHT_ANIMATION.lock().unwrap().stop();
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.set_rgb(light_id as u8, color.r, color.g, color.b) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    } else if let Err(e) = leds.update_underlighting() {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Fills all underlights with a color given as hue, saturation and value.
+///
+/// # Arguments
+///
+/// * `hue` - Hue in degrees (wraps around 360).
+/// * `saturation` - Saturation (0.0 to 1.0).
+/// * `value` - Value, or brightness (0.0 to 1.0).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is an error setting the underlights.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void fillUnderlightingHsv(double hue, double saturation, double value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_fillUnderlightingHsv(
+    mut env: JNIEnv,
+    _class: JClass,
+    hue: jdouble,
+    saturation: jdouble,
+    value: jdouble,
+) {
+    let _status = match claim_underlights() {
+        Some(status) => status,
+        None => return,
+    };
+    UNDERLIGHT_ANIMATION.lock().unwrap().stop();
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.fill_underlight(Color::from_hsv(hue, saturation, value)) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Fills all underlights with a color given as a hex string.
+///
+/// # Arguments
+///
+/// * `hex` - The color as `#RRGGBB` or `#RGB` (the `#` is optional).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the string is not a valid color, or an
+/// `Exception` if there is an error setting the underlights.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void fillUnderlightingHex(String hex);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_fillUnderlightingHex(
+    mut env: JNIEnv,
+    _class: JClass,
+    hex: JString,
+) {
+    let color = match get_string(&mut env, &hex).and_then(|hex| Color::from_hex(&hex)) {
+        Ok(color) => color,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+
+    let _status = match claim_underlights() {
+        Some(status) => status,
+        None => return,
+    };
+    UNDERLIGHT_ANIMATION.lock().unwrap().stop();
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.fill_underlight(color) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Sets the gamma used to correct underlight brightness. Higher values make low
+/// brightness levels dimmer; 1.0 disables the correction.
+///
+/// # Arguments
+///
+/// * `gamma` - The gamma (greater than 0, at most 5), e.g. 2.2. The default is 1.0.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the gamma is out of range, or an
+/// `Exception` if there is an error updating the underlights.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlightGamma(double gamma);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlightGamma(
+    mut env: JNIEnv,
+    _class: JClass,
+    gamma: jdouble,
+) {
+    if let Err(e) = check_gamma(gamma) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+        return;
+    }
+
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.set_gamma(gamma) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Sets the white balance of the underlights, as a gain for each color component.
+/// Lowering the gain of a component that appears too strong gives truer whites.
+///
+/// # Arguments
+///
+/// * `red` - Red gain (0.0 to 1.0).
+/// * `green` - Green gain (0.0 to 1.0).
+/// * `blue` - Blue gain (0.0 to 1.0).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if a gain is out of range, or an
+/// `Exception` if there is an error updating the underlights.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlightWhiteBalance(double red, double green, double blue);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlightWhiteBalance(
+    mut env: JNIEnv,
+    _class: JClass,
+    red: jdouble,
+    green: jdouble,
+    blue: jdouble,
+) {
+    let white_balance = [red, green, blue];
+    if let Err(e) = check_white_balance(white_balance) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+        return;
+    }
+
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.set_white_balance(white_balance) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Limits the overall brightness of the underlights. Every value written to the
+/// LEDs, including those of running effects, is scaled by the limit.
+///
+/// # Arguments
+///
+/// * `limit` - The brightness limit (0.0 to 1.0).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the limit is out of range, or an
+/// `Exception` if there is an error updating the underlights.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlightBrightnessLimit(double limit);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlightBrightnessLimit(
+    mut env: JNIEnv,
+    _class: JClass,
+    limit: jdouble,
+) {
+    if let Err(e) = check_brightness_limit(limit) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+        return;
+    }
+
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.set_brightness_limit(limit) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Changes which SN3218 channels drive an underlight, for board revisions wired
+/// differently from the default layout in `config.rs`.
+///
+/// # Arguments
+///
+/// * `light_id` - The ID of the underlight to remap (0 to 5).
+/// * `first_channel` - The first of the three consecutive SN3218 channels driving the LED (0 to 15).
+/// * `order` - The order of the color components on those channels (0 = RGB, 1 = RBG,
+///   2 = GRB, 3 = GBR, 4 = BRG, 5 = BGR).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the order is invalid, or an `Exception`
+/// if the channels are out of range or already used by another underlight.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlightLayout(int lightId, int firstChannel, int order);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlightLayout(
+    mut env: JNIEnv,
+    _class: JClass,
+    light_id: jint,
+    first_channel: jint,
+    order: jint,
+) {
+    let order = match ChannelOrder::from_id(order) {
+        Ok(order) => order,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+
+    let mut leds = SN3218LEDS.lock().unwrap();
+    let layout = LedLayout { first_channel: first_channel.clamp(0, u8::MAX as jint) as u8, order };
+    if let Err(e) = leds.set_light_layout(light_id as u8, layout) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Changes which SN3218 channels drive every underlight at once. Unlike `setUnderlightLayout`,
+/// this can swap channels between underlights, as the whole layout is checked together.
+///
+/// # Arguments
+///
+/// * `first_channels` - The first SN3218 channel driving each underlight, indexed by light ID (0 to 15).
+/// * `orders` - The color component order of each underlight, indexed by light ID (0 = RGB,
+///   1 = RBG, 2 = GRB, 3 = GBR, 4 = BRG, 5 = BGR).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if an array does not have one entry per
+/// underlight or an order is invalid, or an `Exception` if channels are out of range or
+/// shared by two underlights. The layout is unchanged in either case.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlightLayouts(int[] firstChannels, int[] orders);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlightLayouts(
+    mut env: JNIEnv,
+    _class: JClass,
+    first_channels: JIntArray,
+    orders: JIntArray,
+) {
+    let layout = (|| -> Result<[LedLayout; NUM_UNDERLIGHTS], Box<dyn Error>> {
+        let first_channels = get_int_array(&mut env, &first_channels)?;
+        let orders = get_int_array(&mut env, &orders)?;
+        if first_channels.len() != NUM_UNDERLIGHTS || orders.len() != NUM_UNDERLIGHTS {
+            return Err(format!("Expected {} first channels and orders, one per underlight.", NUM_UNDERLIGHTS).into());
+        }
+        let mut layout = [LedLayout { first_channel: 0, order: ChannelOrder::Rgb }; NUM_UNDERLIGHTS];
+        for (i, led) in layout.iter_mut().enumerate() {
+            *led = LedLayout {
+                first_channel: first_channels[i].clamp(0, u8::MAX as jint) as u8,
+                order: ChannelOrder::from_id(orders[i])?,
+            };
+        }
+        Ok(layout)
+    })();
+
+    match layout {
+        Ok(layout) => {
+            if let Err(e) = SN3218LEDS.lock().unwrap().set_layout(layout) {
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            }
+        }
+        Err(e) => {
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            }
+        }
+    }
+}
+
+/// Switches an underlight on or off. A switched-off underlight stays dark whatever
+/// color it is set to, which is useful for hiding faulty or obstructed LEDs.
+///
+/// # Arguments
+///
+/// * `light_id` - The ID of the underlight (0 to 5).
+/// * `enabled` - `true` to switch the underlight on, `false` to switch it off.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the ID is invalid or the SN3218 cannot be written.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlightEnabled(int lightId, boolean enabled);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlightEnabled(
+    mut env: JNIEnv,
+    _class: JClass,
+    light_id: jint,
+    enabled: jboolean,
+) {
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.set_led_enabled(light_id as u8, enabled != 0) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Checks whether an underlight is switched on.
+///
+/// # Arguments
+///
+/// * `light_id` - The ID of the underlight (0 to 5).
+///
+/// # Returns
+///
+/// `true` if all channels of the underlight are switched on.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the ID is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isUnderlightEnabled(int lightId);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isUnderlightEnabled(
+    mut env: JNIEnv,
+    _class: JClass,
+    light_id: jint,
+) -> jboolean {
+    match SN3218LEDS.lock().unwrap().is_led_enabled(light_id as u8) {
+        Ok(enabled) => enabled as jboolean,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            0
+        }
+    }
+}
+
+/// Switches a single SN3218 channel on or off.
+///
+/// # Arguments
+///
+/// * `channel` - The SN3218 channel (0 to 17).
+/// * `enabled` - `true` to switch the channel on, `false` to switch it off.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the channel is invalid or the SN3218 cannot be written.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setUnderlightChannelEnabled(int channel, boolean enabled);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setUnderlightChannelEnabled(
+    mut env: JNIEnv,
+    _class: JClass,
+    channel: jint,
+    enabled: jboolean,
+) {
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.set_channel_enabled(channel as u8, enabled != 0) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+//
+//
+//                        UNDERLIGHT EFFECTS
+//
+//
+
+/// Starts one of the built-in underlight effects, replacing any effect already playing.
+///
+/// Setting, filling or clearing the underlights directly stops the effect.
+///
+/// # Arguments
+///
+/// * `effect` - The effect ID (0 breathe, 1 blink, 2 chase, 3 rainbow, 4 police, 5 comet).
+/// * `red` - Red color component (0 to 255), ignored by rainbow and police.
+/// * `green` - Green color component (0 to 255), ignored by rainbow and police.
+/// * `blue` - Blue color component (0 to 255), ignored by rainbow and police.
+/// * `period_ms` - The length of one cycle of the effect, in milliseconds.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the effect ID or period is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void startUnderlightEffect(int effect, int red, int green, int blue, int periodMs);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startUnderlightEffect(
+    mut env: JNIEnv,
+    _class: JClass,
+    effect: jint,
+    red: jint,
+    green: jint,
+    blue: jint,
+    period_ms: jint,
+) {
+    let period = Duration::from_millis(period_ms.max(0) as u64);
+    match Effect::from_id(effect, Color::rgb(red as u8, green as u8, blue as u8), period) {
+        Ok(effect) => {
+            if let Some(_status) = claim_underlights() {
+                UNDERLIGHT_ANIMATION.lock().unwrap().start(&SN3218LEDS, effect);
+            }
+        }
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+        }
+    }
+}
+
+/// Starts a keyframe sequence on the underlights, replacing any effect already playing.
+///
+/// # Arguments
+///
+/// * `frames` - Six colors per keyframe, packed as `0xRRGGBB` and indexed by light ID.
+/// * `durations_ms` - How long each keyframe lasts, in milliseconds.
+/// * `fade` - `true` to fade into each keyframe from the previous one.
+/// * `looping` - `true` to repeat the sequence until stopped.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the number of colors does not match the number
+/// of keyframes.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void startUnderlightSequence(int[] frames, int[] durationsMs, boolean fade, boolean looping);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startUnderlightSequence(
+    mut env: JNIEnv,
+    _class: JClass,
+    frames: JIntArray,
+    durations_ms: JIntArray,
+    fade: jboolean,
+    looping: jboolean,
+) {
+    let result = get_int_array(&mut env, &frames).and_then(|frames| {
+        let durations = get_int_array(&mut env, &durations_ms)?;
+        if durations.is_empty() || frames.len() != durations.len() * NUM_UNDERLIGHTS {
+            return Err(format!("Expected {} colors per keyframe.", NUM_UNDERLIGHTS).into());
+        }
+
+        let keyframes = frames
+            .chunks(NUM_UNDERLIGHTS)
+            .zip(durations)
+            .map(|(colors, duration)| {
+                let mut frame = [Color::BLACK; NUM_UNDERLIGHTS];
+                for (light, color) in frame.iter_mut().zip(colors) {
+                    *light = Color::from_packed(*color as u32);
+                }
+                Keyframe {
+                    frame,
+                    duration: Duration::from_millis(duration.max(0) as u64),
+                    fade: fade != 0,
+                }
+            })
+            .collect();
+
+        let effect = Effect::Sequence { keyframes, looping: looping != 0 };
+        if let Some(_status) = claim_underlights() {
+            UNDERLIGHT_ANIMATION.lock().unwrap().start(&SN3218LEDS, effect);
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+    }
+}
+
+/// Stops the underlight effect playing, if any, and turns the underlights off.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is an error clearing the underlights.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopUnderlightEffect();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopUnderlightEffect(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    let _status = match claim_underlights() {
+        Some(status) => status,
+        None => return,
+    };
+    UNDERLIGHT_ANIMATION.lock().unwrap().stop();
+    let mut leds = SN3218LEDS.lock().unwrap();
+    if let Err(e) = leds.clear_underlighting() {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Checks whether an underlight effect is playing.
+///
+/// # Returns
+///
+/// `true` while an effect is playing, `false` once it has been stopped or a non-looping
+/// sequence has finished.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isUnderlightEffectRunning();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isUnderlightEffectRunning(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    UNDERLIGHT_ANIMATION.lock().unwrap().is_running() as jboolean
+}
+
+//
+//
+//                        STATUS INDICATOR
+//
+//
+
+/// Locks the status indicator for an application write to the underlights, so a status
+/// cannot change halfway through it. Returns `None` if the write must be skipped because a
+/// safety status (low battery, obstacle warning or error) is shown.
+fn claim_underlights() -> Option<MutexGuard<'static, StatusManager>> {
+    let mut status = STATUS_MANAGER.lock().unwrap();
+    if status.claim_underlights() {
+        Some(status)
+    } else {
+        None
+    }
+}
+
+/// Like `claim_underlights`, for a write to the button LEDs. Returns `None` while the
+/// error status blinks the button LEDs.
+fn claim_buttons() -> Option<MutexGuard<'static, StatusManager>> {
+    let mut status = STATUS_MANAGER.lock().unwrap();
+    if status.claim_buttons() {
+        Some(status)
+    } else {
+        None
+    }
+}
+
+/// Starts or stops showing the robot's status on the underlights and button LEDs.
+/// While enabled, the highest priority active status or layer is shown. Setting the
+/// underlights or button LEDs directly still works, but is replaced when the status next
+/// changes. Underlight writes are ignored while a safety status (low battery, obstacle
+/// warning or error) is shown, and button LED writes and feedback while the error status is.
+///
+/// # Arguments
+///
+/// * `enabled` - `true` to show status, `false` to turn the LEDs off and leave them to the application.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is an error updating the LEDs.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setStatusIndicatorEnabled(boolean enabled);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setStatusIndicatorEnabled(
+    mut env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+) {
+    if let Err(e) = STATUS_MANAGER.lock().unwrap().set_enabled(enabled != 0) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Turns a status on or off. Idle is on by default. Low battery, obstacle warning and
+/// error always take priority over application layers.
+///
+/// # Arguments
+///
+/// * `status` - The status (0 = booting, 1 = idle, 2 = moving, 3 = obstacle warning,
+///   4 = error, 5 = low battery, 6 = remote connected).
+/// * `active` - `true` to turn the status on, `false` to turn it off.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the status is invalid, or an `Exception`
+/// if there is an error updating the LEDs.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setStatus(int status, boolean active);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setStatus(
+    mut env: JNIEnv,
+    _class: JClass,
+    status: jint,
+    active: jboolean,
+) {
+    let status = match SystemStatus::from_id(status) {
+        Ok(status) => status,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+    if let Err(e) = STATUS_MANAGER.lock().unwrap().set_status(status, active != 0) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Checks whether a status is on.
+///
+/// # Arguments
+///
+/// * `status` - The status ID, as for `setStatus`.
+///
+/// # Returns
+///
+/// `true` if the status is on.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the status is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isStatusActive(int status);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isStatusActive(
+    mut env: JNIEnv,
+    _class: JClass,
+    status: jint,
+) -> jboolean {
+    match SystemStatus::from_id(status) {
+        Ok(status) => STATUS_MANAGER.lock().unwrap().is_status_active(status) as jboolean,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            0
+        }
+    }
+}
+
+/// Adds an application layer showing one of the built-in underlight effects. Layers
+/// are shown above idle, moving, remote connected and booting, and below low battery,
+/// obstacle warning and error.
+///
+/// # Arguments
+///
+/// * `priority` - The priority of the layer (0 to 99). Among equal priorities, the newest layer is shown.
+/// * `effect` - The effect ID, as for `startUnderlightEffect`.
+/// * `red` - Red color component (0 to 255).
+/// * `green` - Green color component (0 to 255).
+/// * `blue` - Blue color component (0 to 255).
+/// * `period_ms` - Duration of one cycle of the effect in milliseconds.
+///
+/// # Returns
+///
+/// The ID of the layer, used to remove it.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if any argument is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int pushStatusLayer(int priority, int effect, int red, int green, int blue, int periodMs);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_pushStatusLayer(
+    mut env: JNIEnv,
+    _class: JClass,
+    priority: jint,
+    effect: jint,
+    red: jint,
+    green: jint,
+    blue: jint,
+    period_ms: jint,
+) -> jint {
+    let period = Duration::from_millis(period_ms.max(0) as u64);
+    let result = Effect::from_id(effect, Color::rgb(red as u8, green as u8, blue as u8), period)
+        .and_then(|effect| STATUS_MANAGER.lock().unwrap().push_layer(priority, effect));
+    match result {
+        Ok(id) => id as jint,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            -1
+        }
+    }
+}
+
+/// Removes an application layer.
+///
+/// # Arguments
+///
+/// * `layer_id` - The ID returned by `pushStatusLayer`.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is no layer with that ID.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void removeStatusLayer(int layerId);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_removeStatusLayer(
+    mut env: JNIEnv,
+    _class: JClass,
+    layer_id: jint,
+) {
+    if let Err(e) = STATUS_MANAGER.lock().unwrap().remove_layer(layer_id as u32) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+//
+//
+//                        LED STATE
+//
+//
+
+/// Reads the color of an underlight, as last set by the application or an effect. The
+/// color is before gamma, white balance and brightness limit correction.
+///
+/// # Arguments
+///
+/// * `light_id` - The ID of the underlight (0 to 5).
+///
+/// # Returns
+///
+/// The color packed as `0xRRGGBB`.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the ID is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getUnderlight(int lightId);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getUnderlight(
+    mut env: JNIEnv,
+    _class: JClass,
+    light_id: jint,
+) -> jint {
+    let frame = SN3218LEDS.lock().unwrap().frame();
+    match frame.get(light_id as usize) {
+        Some(color) => color.packed() as jint,
+        None => {
+            let _ = env.throw_new(
+                "java/lang/IllegalArgumentException",
+                format!("Invalid RGB LED ID: {}. Must be between 0 and 5.", light_id),
+            );
+            0
+        }
+    }
+}
+
+/// Reads the colors of every underlight, before color correction.
+///
+/// # Returns
+///
+/// One color packed as `0xRRGGBB` per underlight, indexed by light ID.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int[] getUnderlightFrame();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getUnderlightFrame(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jintArray {
+    let frame = SN3218LEDS.lock().unwrap().frame();
+    let colors: Vec<i32> = frame.iter().map(|color| color.packed() as i32).collect();
+    let result = env.new_int_array(colors.len() as i32).and_then(|array| {
+        env.set_int_array_region(&array, 0, &colors)?;
+        Ok(array.into_raw())
+    });
+    match result {
+        Ok(array) => array,
+        Err(e) => {
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            }
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Reads the brightness of a button LED, as last set by the application or an effect.
+///
+/// # Arguments
+///
+/// * `button_led_id` - The ID of the button LED (0 for A, 1 for B, 2 for X, 3 for Y).
+///
+/// # Returns
+///
+/// The brightness (0.0 to 1.0).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the ID is invalid.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native double getButtonLed(int buttonLedId);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getButtonLed(
+    mut env: JNIEnv,
+    _class: JClass,
+    button_led_id: jint,
+) -> jdouble {
+    match BUTTONLEDS.lock().unwrap().button_led(button_led_id as u8) {
+        Ok(level) => level,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            0.0
+        }
+    }
+}
+
+/// Saves the state of every LED, including any effects playing, so it can be restored
+/// with `popLedSnapshot` after showing something temporary.
+///
+/// # Returns
+///
+/// The number of snapshots saved, including this one.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if too many snapshots are saved without being restored.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int pushLedSnapshot();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_pushLedSnapshot(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let mut snapshots = LED_SNAPSHOTS.lock().unwrap();
+    let result = snapshots.check_room()
+        .and_then(|_| LedSnapshot::capture(&SN3218LEDS, &UNDERLIGHT_ANIMATION, &BUTTONLEDS, &BUTTON_LED_EFFECTS))
+        .and_then(|snapshot| snapshots.push(snapshot));
+    match result {
+        Ok(count) => count as jint,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            snapshots.count() as jint
+        }
+    }
+}
+
+/// Restores the LEDs to the state saved by the last `pushLedSnapshot`, replacing any
+/// effects playing. Restored effects start again from the beginning.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalStateException` if no snapshot is saved or a safety status is
+/// shown on the LEDs, in which case the snapshot is kept, or an `Exception` if there is an
+/// error restoring the LEDs.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void popLedSnapshot();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_popLedSnapshot(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    //Both claims are made first, so a refused restore leaves the snapshot saved
+    let mut status = STATUS_MANAGER.lock().unwrap();
+    if !(status.claim_underlights() && status.claim_buttons()) {
+        let _ = env.throw_new("java/lang/IllegalStateException", "A safety status is shown on the LEDs.");
+        return;
+    }
+    let snapshot = match LED_SNAPSHOTS.lock().unwrap().pop() {
+        Some(snapshot) => snapshot,
+        None => {
+            let _ = env.throw_new("java/lang/IllegalStateException", "No LED snapshot to restore.");
+            return;
+        }
+    };
+    if let Err(e) = snapshot.restore(&SN3218LEDS, &UNDERLIGHT_ANIMATION, &BUTTONLEDS, &BUTTON_LED_EFFECTS) {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+//
+//
+//                        LED POWER
+//
+//
+
+/// Rewrites the underlights and button LEDs so they pick up a change in the power budget.
+fn refresh_leds() {
+    POWER_BUDGET.lock().unwrap().refresh_started();
+    let _ = SN3218LEDS.lock().unwrap().update_underlighting();
+    let _ = BUTTONLEDS.lock().unwrap().refresh();
+}
+
+/// Limits the current drawn by the underlights and button LEDs, estimated from their PWM
+/// values. When the estimate is over the limit, every LED is dimmed by the same factor.
+///
+/// # Arguments
+///
+/// * `limit_ma` - The current limit in mA, or 0 to remove the limit.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the limit is negative or not finite.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setLedPowerBudget(double limitMa);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setLedPowerBudget(
+    mut env: JNIEnv,
+    _class: JClass,
+    limit_ma: jdouble,
+) {
+    let result = POWER_BUDGET.lock().unwrap().set_limit(Some(limit_ma).filter(|limit| *limit != 0.0));
+    match result {
+        Ok(()) => refresh_leds(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+        }
+    }
+}
+
+/// Limits the current drawn by the underlights and button LEDs while the motors are
+/// accelerating, when the battery is most likely to brown out. The limit applies for a
+/// short time after any motor speeds up or reverses, on top of the normal power budget.
+///
+/// # Arguments
+///
+/// * `limit_ma` - The current limit in mA, or 0 to remove the limit.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the limit is negative or not finite.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setLedAccelerationPowerBudget(double limitMa);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setLedAccelerationPowerBudget(
+    mut env: JNIEnv,
+    _class: JClass,
+    limit_ma: jdouble,
+) {
+    let result = POWER_BUDGET.lock().unwrap().set_acceleration_limit(Some(limit_ma).filter(|limit| *limit != 0.0));
+    match result {
+        Ok(()) => refresh_leds(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+        }
+    }
+}
+
+/// Estimates the current drawn by the underlights and button LEDs, after any power budget
+/// dimming. This is only an estimate from the PWM values and the per-LED currents in `config.rs`.
+///
+/// # Returns
+///
+/// The estimated current in mA.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native double getLedCurrentEstimate();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getLedCurrentEstimate(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jdouble {
+    POWER_BUDGET.lock().unwrap().estimated_ma()
+}
+
+//
+//
+//                        CAMERA
+//
+//
+
+/// Runs `f` on the camera, opening it with the default configuration if needed.
+fn with_camera<T>(f: impl FnOnce(&mut CameraController) -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+    let mut camera = CAMERA.lock().unwrap();
+    if camera.is_none() {
+        *camera = Some(CameraController::new(CameraConfig::default())?);
+    }
+    f(camera.as_mut().unwrap())
+}
+
+/// Starts the continuous capture from `CAMERA`, opening the camera with the default
+/// configuration if needed. The capture stops by itself once the camera is closed.
+fn start_capture() -> Result<(), Box<dyn Error>> {
+    with_camera(|_| Ok(()))?;
+    FRAME_CAPTURE.start(Arc::clone(&CAMERA) as _);
+    Ok(())
+}
+
+/// Opens the camera with a new configuration, closing the current one first. If the new
+/// configuration cannot be started, the previous one is restored.
+fn configure_camera(config: CameraConfig) -> Result<(), Box<dyn Error>> {
+    let mut camera = CAMERA.lock().unwrap();
+    if camera.as_ref().is_some_and(|camera| *camera.config() == config) {
+        return Ok(());
+    }
+
+    //A running capture is restarted, so its buffered frames are all from the same mode
+    let capturing = FRAME_CAPTURE.is_running();
+    FRAME_CAPTURE.stop();
+
+    //The device must be released before it can be opened again
+    let previous = camera.take().map(|camera| camera.config().clone());
+    let result = match CameraController::new(config) {
+        Ok(controller) => {
+            *camera = Some(controller);
+            Ok(())
+        }
+        Err(e) => {
+            *camera = previous.and_then(|config| CameraController::new(config).ok());
+            Err(e)
+        }
+    };
+    if capturing && camera.is_some() {
+        FRAME_CAPTURE.start(Arc::clone(&CAMERA) as _);
+    }
+    result
+}
+
+/// Opens the camera, or switches an open camera to another device or capture mode. A
+/// running continuous capture is restarted in the new mode.
+///
+/// # Arguments
+///
+/// * `device` - The V4L2 device path, e.g. "/dev/video0", or "replay:" followed by images to
+///   replay instead of the camera.
+/// * `width` - The frame width in pixels.
+/// * `height` - The frame height in pixels.
+/// * `format` - The FourCC pixel format, e.g. "MJPG" or "YUYV".
+/// * `fps` - The frame rate in frames per second.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if an argument is invalid, an
+/// `IllegalStateException` if a recording is running, as its file is written in one mode, or
+/// an `Exception` if the camera cannot capture in that mode. The previous mode is kept in
+/// those cases.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void configureCamera(String device, int width, int height, String format, int fps);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_configureCamera(
+    mut env: JNIEnv,
+    _class: JClass,
+    device: JString,
+    width: jint,
+    height: jint,
+    format: JString,
+    fps: jint,
+) {
+    let config = (|| -> Result<CameraConfig, Box<dyn Error>> {
+        if width <= 0 || height <= 0 || fps <= 0 {
+            return Err(format!("Invalid camera mode: {}x{} at {} fps.", width, height, fps).into());
+        }
+        Ok(CameraConfig {
+            device: get_string(&mut env, &device)?,
+            resolution: (width as u32, height as u32),
+            format: CameraConfig::fourcc(&get_string(&mut env, &format)?)?,
+            interval: (1, fps as u32),
+        })
+    })();
+
+    match config {
+        Ok(config) => {
+            //Held while reconfiguring, so a recording cannot start in the old mode meanwhile
+            let recorder = RECORDER.lock().unwrap();
+            let unchanged = CAMERA.lock().unwrap().as_ref().is_some_and(|camera| *camera.config() == config);
+            if !unchanged && recorder.as_ref().is_some_and(|recording| !recording.is_finished()) {
+                let _ = env.throw_new("java/lang/IllegalStateException", "The camera cannot be reconfigured while recording.");
+                return;
+            }
+            if let Err(e) = configure_camera(config) {
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            }
+        }
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+        }
+    }
+}
+
+/// Stops the camera, and any continuous capture, and releases the device. It is opened
+/// again by the next capture.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera cannot be stopped cleanly.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void closeCamera();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_closeCamera(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    FRAME_CAPTURE.stop();
+    let camera = CAMERA.lock().unwrap().take();
+    if let Some(mut camera) = camera {
+        if let Err(e) = camera.stop() {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+        }
+    }
+}
+
+/// Converts a discovered camera into a `bisocm.swiftbot.lib.CameraDevice`.
+fn camera_device_to_java<'local>(env: &mut JNIEnv<'local>, device: &CameraDevice) -> Result<JObject<'local>, Box<dyn Error>> {
+    let modes = env.new_object_array(device.modes.len() as i32, "bisocm/swiftbot/lib/CameraMode", JObject::null())?;
+    for (i, mode) in device.modes.iter().enumerate() {
+        let format = env.new_string(String::from_utf8_lossy(&mode.format))?;
+        let description = env.new_string(&mode.description)?;
+        let obj = env.new_object(
+            "bisocm/swiftbot/lib/CameraMode",
+            "(Ljava/lang/String;Ljava/lang/String;IIII)V",
+            &[
+                (&format).into(),
+                (&description).into(),
+                (mode.resolution.0 as jint).into(),
+                (mode.resolution.1 as jint).into(),
+                (mode.interval.0 as jint).into(),
+                (mode.interval.1 as jint).into(),
+            ],
+        )?;
+        env.set_object_array_element(&modes, i as i32, obj)?;
+    }
+
+    let path = env.new_string(&device.path)?;
+    let name = env.new_string(&device.name)?;
+    let obj = env.new_object(
+        "bisocm/swiftbot/lib/CameraDevice",
+        "(Ljava/lang/String;Ljava/lang/String;[Lbisocm/swiftbot/lib/CameraMode;)V",
+        &[(&path).into(), (&name).into(), (&modes).into()],
+    )?;
+    Ok(obj)
+}
+
+/// Finds the cameras attached to the robot and the capture modes each supports, so a
+/// mode can be picked before calling `configureCamera`.
+///
+/// # Returns
+///
+/// A Java `CameraDevice[]`, one per V4L2 capture device in device number order. Every
+/// supported format, resolution and frame interval combination is listed as a `CameraMode`.
+/// Continuous ranges are reported by their smallest and largest values.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Returns `null` with a pending Java exception if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native CameraDevice[] listCameras();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_listCameras(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobjectArray {
+    let devices = discover_cameras();
+    let result = (|| -> Result<jobjectArray, Box<dyn Error>> {
+        let array = env.new_object_array(devices.len() as i32, "bisocm/swiftbot/lib/CameraDevice", JObject::null())?;
+        for (i, device) in devices.iter().enumerate() {
+            let obj = camera_device_to_java(&mut env, device)?;
+            env.set_object_array_element(&array, i as i32, obj)?;
+        }
+        Ok(array.into_raw())
+    })();
+    result.unwrap_or(std::ptr::null_mut())
+}
+
+/// Lists the pixel formats the camera supports.
+///
+/// # Returns
+///
+/// A Java `String[]` of FourCC codes, e.g. "MJPG" and "YUYV".
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera cannot be opened or queried.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native String[] getCameraFormats();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraFormats(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobjectArray {
+    let result = with_camera(|camera| camera.formats()).and_then(|formats| {
+        let array = env.new_object_array(formats.len() as i32, "java/lang/String", JObject::null())?;
+        for (i, format) in formats.iter().enumerate() {
+            let format = env.new_string(String::from_utf8_lossy(format))?;
+            env.set_object_array_element(&array, i as i32, format)?;
+        }
+        Ok(array.into_raw())
+    });
+    match result {
+        Ok(array) => array,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Lists the resolutions the camera supports for a pixel format. Cameras with a continuous
+/// range of sizes report only the smallest and largest.
+///
+/// # Arguments
+///
+/// * `format` - The FourCC pixel format, e.g. "MJPG".
+///
+/// # Returns
+///
+/// A Java `int[]` of width and height pairs: `{width0, height0, width1, height1, ...}`.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the format is invalid, or the camera cannot be opened or queried.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int[] getCameraResolutions(String format);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraResolutions(
+    mut env: JNIEnv,
+    _class: JClass,
+    format: JString,
+) -> jintArray {
+    let result = get_string(&mut env, &format)
+        .and_then(|format| CameraConfig::fourcc(&format))
+        .and_then(|format| with_camera(|camera| camera.resolutions(&format)))
+        .and_then(|resolutions| {
+            let sizes: Vec<jint> = resolutions
+                .iter()
+                .flat_map(|(width, height)| [*width as jint, *height as jint])
+                .collect();
+            let array = env.new_int_array(sizes.len() as i32)?;
+            env.set_int_array_region(&array, 0, &sizes)?;
+            Ok(array.into_raw())
+        });
+    match result {
+        Ok(array) => array,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Converts a camera control into a `bisocm.swiftbot.lib.CameraControl`.
+fn camera_control_to_java<'local>(env: &mut JNIEnv<'local>, control: &CameraControl) -> Result<JObject<'local>, Box<dyn Error>> {
+    let indices: Vec<jint> = control.menu.iter().map(|(index, _)| *index as jint).collect();
+    let menu_indices = env.new_int_array(indices.len() as i32)?;
+    env.set_int_array_region(&menu_indices, 0, &indices)?;
+    let menu_names = env.new_object_array(control.menu.len() as i32, "java/lang/String", JObject::null())?;
+    for (i, (_, name)) in control.menu.iter().enumerate() {
+        let name = env.new_string(name)?;
+        env.set_object_array_element(&menu_names, i as i32, name)?;
+    }
+
+    let key = env.new_string(&control.key)?;
+    let name = env.new_string(&control.name)?;
+    let obj = env.new_object(
+        "bisocm/swiftbot/lib/CameraControl",
+        "(ILjava/lang/String;Ljava/lang/String;IJJJJJ[I[Ljava/lang/String;ZZ)V",
+        &[
+            (control.id as jint).into(),
+            (&key).into(),
+            (&name).into(),
+            control.kind.id().into(),
+            control.value.into(),
+            control.default.into(),
+            control.minimum.into(),
+            control.maximum.into(),
+            control.step.into(),
+            (&menu_indices).into(),
+            (&menu_names).into(),
+            control.read_only.into(),
+            control.inactive.into(),
+        ],
+    )?;
+    Ok(obj)
+}
+
+/// Lists the image controls the camera supports, such as exposure, gain, white balance,
+/// brightness, contrast and flipping, with their current values and allowed ranges.
+///
+/// # Returns
+///
+/// A Java `CameraControl[]` in the order the driver reports them.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera cannot be opened or queried.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native CameraControl[] listCameraControls();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_listCameraControls(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobjectArray {
+    let result = with_camera(|camera| camera.controls()).and_then(|controls| {
+        let array = env.new_object_array(controls.len() as i32, "bisocm/swiftbot/lib/CameraControl", JObject::null())?;
+        for (i, control) in controls.iter().enumerate() {
+            let obj = camera_control_to_java(&mut env, control)?;
+            env.set_object_array_element(&array, i as i32, obj)?;
+        }
+        Ok(array.into_raw())
+    });
+    match result {
+        Ok(array) => array,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Reads one camera control.
+///
+/// # Arguments
+///
+/// * `name` - The control's key, e.g. "exposure_auto", or its driver name, e.g. "Auto Exposure".
+///
+/// # Returns
+///
+/// A Java `CameraControl` with the current value.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera has no such control, or cannot be opened or queried.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native CameraControl getCameraControl(String name);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraControl(
+    mut env: JNIEnv,
+    _class: JClass,
+    name: JString,
+) -> jobject {
+    let result = get_string(&mut env, &name)
+        .and_then(|name| with_camera(|camera| camera.control(&name)))
+        .and_then(|control| camera_control_to_java(&mut env, &control));
+    match result {
+        Ok(obj) => obj.into_raw(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Sets a camera control. Manual exposure, gain and white balance temperature usually only
+/// take effect once the matching automatic control is turned off.
+///
+/// # Arguments
+///
+/// * `name` - The control's key, e.g. "exposure_absolute", or its driver name.
+/// * `value` - The new value: a number within the control's range, 0 or 1 for a boolean,
+///   or a menu item index.
+///
+/// # Returns
+///
+/// A Java `CameraControl` with the value the driver applied.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera has no such control, the control is read-only,
+/// the value is out of range, or the driver rejects it.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native CameraControl setCameraControl(String name, long value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraControl(
+    mut env: JNIEnv,
+    _class: JClass,
+    name: JString,
+    value: jlong,
+) -> jobject {
+    let result = get_string(&mut env, &name)
+        .and_then(|name| with_camera(|camera| camera.set_control(&name, value)))
+        .and_then(|control| camera_control_to_java(&mut env, &control));
+    match result {
+        Ok(obj) => obj.into_raw(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Captures an image from the camera and returns it as a byte array.
+///
+/// # Returns
+///
+/// A Java byte array containing the JPEG image data.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if there is an error capturing the image.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native byte[] captureImage();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureImage(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jbyteArray {
+    //While capturing continuously the camera is busy, so the next frame is taken from the capture
+    let result = if FRAME_CAPTURE.is_running() {
+        let latest = FRAME_CAPTURE.latest().map(|frame| frame.sequence);
+        FRAME_CAPTURE.wait_for_frame(latest, CAMERA_FRAME_TIMEOUT)
+            .map(|frame| frame.data.clone())
+            .ok_or_else(|| "Timeout waiting for a camera frame".into())
+    } else {
+        with_camera(|camera| camera.capture_image())
+    };
+    match result {
+        Ok(image_data) => {
+            //Convert Rust Vec<u8> to Java byte array
+            let buf = env.byte_array_from_slice(&image_data).unwrap();
+            **buf
+        }
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            //Return null in case of error
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Starts capturing frames continuously on a background thread. The latest frames are kept
+/// for `getLatestFrame`, and each is passed to the registered `FrameListener`s. While
+/// running, `captureImage` returns the next frame from the capture.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera cannot be opened.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void startCameraCapture();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startCameraCapture(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    if let Err(e) = start_capture() {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Stops the continuous capture. The camera stays open.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopCameraCapture();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopCameraCapture(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    FRAME_CAPTURE.stop();
+}
+
+/// Checks whether the continuous capture is running. It stops by itself if the camera
+/// fails, and the reason is thrown.
+///
+/// # Returns
+///
+/// `true` while frames are being captured.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` with the camera error if the capture stopped because of it.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isCameraCapturing();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isCameraCapturing(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    if FRAME_CAPTURE.is_running() {
+        return 1;
+    }
+    if let Some(error) = FRAME_CAPTURE.error() {
+        let _ = env.throw_new("java/lang/Exception", error);
+    }
+    0
+}
+
+/// Converts a frame into a `bisocm.swiftbot.lib.CameraFrame`.
+fn frame_to_java<'local>(env: &mut JNIEnv<'local>, class: &JClass, frame: &Frame) -> Result<JObject<'local>, Box<dyn Error>> {
+    let format = env.new_string(String::from_utf8_lossy(&frame.format))?;
+    let data = env.byte_array_from_slice(&frame.data)?;
+    let obj = env.new_object(
+        class,
+        "(JJIILjava/lang/String;[B)V",
+        &[
+            (frame.sequence as jlong).into(),
+            (frame.timestamp_us as jlong).into(),
+            (frame.resolution.0 as jint).into(),
+            (frame.resolution.1 as jint).into(),
+            (&format).into(),
+            (&data).into(),
+        ],
+    )?;
+    Ok(obj)
+}
+
+/// Gets the latest frame from the continuous capture without waiting.
+///
+/// # Returns
+///
+/// A `bisocm.swiftbot.lib.CameraFrame`, or `null` if no frame has been captured yet.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Returns `null` with a pending Java exception if the frame cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native CameraFrame getLatestFrame();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getLatestFrame(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobject {
+    let Some(frame) = FRAME_CAPTURE.latest() else {
+        return std::ptr::null_mut();
+    };
+    let result = env.find_class("bisocm/swiftbot/lib/CameraFrame")
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|class| frame_to_java(&mut env, &class, &frame));
+    match result {
+        Ok(obj) => obj.into_raw(),
+        Err(e) => {
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            }
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Gets every frame kept by the continuous capture, up to the last 8 by default.
+///
+/// # Returns
+///
+/// A Java `CameraFrame[]`, oldest first. Empty if no frame has been captured yet.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Returns `null` with a pending Java exception if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native CameraFrame[] getBufferedFrames();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getBufferedFrames(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobjectArray {
+    let frames = FRAME_CAPTURE.frames();
+    let result = (|| -> Result<jobjectArray, Box<dyn Error>> {
+        let class = env.find_class("bisocm/swiftbot/lib/CameraFrame")?;
+        let array = env.new_object_array(frames.len() as i32, &class, JObject::null())?;
+        for (i, frame) in frames.iter().enumerate() {
+            let obj = frame_to_java(&mut env, &class, frame)?;
+            env.set_object_array_element(&array, i as i32, obj)?;
+        }
+        Ok(array.into_raw())
+    })();
+    result.unwrap_or(std::ptr::null_mut())
+}
+
+/// Number of values `readFrame` writes to its metadata array.
+const FRAME_METADATA_LEN: usize = 5;
+
+/// Sets the sequence number in a `readFrame` metadata array to -1, meaning no frame was read.
+fn write_no_frame(env: &mut JNIEnv, metadata: &JLongArray) {
+    if env.get_array_length(metadata).unwrap_or(0) > 0 {
+        let _ = env.set_long_array_region(metadata, 0, &[-1]);
+    }
+}
+
+/// Copies a frame into a direct buffer and its details into a metadata array.
+/// Returns the frame length, or minus the length if the buffer is too small.
+fn write_frame_to_buffer(env: &mut JNIEnv, buffer: &JByteBuffer, metadata: &JLongArray, frame: &Frame) -> Result<jint, Box<dyn Error>> {
+    if env.get_array_length(metadata)? < FRAME_METADATA_LEN as i32 {
+        return Err(format!("The metadata array must hold at least {} values.", FRAME_METADATA_LEN).into());
+    }
+    let address = env.get_direct_buffer_address(buffer)?;
+    let capacity = env.get_direct_buffer_capacity(buffer)?;
+    //A direct buffer holds at most `jint::MAX` bytes, so a longer frame could never be read
+    let length = jint::try_from(frame.data.len())
+        .map_err(|_| format!("The frame is too large for a ByteBuffer: {} bytes.", frame.data.len()))?;
+
+    let values = [
+        frame.sequence as jlong,
+        frame.timestamp_us as jlong,
+        frame.resolution.0 as jlong,
+        frame.resolution.1 as jlong,
+        u32::from_le_bytes(frame.format) as jlong,
+    ];
+    env.set_long_array_region(metadata, 0, &values)?;
+
+    if length as usize > capacity {
+        return Ok(-length);
+    }
+    //SAFETY: the address and capacity come from the JVM for a live direct buffer, and the
+    //length was checked against the capacity
+    unsafe { std::ptr::copy_nonoverlapping(frame.data.as_ptr(), address, length as usize) };
+    Ok(length)
+}
+
+/// Copies a frame from the continuous capture into a direct `ByteBuffer`, so frames can be
+/// processed without allocating a Java array for each one. The buffer and metadata array
+/// can be reused for every frame.
+///
+/// # Arguments
+///
+/// * `buffer` - A direct `ByteBuffer` to copy the frame data into, from position 0. Its
+///   position and limit are left unchanged.
+/// * `metadata` - An array of at least 5 values, set to the sequence number, timestamp in
+///   microseconds since the Unix epoch, width, height and FourCC pixel format (packed little
+///   endian, as V4L2 does) of the frame.
+/// * `after_sequence` - Only a frame with a higher sequence number is read, or -1 for any frame.
+/// * `timeout_ms` - How long to wait for such a frame, or 0 to return at once.
+///
+/// # Returns
+///
+/// The frame length in bytes, or minus the frame length if the buffer is too small. The
+/// metadata is written in that case, so the frame can be told apart. If no frame arrived in
+/// time, 0 is returned and the sequence number in the metadata is set to -1, as a frame
+/// can also be empty.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the buffer is not direct, the metadata array
+/// is too short or the frame is too large for a `ByteBuffer`, or an `IllegalStateException`
+/// if the capture is not running and no frame matches.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int readFrame(ByteBuffer buffer, long[] metadata, long afterSequence, int timeoutMs);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_readFrame(
+    mut env: JNIEnv,
+    _class: JClass,
+    buffer: JByteBuffer,
+    metadata: JLongArray,
+    after_sequence: jlong,
+    timeout_ms: jint,
+) -> jint {
+    let after = (after_sequence >= 0).then_some(after_sequence as u64);
+    let timeout = Duration::from_millis(timeout_ms.max(0) as u64);
+    let frame = match FRAME_CAPTURE.wait_for_frame(after, timeout) {
+        Some(frame) => frame,
+        None if FRAME_CAPTURE.is_running() => {
+            write_no_frame(&mut env, &metadata);
+            return 0;
+        }
+        None => {
+            write_no_frame(&mut env, &metadata);
+            let _ = env.throw_new("java/lang/IllegalStateException", "The camera capture is not running.");
+            return 0;
+        }
+    };
+
+    match write_frame_to_buffer(&mut env, &buffer, &metadata, &frame) {
+        Ok(length) => length,
+        Err(e) => {
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            }
+            0
+        }
+    }
+}
+
+/// Converts a decoded frame into a `bisocm.swiftbot.lib.DecodedFrame`.
+fn decoded_frame_to_java<'local>(env: &mut JNIEnv<'local>, frame: &DecodedFrame) -> Result<JObject<'local>, Box<dyn Error>> {
+    let pixels = env.byte_array_from_slice(&frame.pixels)?;
+    let format = match frame.format {
+        PixelFormat::Rgb24 => 0,
+        PixelFormat::Gray8 => 1,
+    };
+    let obj = env.new_object(
+        "bisocm/swiftbot/lib/DecodedFrame",
+        "(JJIIII[B)V",
+        &[
+            (frame.sequence as jlong).into(),
+            (frame.timestamp_us as jlong).into(),
+            (frame.width as jint).into(),
+            (frame.height as jint).into(),
+            (frame.stride as jint).into(),
+            jint::from(format).into(),
+            (&pixels).into(),
+        ],
+    )?;
+    Ok(obj)
+}
+
+/// Reads the size limit passed from Java, where 0 means no limit on that side.
+fn max_size(max_width: jint, max_height: jint) -> Result<Option<(u32, u32)>, Box<dyn Error>> {
+    if max_width < 0 || max_height < 0 {
+        return Err(format!("Invalid maximum size: {}x{}.", max_width, max_height).into());
+    }
+    Ok((max_width > 0 || max_height > 0).then_some((max_width as u32, max_height as u32)))
+}
+
+/// Decodes a frame from the continuous capture to RGB or grayscale pixels, so it can be
+/// processed without a JPEG decoder in Java.
+///
+/// # Arguments
+///
+/// * `format` - The pixel format to decode to (0 = RGB, 3 bytes per pixel; 1 = grayscale,
+///   1 byte per pixel).
+/// * `max_width` - The largest width wanted, or 0 for no limit. The frame is shrunk to fit
+///   within the limits, keeping its aspect ratio, but never enlarged.
+/// * `max_height` - The largest height wanted, or 0 for no limit.
+/// * `after_sequence` - Only a frame with a higher sequence number is decoded, or -1 for any frame.
+/// * `timeout_ms` - How long to wait for such a frame, or 0 to return at once.
+///
+/// # Returns
+///
+/// A `bisocm.swiftbot.lib.DecodedFrame`, or `null` if no frame arrived in time.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if an argument is invalid, an
+/// `IllegalStateException` if the capture is not running and no frame matches, or an
+/// `Exception` if the frame cannot be decoded. Only MJPG and YUYV frames can be decoded.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native DecodedFrame getDecodedFrame(int format, int maxWidth, int maxHeight, long afterSequence, int timeoutMs);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getDecodedFrame(
+    mut env: JNIEnv,
+    _class: JClass,
+    format: jint,
+    max_width: jint,
+    max_height: jint,
+    after_sequence: jlong,
+    timeout_ms: jint,
+) -> jobject {
+    let args = PixelFormat::from_id(format).and_then(|format| Ok((format, max_size(max_width, max_height)?)));
+    let (format, max_size) = match args {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    let after = (after_sequence >= 0).then_some(after_sequence as u64);
+    let timeout = Duration::from_millis(timeout_ms.max(0) as u64);
+    let frame = match FRAME_CAPTURE.wait_for_frame(after, timeout) {
+        Some(frame) => frame,
+        None if FRAME_CAPTURE.is_running() => return std::ptr::null_mut(),
+        None => {
+            let _ = env.throw_new("java/lang/IllegalStateException", "The camera capture is not running.");
+            return std::ptr::null_mut();
+        }
+    };
+
+    let result = decode_frame(&frame, format, max_size)
+        .and_then(|decoded| decoded_frame_to_java(&mut env, &decoded));
+    match result {
+        Ok(obj) => obj.into_raw(),
+        Err(e) => {
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            }
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Decodes a JPEG image, such as one returned by `captureImage`, to RGB or grayscale pixels.
+///
+/// # Arguments
+///
+/// * `data` - The JPEG image.
+/// * `format` - The pixel format to decode to (0 = RGB, 1 = grayscale).
+/// * `max_width` - The largest width wanted, or 0 for no limit.
+/// * `max_height` - The largest height wanted, or 0 for no limit.
+///
+/// # Returns
+///
+/// A `bisocm.swiftbot.lib.DecodedFrame` with a sequence number and timestamp of 0.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if an argument is invalid, or an `Exception`
+/// if the image cannot be decoded.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native DecodedFrame decodeImage(byte[] data, int format, int maxWidth, int maxHeight);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_decodeImage(
+    mut env: JNIEnv,
+    _class: JClass,
+    data: JByteArray,
+    format: jint,
+    max_width: jint,
+    max_height: jint,
+) -> jobject {
+    let args = PixelFormat::from_id(format).and_then(|format| Ok((format, max_size(max_width, max_height)?)));
+    let (format, max_size) = match args {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    let result = env.convert_byte_array(&data)
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|data| {
+            //The size is read from the JPEG itself
+            let frame = Frame { sequence: 0, timestamp_us: 0, resolution: (0, 0), format: *b"JPEG", data };
+            decode_frame(&frame, format, max_size)
+        })
+        .and_then(|decoded| decoded_frame_to_java(&mut env, &decoded));
+    match result {
+        Ok(obj) => obj.into_raw(),
+        Err(e) => {
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            }
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Passes frames from the capture to `NativeBindings.onFrame` on a thread of their own, so
+/// a slow listener never holds up the capture. Frames arriving while the listener is busy
+/// are dropped.
+fn deliver_frames(jvm: jni::JavaVM, bindings: GlobalRef, frame_class: GlobalRef, frames: mpsc::Receiver<Arc<Frame>>) {
+    let Ok(mut env) = jvm.attach_current_thread_as_daemon() else { return };
+    while let Ok(frame) = frames.recv() {
+        let _ = env.with_local_frame(8, |env| -> Result<(), Box<dyn Error>> {
+            let obj = frame_to_java(env, <&JClass>::from(frame_class.as_obj()), &frame)?;
+            env.call_static_method(
+                <&JClass>::from(bindings.as_obj()),
+                "onFrame",
+                "(Lbisocm/swiftbot/lib/CameraFrame;)V",
+                &[(&obj).into()],
+            )?;
+            Ok(())
+        });
+        //An exception thrown by a listener must not stop the delivery of later frames
+        if env.exception_check().unwrap_or(false) {
+            let _ = env.exception_describe();
+            let _ = env.exception_clear();
+        }
+    }
+}
+
+/// Starts passing captured frames to `NativeBindings.onFrame`, which forwards them to the
+/// registered `FrameListener`s.
+///
+/// # Returns
+///
+/// The subscription ID, for `unsubscribeFrames`.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the callback cannot be set up.
+///
+/// # JNI Signature
+///
+/// ```java
+/// private static native int subscribeFrames();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_subscribeFrames(
+    mut env: JNIEnv,
+    class: JClass,
+) -> jint {
+    //Classes are looked up here, as application classes cannot be found from a native thread
+    let result = (|| -> Result<(jni::JavaVM, GlobalRef, GlobalRef), Box<dyn Error>> {
+        let jvm = env.get_java_vm()?;
+        let bindings = env.new_global_ref(&class)?;
+        let frame_class = env.find_class("bisocm/swiftbot/lib/CameraFrame")?;
+        let frame_class = env.new_global_ref(frame_class)?;
+        Ok((jvm, bindings, frame_class))
+    })();
+    let (jvm, bindings, frame_class) = match result {
+        Ok(refs) => refs,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            return -1;
+        }
+    };
+
+    let (tx, rx) = mpsc::sync_channel::<Arc<Frame>>(1);
+    thread::spawn(move || deliver_frames(jvm, bindings, frame_class, rx));
+
+    //Dropping the sender on unsubscribe ends the delivery thread
+    let tx = Mutex::new(tx);
+    FRAME_CAPTURE.subscribe(move |frame| {
+        let _ = tx.lock().unwrap().try_send(Arc::clone(frame));
+    }) as jint
+}
+
+/// Stops passing captured frames to Java.
+///
+/// # Arguments
+///
+/// * `subscription` - The ID returned by `subscribeFrames`.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// private static native void unsubscribeFrames(int subscription);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_unsubscribeFrames(
+    _env: JNIEnv,
+    _class: JClass,
+    subscription: jint,
+) {
+    FRAME_CAPTURE.unsubscribe(subscription as u32);
+}
+
+/// Serves the camera over HTTP as an MJPEG stream at `/stream.mjpg` and the latest frame at
+/// `/snapshot.jpg`, and starts the continuous capture. Any running stream is stopped first.
+/// Only available when the library is built with the `http-stream` feature.
+///
+/// # Arguments
+///
+/// * `port` - The TCP port to listen on, on all interfaces, or 0 to pick a free port.
+///
+/// # Returns
+///
+/// The port the server is listening on.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the port is invalid, an `Exception` if the
+/// server cannot be started or the camera cannot be opened, or an `UnsupportedOperationException` if the library was built
+/// without the `http-stream` feature.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int startCameraStream(int port);
+/// ```
+#[no_mangle]
+#[cfg(feature = "http-stream")]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startCameraStream(
+    mut env: JNIEnv,
+    _class: JClass,
+    port: jint,
+) -> jint {
+    let Ok(port) = u16::try_from(port) else {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Invalid port: {}.", port));
+        return -1;
+    };
+
+    let mut server = STREAM_SERVER.lock().unwrap();
+    if let Some(running) = server.take() {
+        running.stop();
+    }
+    match StreamServer::start(("0.0.0.0", port), FRAME_CAPTURE.clone()) {
+        Ok(started) => {
+            if let Err(e) = start_capture() {
+                started.stop();
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+                return -1;
+            }
+            let port = started.address().port();
+            *server = Some(started);
+            port as jint
+        }
+        Err(e) => {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+#[cfg(not(feature = "http-stream"))]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startCameraStream(
+    mut env: JNIEnv,
+    _class: JClass,
+    _port: jint,
+) -> jint {
+    let _ = env.throw_new(
+        "java/lang/UnsupportedOperationException",
+        "The camera stream is not available. Build the Rust library with the http-stream feature.",
+    );
+    -1
+}
+
+/// Stops the HTTP camera stream, if running. The continuous capture keeps running.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopCameraStream();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopCameraStream(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    #[cfg(feature = "http-stream")]
+    if let Some(server) = STREAM_SERVER.lock().unwrap().take() {
+        server.stop();
+    }
+}
+
+/// Gets the port the HTTP camera stream is served on.
+///
+/// # Returns
+///
+/// The port, or -1 if the stream is not running.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraStreamPort();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraStreamPort(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    #[cfg(feature = "http-stream")]
+    if let Some(server) = STREAM_SERVER.lock().unwrap().as_ref() {
+        return server.address().port() as jint;
+    }
+    -1
+}
+
+/// Starts recording the camera to an MJPEG AVI file, and starts the continuous capture.
+/// Any recording in progress is stopped first. Each frame keeps its capture time in a JPEG
+/// comment. The camera must capture MJPG.
+///
+/// # Arguments
+///
+/// * `path` - The file to record to. It is replaced if it exists.
+/// * `max_seconds` - Stop after this many seconds, or 0 for no limit.
+/// * `max_bytes` - Stop before the file grows past this size, or 0 for no limit. Files are
+///   never larger than 1 GiB.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if a limit is negative, or an `Exception` if
+/// the file cannot be created or the camera cannot be opened.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void startRecording(String path, int maxSeconds, long maxBytes);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startRecording(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    max_seconds: jint,
+    max_bytes: jlong,
+) {
+    if max_seconds < 0 || max_bytes < 0 {
+        let _ = env.throw_new(
+            "java/lang/IllegalArgumentException",
+            format!("Invalid recording limits: {} s, {} bytes.", max_seconds, max_bytes),
+        );
+        return;
+    }
+    let limits = RecordingLimits {
+        max_duration: (max_seconds > 0).then(|| Duration::from_secs(max_seconds as u64)),
+        max_bytes: (max_bytes > 0).then_some(max_bytes as u64),
+    };
+
+    let mut recorder = RECORDER.lock().unwrap();
+    if let Some(recording) = recorder.take() {
+        let _ = recording.stop();
+    }
+    //The capture must be running first, or the recorder finds it stopped and ends at once
+    let result = get_string(&mut env, &path)
+        .and_then(|path| start_capture().map(|_| path))
+        .and_then(|path| VideoRecorder::start(path, FRAME_CAPTURE.clone(), limits));
+    match result {
+        Ok(recording) => *recorder = Some(recording),
+        Err(e) => {
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            }
+        }
+    }
+}
+
+/// Converts a recording summary to a `bisocm.swiftbot.lib.RecordingSummary`.
+fn recording_summary_to_java<'local>(env: &mut JNIEnv<'local>, summary: &RecordingSummary) -> Result<JObject<'local>, Box<dyn Error>> {
+    let path = env.new_string(summary.path.to_string_lossy())?;
+    let obj = env.new_object(
+        "bisocm/swiftbot/lib/RecordingSummary",
+        "(Ljava/lang/String;JJJ)V",
+        &[
+            (&path).into(),
+            (summary.frames as jlong).into(),
+            (summary.bytes as jlong).into(),
+            (summary.duration.as_millis() as jlong).into(),
+        ],
+    )?;
+    Ok(obj)
+}
+
+/// Stops recording and finishes the file. The continuous capture keeps running.
+///
+/// # Returns
+///
+/// A `bisocm.swiftbot.lib.RecordingSummary` describing the recording, or `null` if nothing
+/// was being recorded. A recording that stopped at a limit is still returned.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the file could not be written, or the camera does not
+/// capture MJPG.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native RecordingSummary stopRecording();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopRecording(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobject {
+    let Some(recording) = RECORDER.lock().unwrap().take() else {
+        return std::ptr::null_mut();
+    };
+    let result = recording.stop().and_then(|summary| recording_summary_to_java(&mut env, &summary));
+    match result {
+        Ok(obj) => obj.into_raw(),
+        Err(e) => {
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            }
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Checks whether the camera is being recorded.
+///
+/// # Returns
+///
+/// `true` until the recording is stopped or reaches a limit, or the capture stops.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isRecording();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isRecording(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    RECORDER.lock().unwrap().as_ref().is_some_and(|recording| !recording.is_finished()) as jboolean
+}
+
+/// Gathers the robot state saved next to each camera snapshot. Parts whose lock is held,
+/// e.g. the motors during a timed turn, are left out rather than waited for.
+fn robot_state() -> RobotState {
+    let statuses = match STATUS_MANAGER.try_lock() {
+        Ok(manager) => SystemStatus::ALL
+            .into_iter()
+            .filter(|status| manager.is_status_active(*status))
+            .map(SystemStatus::name)
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    RobotState {
+        motor_speeds: MOTORS.try_lock().ok().map(|motors| motors.speeds()),
+        distance: SENSORS.try_lock().ok().and_then(|sensors| sensors.last_reading),
+        orientation: IMU
+            .try_lock()
+            .ok()
+            .and_then(|imu| imu.as_ref()?.last_reading())
+            .map(|reading| [reading.heading, reading.pitch, reading.roll]),
+        statuses,
+    }
+}
+
+/// Sets the directory camera snapshots are saved to, creating it if needed.
+///
+/// # Arguments
+///
+/// * `directory` - The directory path.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the directory cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setSnapshotDirectory(String directory);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setSnapshotDirectory(
+    mut env: JNIEnv,
+    _class: JClass,
+    directory: JString,
+) {
+    let result = get_string(&mut env, &directory).and_then(|directory| CAMERA_SNAPSHOTS.set_directory(directory));
+    if let Err(e) = result {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Saves the next camera frame captured to the snapshot directory, with a JSON sidecar of
+/// the robot state, and starts the continuous capture if needed.
+///
+/// # Returns
+///
+/// The path of the JPEG. The sidecar has the same name, ending in `.json`.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if no directory is set, the camera cannot be opened, no MJPG
+/// frame arrives in time or the files cannot be written.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native String takeSnapshot();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_takeSnapshot(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let result = start_capture()
+        .and_then(|_| CAMERA_SNAPSHOTS.take(SnapshotTrigger::Manual))
+        .and_then(|path| Ok(env.new_string(path.to_string_lossy())?));
+    match result {
+        Ok(path) => path.into_raw(),
+        Err(e) => {
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            }
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Starts a time-lapse, saving a snapshot every interval, and starts the continuous capture.
+/// A running time-lapse is replaced.
+///
+/// # Arguments
+///
+/// * `interval_seconds` - The time between snapshots in seconds.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the interval is not positive, an
+/// `IllegalStateException` if no snapshot directory is set, or an `Exception` if the camera
+/// cannot be opened.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void startTimeLapse(double intervalSeconds);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startTimeLapse(
+    mut env: JNIEnv,
+    _class: JClass,
+    interval_seconds: jdouble,
+) {
+    let interval = match Duration::try_from_secs_f64(interval_seconds) {
+        Ok(interval) if !interval.is_zero() => interval,
+        _ => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Invalid interval: {} s.", interval_seconds));
+            return;
+        }
+    };
+    if let Err(e) = CAMERA_SNAPSHOTS.start_time_lapse(interval) {
+        let _ = env.throw_new("java/lang/IllegalStateException", format!("{}", e));
+        return;
+    }
+    if let Err(e) = start_capture() {
+        let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+    }
+}
+
+/// Stops the time-lapse, if running. The continuous capture keeps running.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopTimeLapse();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopTimeLapse(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    CAMERA_SNAPSHOTS.stop_time_lapse();
+}
+
+/// Checks whether a time-lapse is running.
+///
+/// # Returns
+///
+/// `true` until the time-lapse is stopped.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isTimeLapseRunning();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isTimeLapseRunning(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    CAMERA_SNAPSHOTS.is_time_lapse_running() as jboolean
+}
+
+/// Selects the buttons that save a snapshot when pressed, and starts the continuous capture
+/// if any are selected. Button monitoring must be running.
+///
+/// # Arguments
+///
+/// * `mask` - A bit for each button (1 = A, 2 = B, 4 = X, 8 = Y), or 0 for none.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the mask has other bits set, or an
+/// `Exception` if the camera cannot be opened.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setSnapshotButtons(int mask);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setSnapshotButtons(
+    mut env: JNIEnv,
+    _class: JClass,
+    mask: jint,
+) {
+    if !(0..16).contains(&mask) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Invalid button mask: {}. Must be between 0 and 15.", mask));
+        return;
+    }
+    CAMERA_SNAPSHOTS.set_button_triggers(mask as u8);
+    if mask != 0 {
+        if let Err(e) = start_capture() {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+        }
+    }
+}
+
+/// Saves a snapshot whenever an ultrasonic reading comes within a distance after being
+/// further away, and starts the continuous capture. Only readings the application makes
+/// are checked; the sensor is not polled.
+///
+/// # Arguments
+///
+/// * `zone_cm` - The distance in centimeters, or 0 to turn this off.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the distance is negative or not a number, or
+/// an `Exception` if the camera cannot be opened.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setSnapshotObstacleZone(double zoneCm);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setSnapshotObstacleZone(
+    mut env: JNIEnv,
+    _class: JClass,
+    zone_cm: jdouble,
+) {
+    let zone = (zone_cm != 0.0).then_some(zone_cm);
+    if let Err(e) = CAMERA_SNAPSHOTS.set_obstacle_zone(zone) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+        return;
+    }
+    if zone.is_some() {
+        if let Err(e) = start_capture() {
+            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+        }
+    }
+}
+
+/// Gets why the last time-lapse or triggered snapshot failed, and clears it.
+///
+/// # Returns
+///
+/// The error message, or `null` if none has failed since the last call.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native String getSnapshotError();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getSnapshotError(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    match CAMERA_SNAPSHOTS.last_error().map(|error| env.new_string(error)) {
+        Some(Ok(error)) => error.into_raw(),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Updates a button's LED for its feedback mode when the button is pressed or released.
+fn show_button_feedback(button: u8, pressed: bool) {
+    let _status = match claim_buttons() {
+        Some(status) => status,
+        None => return,
+    };
+    let Some(action) = BUTTONLEDS.lock().unwrap().feedback(button).action(pressed) else { return };
+    let mut effects = BUTTON_LED_EFFECTS.lock().unwrap();
+    let _ = match action {
+        FeedbackAction::Hold(level) => effects.hold(&BUTTONLEDS, button, level),
+        FeedbackAction::Restore => effects.release(&BUTTONLEDS, button),
+        FeedbackAction::Play(effect) => effects.start(&BUTTONLEDS, button, effect),
+    };
+}
+
 #[no_mangle]
 pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startButtonMonitoring(
     mut env: JNIEnv,
@@ -534,9 +4777,12 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startButtonMonito
                 let tx = tx_clone.lock().unwrap();
 
                 if is_pressed && !button_states[i] {
+                    show_button_feedback(i as u8, true);
+                    CAMERA_SNAPSHOTS.button_pressed(i as u8);
                     tx.send((i as u8, true)).unwrap();
                     button_states[i] = true;
                 } else if !is_pressed && button_states[i] {
+                    show_button_feedback(i as u8, false);
                     tx.send((i as u8, false)).unwrap();
                     button_states[i] = false;
                 }
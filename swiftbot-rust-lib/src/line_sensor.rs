@@ -0,0 +1,253 @@
+use crate::i2c::I2cBus;
+use crate::sensor_registry::RESERVED_PINS;
+use rppal::gpio::{Gpio, InputPin};
+use rppal::i2c::I2c;
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ADS7830_MAX_CHANNELS: u8 = 8;
+const ADS7830_SINGLE_ENDED: u8 = 0x84; //Single-ended input, internal reference off, converter on
+const LINE_DETECT_THRESHOLD: f64 = 0.5; //Normalised value above which a sensor sees the line
+const MIN_CALIBRATION_RANGE: f64 = 0.05; //Smallest black/white contrast accepted, as a fraction of full scale
+
+/// Where the line sensors are read from.
+enum LineSensorInput {
+    Gpio { pins: Vec<InputPin>, active_low: bool }, //Digital sensors, one per pin
+    Adc { bus: Box<dyn I2cBus>, channels: u8 },     //Analogue sensors on an ADS7830 ADC
+}
+
+/// Result of reading the line sensor array.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineReading {
+    pub position: f64,    //Line position from -1.0 (leftmost sensor) to 1.0 (rightmost sensor)
+    pub lost: bool,       //`true` if no sensor sees the line; `position` then holds the last known position
+    pub values: Vec<f64>, //Normalised reading per sensor, from 0.0 (white) to 1.0 (black)
+}
+
+/// Array of reflective IR line sensors, ordered from left to right.
+pub struct LineSensorArray {
+    input: LineSensorInput,
+    white: Vec<f64>, //Raw value per sensor over the background
+    black: Vec<f64>, //Raw value per sensor over the line
+    last_position: f64,
+}
+
+impl LineSensorArray {
+    /// Creates an array of digital line sensors on spare GPIO pins.
+    pub fn with_gpio(gpio: &Gpio, pins: &[u8], active_low: bool) -> Result<Self, Box<dyn Error>> {
+        if pins.is_empty() {
+            return Err("At least one line sensor pin is required.".into());
+        }
+        let mut inputs = Vec::with_capacity(pins.len());
+        for &pin in pins {
+            if RESERVED_PINS.contains(&pin) {
+                return Err(format!("GPIO {} is used by the SwiftBot and cannot be used for a line sensor.", pin).into());
+            }
+            inputs.push(gpio.get(pin)?.into_input());
+        }
+
+        Ok(Self::new(LineSensorInput::Gpio { pins: inputs, active_low }, pins.len(), 1.0))
+    }
+
+    /// Opens an array of analogue line sensors on an ADS7830 ADC.
+    pub fn open_adc(bus: u8, address: u16, channels: u8) -> Result<Self, Box<dyn Error>> {
+        let mut i2c = I2c::with_bus(bus)?;
+        i2c.set_slave_address(address)?;
+        Self::with_adc(Box::new(i2c), channels)
+    }
+
+    /// Creates an array of analogue line sensors on an ADS7830 ADC on the given bus.
+    pub fn with_adc(bus: Box<dyn I2cBus>, channels: u8) -> Result<Self, Box<dyn Error>> {
+        if channels == 0 || channels > ADS7830_MAX_CHANNELS {
+            return Err(format!("Channel count must be between 1 and {}.", ADS7830_MAX_CHANNELS).into());
+        }
+        Ok(Self::new(LineSensorInput::Adc { bus, channels }, channels as usize, 255.0))
+    }
+
+    fn new(input: LineSensorInput, count: usize, full_scale: f64) -> Self {
+        LineSensorArray {
+            input,
+            white: vec![0.0; count],
+            black: vec![full_scale; count],
+            last_position: 0.0,
+        }
+    }
+
+    /// Returns the number of sensors in the array.
+    pub fn sensor_count(&self) -> usize {
+        self.white.len()
+    }
+
+    /// Sets the raw white (background) and black (line) values for each sensor.
+    pub fn set_thresholds(&mut self, white: &[f64], black: &[f64]) -> Result<(), Box<dyn Error>> {
+        if white.len() != self.sensor_count() || black.len() != self.sensor_count() {
+            return Err(format!("Expected {} white and black thresholds.", self.sensor_count()).into());
+        }
+        if white.iter().zip(black).any(|(w, b)| w == b) {
+            return Err("White and black thresholds must differ for every sensor.".into());
+        }
+        self.white = white.to_vec();
+        self.black = black.to_vec();
+        Ok(())
+    }
+
+    /// Samples the sensors for `duration` while the robot is moved across the line, and uses the
+    /// lowest and highest values seen as the white and black thresholds. This assumes the sensors
+    /// read higher over the line; use `set_thresholds` for sensors that read lower.
+    pub fn calibrate(&mut self, duration: Duration) -> Result<(), Box<dyn Error>> {
+        let mut low = self.read_raw()?;
+        let mut high = low.clone();
+
+        let started = Instant::now();
+        while started.elapsed() < duration {
+            thread::sleep(Duration::from_millis(10));
+            for (i, value) in self.read_raw()?.into_iter().enumerate() {
+                low[i] = low[i].min(value);
+                high[i] = high[i].max(value);
+            }
+        }
+
+        let full_scale = match self.input {
+            LineSensorInput::Gpio { .. } => 1.0,
+            LineSensorInput::Adc { .. } => 255.0,
+        };
+        if low.iter().zip(&high).any(|(l, h)| h - l < MIN_CALIBRATION_RANGE * full_scale) {
+            return Err("Not every sensor saw both the line and the background during calibration.".into());
+        }
+
+        self.white = low;
+        self.black = high;
+        Ok(())
+    }
+
+    /// Reads the array and computes the line position.
+    pub fn read(&mut self) -> Result<LineReading, Box<dyn Error>> {
+        let values: Vec<f64> = self.read_raw()?
+            .iter()
+            .enumerate()
+            .map(|(i, raw)| ((raw - self.white[i]) / (self.black[i] - self.white[i])).clamp(0.0, 1.0))
+            .collect();
+
+        let lost = values.iter().all(|&v| v < LINE_DETECT_THRESHOLD);
+        if !lost {
+            //Weighted average of the sensor positions, spread evenly from -1 to 1
+            let count = values.len();
+            let total: f64 = values.iter().sum();
+            let weighted: f64 = values.iter().enumerate()
+                .map(|(i, v)| v * sensor_offset(i, count))
+                .sum();
+            self.last_position = weighted / total;
+        }
+
+        Ok(LineReading {
+            position: self.last_position,
+            lost,
+            values,
+        })
+    }
+
+    fn read_raw(&mut self) -> Result<Vec<f64>, Box<dyn Error>> {
+        match &mut self.input {
+            LineSensorInput::Gpio { pins, active_low } => Ok(pins
+                .iter()
+                .map(|pin| if pin.is_high() != *active_low { 1.0 } else { 0.0 })
+                .collect()),
+            LineSensorInput::Adc { bus, channels } => {
+                let mut values = Vec::with_capacity(*channels as usize);
+                for channel in 0..*channels {
+                    //The ADS7830 numbers its single-ended inputs in an interleaved order
+                    let select = (channel >> 1) | ((channel & 1) << 2);
+                    let mut value = [0u8; 1];
+                    bus.write_read(&[ADS7830_SINGLE_ENDED | (select << 4)], &mut value)?;
+                    values.push(value[0] as f64);
+                }
+                Ok(values)
+            }
+        }
+    }
+}
+
+/// Position of sensor `index` in an array of `count`, from -1.0 (left) to 1.0 (right).
+fn sensor_offset(index: usize, count: usize) -> f64 {
+    if count < 2 {
+        0.0
+    } else {
+        -1.0 + 2.0 * index as f64 / (count - 1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i2c::fake::FakeI2c;
+
+    fn adc(channels: u8) -> (LineSensorArray, FakeI2c) {
+        let bus = FakeI2c::new();
+        let array = LineSensorArray::with_adc(Box::new(bus.clone()), channels).unwrap();
+        (array, bus)
+    }
+
+    //The fake answers each command byte from the register of the same number
+    fn set_channels(bus: &FakeI2c, values: &[u8]) {
+        for (channel, value) in values.iter().enumerate() {
+            let channel = channel as u8;
+            let select = (channel >> 1) | ((channel & 1) << 2);
+            bus.set_registers(ADS7830_SINGLE_ENDED | (select << 4), &[*value]);
+        }
+    }
+
+    #[test]
+    fn adc_channels_are_read_in_interleaved_order() {
+        let (mut array, bus) = adc(4);
+        set_channels(&bus, &[0, 51, 102, 255]);
+
+        let reading = array.read().unwrap();
+        assert_eq!(reading.values, vec![0.0, 0.2, 0.4, 1.0]);
+
+        assert!(LineSensorArray::with_adc(Box::new(FakeI2c::new()), 0).is_err());
+        assert!(LineSensorArray::with_adc(Box::new(FakeI2c::new()), 9).is_err());
+    }
+
+    #[test]
+    fn position_follows_the_line_and_is_kept_when_lost() {
+        let (mut array, bus) = adc(3);
+        array.set_thresholds(&[50.0; 3], &[200.0; 3]).unwrap();
+
+        set_channels(&bus, &[50, 200, 50]);
+        assert_eq!(array.read().unwrap().position, 0.0);
+        set_channels(&bus, &[50, 200, 200]);
+        assert_eq!(array.read().unwrap().position, 0.5);
+        set_channels(&bus, &[20, 50, 250]);
+        let reading = array.read().unwrap();
+        assert_eq!((reading.position, reading.lost), (1.0, false));
+        assert_eq!(reading.values, vec![0.0, 0.0, 1.0]);
+
+        set_channels(&bus, &[50, 60, 50]);
+        let reading = array.read().unwrap();
+        assert!(reading.lost);
+        assert_eq!(reading.position, 1.0);
+
+        assert!(array.set_thresholds(&[50.0; 2], &[200.0; 2]).is_err());
+        assert!(array.set_thresholds(&[50.0; 3], &[50.0, 200.0, 200.0]).is_err());
+    }
+
+    #[test]
+    fn calibration_uses_the_lowest_and_highest_values_seen() {
+        let (mut array, bus) = adc(2);
+        set_channels(&bus, &[40, 60]);
+        assert!(array.calibrate(Duration::ZERO).is_err());
+
+        //Moved across the line part way through
+        let line = bus.clone();
+        let mover = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(30));
+            set_channels(&line, &[160, 220]);
+        });
+        array.calibrate(Duration::from_millis(100)).unwrap();
+        mover.join().unwrap();
+
+        set_channels(&bus, &[100, 140]);
+        assert_eq!(array.read().unwrap().values, vec![0.5, 0.5]);
+    }
+}
@@ -1,7 +1,12 @@
 use crate::config::*;
+use crate::imu::{heading_difference, Imu};
+use crate::power::{PowerBudget, SharedPowerBudget};
 use crate::utils::clamp;
 use rppal::gpio::{Gpio, OutputPin};
 use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub struct Motors {
     pub motor_en: OutputPin,
@@ -9,10 +14,12 @@ pub struct Motors {
     pub motor_left_n: OutputPin,
     pub motor_right_p: OutputPin,
     pub motor_right_n: OutputPin,
+    speeds: [f64; NUM_MOTORS], //Last speed set on each motor, to detect acceleration
+    power: SharedPowerBudget, //LED power budget, tightened while the motors accelerate
 }
 
 impl Motors {
-    pub fn new(gpio: &Gpio) -> Result<Self, Box<dyn Error>> {
+    pub fn new(gpio: &Gpio, power: SharedPowerBudget) -> Result<Self, Box<dyn Error>> {
         let mut motor_en = gpio.get(MOTOR_EN_PIN)?.into_output();
         motor_en.set_low();
 
@@ -37,11 +44,14 @@ impl Motors {
             motor_left_n,
             motor_right_p,
             motor_right_n,
+            speeds: [0.0; NUM_MOTORS],
+            power,
         })
     }
 
     pub fn set_motor_speed(&mut self, motor: u8, speed: f64) -> Result<(), Box<dyn Error>> {
         let speed = clamp(speed, -1.0, 1.0);
+        self.note_speed(motor, speed);
         self.motor_en.set_high();
 
         let (pwm_p, pwm_n) = match motor {
@@ -75,8 +85,14 @@ impl Motors {
         Ok(())
     }
 
+    /// Returns the last speed set on each motor, -1.0 to 1.0.
+    pub fn speeds(&self) -> [f64; NUM_MOTORS] {
+        self.speeds
+    }
+
     pub fn disable_motors(&mut self) -> Result<(), Box<dyn Error>> {
         self.motor_en.set_low();
+        self.speeds = [0.0; NUM_MOTORS];
         for pin in [
             &mut self.motor_left_p,
             &mut self.motor_left_n,
@@ -109,6 +125,77 @@ impl Motors {
         self.set_motor_speeds(speed, -speed)
     }
 
+    /// Turns on the spot by `degrees` (positive turns right) and stops.
+    ///
+    /// With an IMU the turn ends when the measured heading has changed by `degrees`; without one,
+    /// the duration is estimated from `TURN_RATE_DEG_PER_S`. Setting `cancelled` ends the turn
+    /// early, as the caller holds the motors for the whole turn.
+    pub fn turn_degrees(&mut self, degrees: f64, speed: f64, imu: Option<&mut Imu>, cancelled: &AtomicBool) -> Result<(), Box<dyn Error>> {
+        let speed = clamp(speed.abs(), 0.0, 1.0);
+        if degrees == 0.0 || speed == 0.0 {
+            return Ok(());
+        }
+        let target = degrees.abs();
+        let estimated = Duration::from_secs_f64(target / (TURN_RATE_DEG_PER_S * speed));
+
+        let result = match imu {
+            Some(imu) => {
+                let mut last_heading = imu.update()?.heading;
+                self.start_turn(degrees, speed)?;
+
+                let started = Instant::now();
+                let timeout = estimated * 2 + Duration::from_secs(1);
+                let mut turned: f64 = 0.0;
+                loop {
+                    thread::sleep(Duration::from_millis(5));
+                    let heading = match imu.update() {
+                        Ok(reading) => reading.heading,
+                        Err(e) => break Err(e),
+                    };
+                    turned += heading_difference(heading, last_heading);
+                    last_heading = heading;
+
+                    if turned.abs() >= target || cancelled.load(Ordering::SeqCst) {
+                        break Ok(());
+                    }
+                    if started.elapsed() > timeout {
+                        break Err("Timeout waiting for the turn to complete".into());
+                    }
+                }
+            }
+            None => {
+                self.start_turn(degrees, speed)?;
+                let started = Instant::now();
+                while started.elapsed() < estimated && !cancelled.load(Ordering::SeqCst) {
+                    thread::sleep(estimated.saturating_sub(started.elapsed()).min(Duration::from_millis(5)));
+                }
+                Ok(())
+            }
+        };
+
+        //Always stop, even if the IMU failed mid-turn
+        self.stop()?;
+        result
+    }
+
+    /// Records a motor's new speed, applying the LED acceleration power limit if it has sped up
+    /// or reversed.
+    fn note_speed(&mut self, motor: u8, speed: f64) {
+        let Some(last) = self.speeds.get_mut(motor as usize) else { return };
+        if speed.abs() > last.abs() || speed * *last < 0.0 {
+            PowerBudget::note_acceleration(&self.power);
+        }
+        *last = speed;
+    }
+
+    fn start_turn(&mut self, degrees: f64, speed: f64) -> Result<(), Box<dyn Error>> {
+        if degrees > 0.0 {
+            self.turn_right(speed)
+        } else {
+            self.turn_left(speed)
+        }
+    }
+
     pub fn set_motor_speeds(&mut self, left_speed: f64, right_speed: f64) -> Result<(), Box<dyn Error>> {
         self.set_motor_speed(MOTOR_LEFT, left_speed)?;
         self.set_motor_speed(MOTOR_RIGHT, right_speed)
@@ -116,6 +203,7 @@ impl Motors {
 
     pub fn set_motor_direction(&mut self, motor: u8, direction: bool, speed: f64) -> Result<(), Box<dyn Error>> {
         let clamped_speed = clamp(speed.abs(), 0.0, 1.0);
+        self.note_speed(motor, if direction { clamped_speed } else { -clamped_speed });
         self.motor_en.set_high();
 
         let (pwm_p, pwm_n) = match motor {
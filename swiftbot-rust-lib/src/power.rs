@@ -0,0 +1,239 @@
+use crate::config::*;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// LEDs sharing the power budget.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LedGroup {
+    Underlights,
+    Buttons,
+}
+
+/// Called when the current limit changes, so the LEDs can be written again with the new scale.
+pub type LimitChanged = Arc<dyn Fn() + Send + Sync>;
+
+/// Power budget shared by the LEDs and the motors.
+pub type SharedPowerBudget = Arc<Mutex<PowerBudget>>;
+
+/// Estimates the current drawn by the LEDs from their PWM values and scales their brightness
+/// down when it exceeds a limit. A lower limit applies while the motors are accelerating,
+/// since that is when the battery voltage dips the most. Disabled until a limit is set.
+pub struct PowerBudget {
+    limit_ma: Option<f64>,
+    acceleration_limit_ma: Option<f64>,
+    accelerating_until: Option<Instant>,
+    demand_ma: [f64; 2], //Current each LED group would draw unscaled, indexed by `LedGroup`
+    scales: [f64; 2],    //Scale each LED group was last given, indexed by `LedGroup`
+    refresh_pending: bool,
+    on_limit_changed: LimitChanged,
+}
+
+impl PowerBudget {
+    /// Creates a disabled budget, shared between its users.
+    pub fn shared(on_limit_changed: LimitChanged) -> SharedPowerBudget {
+        Arc::new(Mutex::new(PowerBudget {
+            limit_ma: None,
+            acceleration_limit_ma: None,
+            accelerating_until: None,
+            demand_ma: [0.0; 2],
+            scales: [1.0; 2],
+            refresh_pending: false,
+            on_limit_changed,
+        }))
+    }
+
+    /// Sets the LED current limit in mA, or `None` to remove it.
+    pub fn set_limit(&mut self, limit_ma: Option<f64>) -> Result<(), Box<dyn Error>> {
+        Self::check_limit(limit_ma)?;
+        self.limit_ma = limit_ma;
+        Ok(())
+    }
+
+    /// Sets the LED current limit in mA applied while the motors are accelerating, or `None`
+    /// to remove it.
+    pub fn set_acceleration_limit(&mut self, limit_ma: Option<f64>) -> Result<(), Box<dyn Error>> {
+        Self::check_limit(limit_ma)?;
+        self.acceleration_limit_ma = limit_ma;
+        Ok(())
+    }
+
+    fn check_limit(limit_ma: Option<f64>) -> Result<(), Box<dyn Error>> {
+        match limit_ma {
+            Some(limit) if !(limit > 0.0 && limit.is_finite()) => {
+                Err(format!("Invalid current limit: {} mA. Must be greater than zero.", limit).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the callback to run after changing the limits, so the LEDs pick them up.
+    pub fn on_limit_changed(&self) -> LimitChanged {
+        Arc::clone(&self.on_limit_changed)
+    }
+
+    /// Marks a background refresh, started by `scale`, as running, so the next change in
+    /// scale starts another. Called by the refresh before it rewrites the LEDs.
+    pub fn refresh_started(&mut self) {
+        self.refresh_pending = false;
+    }
+
+    /// Records the current an LED group would draw at its requested brightness, and returns
+    /// the factor (0.0 to 1.0) its brightness must be scaled by to stay within budget.
+    ///
+    /// If this changes the scale the other group should use, the LEDs are refreshed on a
+    /// background thread, as the caller holds its own LEDs and the refresh needs the others.
+    pub fn scale(&mut self, group: LedGroup, demand_ma: f64) -> f64 {
+        self.demand_ma[group as usize] = demand_ma;
+        let total: f64 = self.demand_ma.iter().sum();
+        let scale = match self.current_limit() {
+            Some(limit) if total > limit => limit / total,
+            _ => 1.0,
+        };
+        self.scales[group as usize] = scale;
+
+        let other = 1 - group as usize;
+        if self.demand_ma[other] > 0.0 && self.scales[other] != scale && !self.refresh_pending {
+            self.refresh_pending = true;
+            let budget = self.on_limit_changed();
+            thread::spawn(move || budget());
+        }
+        scale
+    }
+
+    /// Returns the estimated LED current in mA, after scaling. It can briefly exceed the
+    /// limit while one group waits to be rescaled.
+    pub fn estimated_ma(&self) -> f64 {
+        self.demand_ma.iter().zip(self.scales).map(|(demand, scale)| demand * scale).sum()
+    }
+
+    fn current_limit(&self) -> Option<f64> {
+        let accelerating = self.accelerating_until.is_some_and(|until| Instant::now() < until);
+        match (self.limit_ma, self.acceleration_limit_ma.filter(|_| accelerating)) {
+            (Some(limit), Some(acceleration)) => Some(limit.min(acceleration)),
+            (limit, acceleration) => limit.or(acceleration),
+        }
+    }
+
+    /// Applies the acceleration limit for `MOTOR_ACCELERATION_WINDOW` from now. The LEDs are
+    /// rescaled when the limit starts and again when it ends.
+    pub fn note_acceleration(budget: &SharedPowerBudget) {
+        let mut power = budget.lock().unwrap();
+        if power.acceleration_limit_ma.is_none() {
+            return;
+        }
+
+        let started = power.accelerating_until.is_none();
+        power.accelerating_until = Some(Instant::now() + MOTOR_ACCELERATION_WINDOW);
+        if !started {
+            //Already accelerating, the thread below picks up the later end
+            return;
+        }
+
+        let on_limit_changed = power.on_limit_changed();
+        drop(power);
+        on_limit_changed();
+
+        let budget = Arc::clone(budget);
+        thread::spawn(move || loop {
+            let until = budget.lock().unwrap().accelerating_until;
+            match until {
+                Some(until) if Instant::now() < until => thread::sleep(until - Instant::now()),
+                _ => {
+                    let on_limit_changed = {
+                        let mut power = budget.lock().unwrap();
+                        power.accelerating_until = None;
+                        power.on_limit_changed()
+                    };
+                    on_limit_changed();
+                    return;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn scale_shares_the_limit_between_groups() {
+        let budget = PowerBudget::shared(Arc::new(|| {}));
+        let mut power = budget.lock().unwrap();
+        assert_eq!(power.scale(LedGroup::Underlights, 300.0), 1.0);
+
+        power.set_limit(Some(200.0)).unwrap();
+        assert_eq!(power.scale(LedGroup::Buttons, 100.0), 0.5);
+        assert_eq!(power.scale(LedGroup::Underlights, 300.0), 0.5);
+        assert_eq!(power.estimated_ma(), 200.0);
+
+        assert!(power.set_limit(Some(-1.0)).is_err());
+        assert!(power.set_limit(Some(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn other_group_is_refreshed_when_the_scale_changes() {
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&refreshes);
+        let budget = PowerBudget::shared(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        let wait_for_refreshes = |count: usize| {
+            for _ in 0..100 {
+                if refreshes.load(Ordering::SeqCst) >= count {
+                    break;
+                }
+                thread::sleep(std::time::Duration::from_millis(5));
+            }
+            assert_eq!(refreshes.load(Ordering::SeqCst), count);
+        };
+
+        let mut power = budget.lock().unwrap();
+        power.set_limit(Some(200.0)).unwrap();
+        assert_eq!(power.scale(LedGroup::Buttons, 100.0), 1.0);
+        assert_eq!(power.scale(LedGroup::Underlights, 100.0), 1.0);
+
+        //The underlights get brighter, so the buttons must be dimmed too
+        assert_eq!(power.scale(LedGroup::Underlights, 300.0), 0.5);
+        assert_eq!(power.estimated_ma(), 250.0);
+        drop(power);
+        wait_for_refreshes(1);
+
+        let mut power = budget.lock().unwrap();
+        power.refresh_started();
+        assert_eq!(power.scale(LedGroup::Buttons, 100.0), 0.5);
+        assert_eq!(power.estimated_ma(), 200.0);
+
+        //Rescaling the underlights at the same scale needs no further refresh
+        assert_eq!(power.scale(LedGroup::Underlights, 300.0), 0.5);
+        drop(power);
+        thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn acceleration_limit_applies_for_a_window() {
+        let changes = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&changes);
+        let budget = PowerBudget::shared(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        //Without an acceleration limit there is nothing to apply
+        PowerBudget::note_acceleration(&budget);
+        assert_eq!(changes.load(Ordering::SeqCst), 0);
+
+        budget.lock().unwrap().set_limit(Some(200.0)).unwrap();
+        budget.lock().unwrap().set_acceleration_limit(Some(50.0)).unwrap();
+        PowerBudget::note_acceleration(&budget);
+        assert_eq!(changes.load(Ordering::SeqCst), 1);
+        assert_eq!(budget.lock().unwrap().scale(LedGroup::Underlights, 100.0), 0.5);
+
+        thread::sleep(MOTOR_ACCELERATION_WINDOW * 2);
+        assert_eq!(changes.load(Ordering::SeqCst), 2);
+        assert_eq!(budget.lock().unwrap().scale(LedGroup::Underlights, 100.0), 1.0);
+    }
+}
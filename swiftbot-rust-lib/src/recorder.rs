@@ -0,0 +1,448 @@
+use crate::capture::{Frame, FrameCapture};
+use crate::config::*;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+//How often the recording thread checks whether it has been stopped while waiting for frames
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+//Frame interval written to recordings too short to measure it from
+const DEFAULT_FRAME_US: u32 = 33_333;
+
+//AVI flags: the file has an index, and each frame is a key frame
+const AVIF_HASINDEX: u32 = 0x10;
+const AVIIF_KEYFRAME: u32 = 0x10;
+
+/// Writes MJPEG frames to an AVI file. Each frame keeps its capture time in a JPEG comment,
+/// and the frame rate in the header is measured from the capture times when finished.
+pub struct AviWriter<W: Write + Seek> {
+    out: W,
+    header: Vec<u8>,
+    patches: AviPatches,
+    index: Vec<(u32, u32)>, //Offset from the movi list and length of each frame
+    movi_bytes: u32,        //Bytes written to the movi list, after its "movi" code
+    max_frame: u32,
+    first_timestamp_us: Option<u64>,
+    last_timestamp_us: u64,
+}
+
+/// Offsets in the header of the fields only known once the recording ends.
+struct AviPatches {
+    riff_size: usize,
+    us_per_frame: usize,
+    total_frames: usize,
+    suggested_buffer: usize,
+    scale: usize,
+    length: usize,
+    stream_buffer: usize,
+    movi_size: usize,
+}
+
+impl<W: Write + Seek> AviWriter<W> {
+    /// Writes the AVI header for frames of the given size. `out` must be at its start.
+    pub fn new(mut out: W, resolution: (u32, u32)) -> io::Result<Self> {
+        let (width, height) = resolution;
+        let mut header = Vec::new();
+        let u32s = |header: &mut Vec<u8>, values: &[u32]| {
+            for value in values {
+                header.extend_from_slice(&value.to_le_bytes());
+            }
+        };
+
+        header.extend_from_slice(b"RIFF");
+        let riff_size = header.len();
+        header.extend_from_slice(b"\0\0\0\0AVI LIST");
+        u32s(&mut header, &[192]);
+        header.extend_from_slice(b"hdrl");
+
+        //Main header
+        header.extend_from_slice(b"avih");
+        u32s(&mut header, &[56]);
+        let us_per_frame = header.len();
+        u32s(&mut header, &[DEFAULT_FRAME_US, 0, 0, AVIF_HASINDEX]);
+        let total_frames = header.len();
+        u32s(&mut header, &[0, 0, 1]);
+        let suggested_buffer = header.len();
+        u32s(&mut header, &[0, width, height, 0, 0, 0, 0]);
+
+        //Stream header and format
+        header.extend_from_slice(b"LIST");
+        u32s(&mut header, &[116]);
+        header.extend_from_slice(b"strlstrh");
+        u32s(&mut header, &[56]);
+        header.extend_from_slice(b"vidsMJPG");
+        u32s(&mut header, &[0, 0, 0]);
+        let scale = header.len();
+        u32s(&mut header, &[DEFAULT_FRAME_US, 1_000_000, 0]);
+        let length = header.len();
+        u32s(&mut header, &[0]);
+        let stream_buffer = header.len();
+        u32s(&mut header, &[0, u32::MAX, 0]);
+        for value in [0, 0, width.min(u16::MAX as u32) as u16, height.min(u16::MAX as u32) as u16] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        header.extend_from_slice(b"strf");
+        u32s(&mut header, &[40, 40, width, height]);
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&24u16.to_le_bytes());
+        header.extend_from_slice(b"MJPG");
+        u32s(&mut header, &[width * height * 3, 0, 0, 0, 0]);
+
+        header.extend_from_slice(b"LIST");
+        let movi_size = header.len();
+        header.extend_from_slice(b"\0\0\0\0movi");
+
+        out.write_all(&header)?;
+        Ok(AviWriter {
+            out,
+            header,
+            patches: AviPatches { riff_size, us_per_frame, total_frames, suggested_buffer, scale, length, stream_buffer, movi_size },
+            index: Vec::new(),
+            movi_bytes: 0,
+            max_frame: 0,
+            first_timestamp_us: None,
+            last_timestamp_us: 0,
+        })
+    }
+
+    /// Returns the size the file will have if it is finished now.
+    pub fn file_size(&self) -> u64 {
+        self.header.len() as u64 + self.movi_bytes as u64 + 8 + 16 * self.index.len() as u64
+    }
+
+    /// Returns the number of frames written.
+    pub fn frame_count(&self) -> u64 {
+        self.index.len() as u64
+    }
+
+    /// Returns the time between the first and last frames written.
+    pub fn duration(&self) -> Duration {
+        Duration::from_micros(self.first_timestamp_us.map_or(0, |first| self.last_timestamp_us.saturating_sub(first)))
+    }
+
+    /// Returns how much a frame adds to the file, so limits can be checked before writing it.
+    pub fn frame_size(&self, frame: &Frame) -> u64 {
+        let length = frame.data.len() as u64 + comment(frame).map_or(0, |comment| comment.len() as u64);
+        8 + length + length % 2 + 16
+    }
+
+    /// Appends a JPEG frame.
+    pub fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        let comment = comment(frame);
+        let length = frame.data.len() + comment.as_ref().map_or(0, |comment| comment.len());
+        let length = u32::try_from(length).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Frame too large"))?;
+
+        self.out.write_all(b"00dc")?;
+        self.out.write_all(&length.to_le_bytes())?;
+        match comment {
+            //The comment goes straight after the start of image marker
+            Some(comment) => {
+                self.out.write_all(&frame.data[..2])?;
+                self.out.write_all(&comment)?;
+                self.out.write_all(&frame.data[2..])?;
+            }
+            None => self.out.write_all(&frame.data)?,
+        }
+        if length % 2 != 0 {
+            self.out.write_all(&[0])?;
+        }
+
+        self.index.push((self.movi_bytes + 4, length));
+        self.movi_bytes += 8 + length + length % 2;
+        self.max_frame = self.max_frame.max(length);
+        self.first_timestamp_us.get_or_insert(frame.timestamp_us);
+        self.last_timestamp_us = frame.timestamp_us;
+        Ok(())
+    }
+
+    /// Writes the index and fills in the header. Returns the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(b"idx1")?;
+        self.out.write_all(&(16 * self.index.len() as u32).to_le_bytes())?;
+        for (offset, length) in &self.index {
+            self.out.write_all(b"00dc")?;
+            for value in [AVIIF_KEYFRAME, *offset, *length] {
+                self.out.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        let frames = self.index.len() as u32;
+        let us_per_frame = match frames {
+            0 | 1 => DEFAULT_FRAME_US,
+            _ => (self.duration().as_micros() as u64 / (frames as u64 - 1)).clamp(1, u32::MAX as u64) as u32,
+        };
+        let riff_size = self.file_size() as u32 - 8;
+        let patches = &self.patches;
+        for (offset, value) in [
+            (patches.riff_size, riff_size),
+            (patches.us_per_frame, us_per_frame),
+            (patches.total_frames, frames),
+            (patches.suggested_buffer, self.max_frame),
+            (patches.scale, us_per_frame),
+            (patches.length, frames),
+            (patches.stream_buffer, self.max_frame),
+            (patches.movi_size, 4 + self.movi_bytes),
+        ] {
+            self.header[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&self.header)?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// A JPEG comment segment holding the frame's sequence number and capture time, or `None`
+/// if the frame does not start like a JPEG.
+fn comment(frame: &Frame) -> Option<Vec<u8>> {
+    if !frame.data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let text = format!("sequence={} timestamp_us={}", frame.sequence, frame.timestamp_us);
+    let mut segment = vec![0xFF, 0xFE];
+    segment.extend_from_slice(&(text.len() as u16 + 2).to_be_bytes());
+    segment.extend_from_slice(text.as_bytes());
+    Some(segment)
+}
+
+/// When a recording stops by itself.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RecordingLimits {
+    pub max_duration: Option<Duration>,
+    pub max_bytes: Option<u64>,
+}
+
+/// What was recorded.
+#[derive(Clone, Debug)]
+pub struct RecordingSummary {
+    pub path: PathBuf,
+    pub frames: u64,
+    pub bytes: u64,
+    pub duration: Duration, //Between the first and last frames
+}
+
+/// Records frames from the continuous capture to an MJPEG AVI file on a thread of its own,
+/// until stopped, a limit is reached or the capture stops. The capture must be started
+/// separately and capture MJPG.
+pub struct VideoRecorder {
+    stopped: Arc<AtomicBool>,
+    thread: JoinHandle<Result<RecordingSummary, String>>,
+}
+
+impl VideoRecorder {
+    /// Creates the file and starts recording into it. Frames captured before this are not
+    /// recorded.
+    pub fn start(path: impl AsRef<Path>, capture: FrameCapture, limits: RecordingLimits) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+        let max_bytes = limits.max_bytes.unwrap_or(RECORDING_MAX_FILE_BYTES).min(RECORDING_MAX_FILE_BYTES);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stopped);
+        let after = capture.latest().map(|frame| frame.sequence);
+        let thread = thread::spawn(move || {
+            record(BufWriter::new(file), &capture, after, &flag, limits.max_duration, max_bytes)
+                .map(|(frames, bytes, duration)| RecordingSummary { path: path.clone(), frames, bytes, duration })
+                .map_err(|e| format!("Failed to record to {}: {}", path.display(), e))
+        });
+
+        Ok(VideoRecorder { stopped, thread })
+    }
+
+    /// Returns `true` once the recording has stopped by itself.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stops recording, finishes the file and returns what was recorded.
+    pub fn stop(self) -> Result<RecordingSummary, Box<dyn Error>> {
+        self.stopped.store(true, Ordering::SeqCst);
+        match self.thread.join() {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => Err("The recording thread panicked".into()),
+        }
+    }
+}
+
+fn record(
+    out: BufWriter<File>,
+    capture: &FrameCapture,
+    after: Option<u64>, //Latest frame when the recording started, which is not recorded
+    stopped: &AtomicBool,
+    max_duration: Option<Duration>,
+    max_bytes: u64,
+) -> Result<(u64, u64, Duration), Box<dyn Error>> {
+    let mut out = Some(out);
+    let mut writer: Option<AviWriter<BufWriter<File>>> = None;
+    let mut last = after;
+
+    'recording: while !stopped.load(Ordering::SeqCst) {
+        let Some(latest) = capture.wait_for_frame(last, STOP_POLL_INTERVAL) else {
+            if capture.is_running() {
+                continue;
+            }
+            break;
+        };
+
+        //Catch up on frames captured while the last ones were written
+        let frames = match last {
+            None => vec![latest],
+            Some(last) => capture.frames().into_iter().filter(|frame| frame.sequence > last).collect(),
+        };
+        for frame in frames {
+            last = Some(frame.sequence);
+            if !matches!(&frame.format, b"MJPG" | b"JPEG") {
+                return Err(format!("Only MJPG frames can be recorded, not {}.", String::from_utf8_lossy(&frame.format)).into());
+            }
+
+            if writer.is_none() {
+                writer = Some(AviWriter::new(out.take().unwrap(), frame.resolution)?);
+            }
+            let avi = writer.as_mut().unwrap();
+            let first = avi.first_timestamp_us.unwrap_or(frame.timestamp_us);
+            let too_long = max_duration.is_some_and(|max| frame.timestamp_us.saturating_sub(first) > max.as_micros() as u64);
+            if too_long || avi.file_size() + avi.frame_size(&frame) > max_bytes {
+                break 'recording;
+            }
+            avi.write_frame(&frame)?;
+        }
+    }
+
+    //Stopped before any frame arrived, so the size is unknown
+    let writer = match writer {
+        Some(writer) => writer,
+        None => AviWriter::new(out.take().unwrap(), (0, 0))?,
+    };
+    let summary = (writer.frame_count(), writer.file_size(), writer.duration());
+    writer.finish()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::fake::FakeCamera;
+    use std::io::Cursor;
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn frame(sequence: u64, timestamp_us: u64, data: &[u8]) -> Frame {
+        Frame { sequence, timestamp_us, resolution: (4, 2), format: *b"MJPG", data: data.to_vec() }
+    }
+
+    #[test]
+    fn avi_has_consistent_sizes_and_index() {
+        let mut avi = AviWriter::new(Cursor::new(Vec::new()), (4, 2)).unwrap();
+        let frames = [frame(5, 1_000_000, b"\xFF\xD8abc\xFF\xD9"), frame(6, 1_100_000, b"\xFF\xD8de\xFF\xD9"), frame(7, 1_200_000, b"raw")];
+        let mut expected = avi.file_size();
+        for frame in &frames {
+            expected += avi.frame_size(frame);
+            avi.write_frame(frame).unwrap();
+        }
+        assert_eq!(avi.file_size(), expected);
+        assert_eq!(avi.duration(), Duration::from_millis(200));
+        let data = avi.finish().unwrap().into_inner();
+
+        assert_eq!(data.len() as u64, expected);
+        assert_eq!(&data[..4], b"RIFF");
+        assert_eq!(u32_at(&data, 4) as usize, data.len() - 8);
+        assert_eq!(&data[8..12], b"AVI ");
+        assert_eq!(u32_at(&data, 32), 100_000); //Microseconds per frame
+        assert_eq!(u32_at(&data, 48), 3); //Total frames
+        assert_eq!((u32_at(&data, 64), u32_at(&data, 68)), (4, 2));
+
+        //The hdrl list ends where the movi list starts
+        let movi = 20 + u32_at(&data, 16) as usize;
+        assert_eq!(&data[movi..movi + 4], b"LIST");
+        assert_eq!(&data[movi + 8..movi + 12], b"movi");
+        let idx1 = movi + 8 + u32_at(&data, movi + 4) as usize;
+        assert_eq!(&data[idx1..idx1 + 4], b"idx1");
+        assert_eq!(u32_at(&data, idx1 + 4), 3 * 16);
+
+        //Index offsets point at each frame's chunk from the "movi" code
+        let (offset, length) = (u32_at(&data, idx1 + 16), u32_at(&data, idx1 + 20));
+        let chunk = movi + 8 + offset as usize;
+        assert_eq!(&data[chunk..chunk + 4], b"00dc");
+        let jpeg = &data[chunk + 8..chunk + 8 + length as usize];
+        let text = b"sequence=5 timestamp_us=1000000";
+        assert_eq!(&jpeg[..4], b"\xFF\xD8\xFF\xFE");
+        assert_eq!(u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize, text.len() + 2);
+        assert_eq!(&jpeg[6..6 + text.len()], text);
+        assert_eq!(&jpeg[6 + text.len()..], b"abc\xFF\xD9");
+        let (offset, length) = (u32_at(&data, idx1 + 48), u32_at(&data, idx1 + 52));
+        let chunk = movi + 8 + offset as usize;
+        assert_eq!(&data[chunk + 8..chunk + 8 + length as usize], b"raw");
+    }
+
+    #[test]
+    fn frames_buffered_before_the_start_are_not_recorded() {
+        let capture = FrameCapture::new(4);
+        let image = b"\xFF\xD8image\xFF\xD9".to_vec();
+        capture.start(Arc::new(FakeCamera::new(vec![image], Duration::from_millis(10))));
+        let mut buffered = None;
+        while buffered.is_none_or(|sequence| sequence < 2) {
+            buffered = capture.wait_for_frame(buffered, Duration::from_secs(1)).map(|frame| frame.sequence);
+            assert!(buffered.is_some());
+        }
+
+        let path = std::env::temp_dir().join(format!("swiftbot-recording-buffered-{}.avi", std::process::id()));
+        let recorder = VideoRecorder::start(&path, capture.clone(), RecordingLimits::default()).unwrap();
+        let buffered = buffered.unwrap();
+        let mut last = Some(buffered);
+        for _ in 0..3 {
+            last = capture.wait_for_frame(last, Duration::from_secs(1)).map(|frame| frame.sequence);
+        }
+        thread::sleep(Duration::from_millis(5));
+        let summary = recorder.stop().unwrap();
+        capture.stop();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(summary.frames > 0);
+
+        //The first recorded frame is the one after the latest at the start
+        let movi = 20 + u32_at(&data, 16) as usize;
+        let idx1 = movi + 8 + u32_at(&data, movi + 4) as usize;
+        let (offset, length) = (u32_at(&data, idx1 + 16), u32_at(&data, idx1 + 20));
+        let chunk = movi + 8 + offset as usize;
+        let jpeg = &data[chunk + 8..chunk + 8 + length as usize];
+        let text = format!("sequence={} ", buffered + 1);
+        assert_eq!(&jpeg[6..6 + text.len()], text.as_bytes());
+    }
+
+    #[test]
+    fn recording_stops_at_the_size_limit() {
+        let capture = FrameCapture::new(4);
+        let image = b"\xFF\xD8image\xFF\xD9".to_vec();
+        capture.start(Arc::new(FakeCamera::new(vec![image], Duration::from_millis(2))));
+
+        let path = std::env::temp_dir().join(format!("swiftbot-recording-{}.avi", std::process::id()));
+        let limits = RecordingLimits { max_duration: None, max_bytes: Some(1024) };
+        let recorder = VideoRecorder::start(&path, capture.clone(), limits).unwrap();
+        for _ in 0..100 {
+            if recorder.is_finished() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(recorder.is_finished());
+        capture.stop();
+
+        let summary = recorder.stop().unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(summary.frames > 0);
+        assert_eq!(summary.bytes, data.len() as u64);
+        assert!(summary.bytes <= 1024);
+        assert_eq!(u32_at(&data, 48) as u64, summary.frames);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::capture::Frame;
+use std::error::Error;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Camera stand-in that replays JPEG images in a loop at a steady frame rate, so vision code
+/// can be tested without the camera. The images come from one of:
+///
+/// * a directory, whose `.jpg` and `.jpeg` files are replayed in name order;
+/// * a single `.jpg` or `.jpeg` file, repeated;
+/// * an MJPEG stream or AVI file, such as one written by `VideoRecorder`, split into the
+///   JPEG images it contains.
+///
+/// Frames are reported as MJPG at the size read from each image. Files in a directory are read
+/// as they are replayed, while a stream or AVI file is loaded into memory when opened.
+pub struct ReplayCamera {
+    path: PathBuf,
+    images: Images,
+    interval: Duration,
+    position: Mutex<Position>,
+}
+
+enum Images {
+    Files(Vec<PathBuf>),
+    Stream { data: Vec<u8>, frames: Vec<Range<usize>> },
+}
+
+struct Position {
+    next: usize,
+    due: Option<Instant>, //When the next frame should be returned
+}
+
+impl ReplayCamera {
+    /// Opens `path` for replay at one frame per `interval`.
+    pub fn open(path: impl AsRef<Path>, interval: Duration) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let images = if path.is_dir() {
+            let mut files: Vec<PathBuf> = fs::read_dir(path)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|file| file.is_file() && is_jpeg_file(file))
+                .collect();
+            files.sort();
+            Images::Files(files)
+        } else if is_jpeg_file(path) {
+            Images::Files(vec![path.to_path_buf()])
+        } else {
+            let data = fs::read(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+            let frames = split_jpegs(&data);
+            Images::Stream { data, frames }
+        };
+
+        let count = match &images {
+            Images::Files(files) => files.len(),
+            Images::Stream { frames, .. } => frames.len(),
+        };
+        if count == 0 {
+            return Err(format!("No JPEG images found in {}", path.display()).into());
+        }
+
+        Ok(ReplayCamera {
+            path: path.to_path_buf(),
+            images,
+            interval,
+            position: Mutex::new(Position { next: 0, due: None }),
+        })
+    }
+
+    /// Returns the number of images replayed before the replay starts over.
+    pub fn len(&self) -> usize {
+        match &self.images {
+            Images::Files(files) => files.len(),
+            Images::Stream { frames, .. } => frames.len(),
+        }
+    }
+
+    /// Returns the size of the first image.
+    pub fn resolution(&self) -> Result<(u32, u32), Box<dyn Error>> {
+        let image = self.image(0)?;
+        jpeg_size(&image).ok_or_else(|| format!("Invalid JPEG image in {}", self.path.display()).into())
+    }
+
+    /// Waits until the next frame is due, then returns it. The sequence number and timestamp
+    /// are left at zero.
+    pub fn next_frame(&self) -> Result<Frame, Box<dyn Error>> {
+        let mut position = self.position.lock().unwrap();
+        let now = Instant::now();
+        match position.due {
+            Some(due) if due > now => thread::sleep(due - now),
+            //Fell behind, e.g. nothing was captured for a while, so carry on from now
+            _ => position.due = Some(now),
+        }
+        position.due = position.due.map(|due| due + self.interval);
+
+        let index = position.next;
+        position.next = (index + 1) % self.len();
+        let data = self.image(index)?;
+        let resolution = jpeg_size(&data).ok_or_else(|| format!("Invalid JPEG image {} in {}", index, self.path.display()))?;
+        Ok(Frame { sequence: 0, timestamp_us: 0, resolution, format: *b"MJPG", data })
+    }
+
+    fn image(&self, index: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        match &self.images {
+            Images::Files(files) => {
+                fs::read(&files[index]).map_err(|e| format!("Failed to read {}: {}", files[index].display(), e).into())
+            }
+            Images::Stream { data, frames } => Ok(data[frames[index].clone()].to_vec()),
+        }
+    }
+}
+
+fn is_jpeg_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("jpg") || extension.eq_ignore_ascii_case("jpeg"))
+}
+
+/// Finds the JPEG images in an MJPEG stream or AVI file. Anything between them, such as
+/// multipart headers or AVI chunks, is skipped.
+fn split_jpegs(data: &[u8]) -> Vec<Range<usize>> {
+    let mut frames = Vec::new();
+    let mut i = 0;
+    while i + 1 < data.len() {
+        if data[i] == 0xFF && data[i + 1] == 0xD8 {
+            if let Some(length) = jpeg_length(&data[i..]) {
+                frames.push(i..i + length);
+                i += length;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    frames
+}
+
+/// Length of the JPEG image at the start of `data`, up to and including its EOI marker.
+/// Segments are skipped by their lengths, so EXIF thumbnails do not end the image early.
+fn jpeg_length(data: &[u8]) -> Option<usize> {
+    let mut i = 2;
+    loop {
+        if *data.get(i)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(i + 1)?;
+        match marker {
+            0xFF => i += 1, //Fill byte
+            0xD9 => return Some(i + 2),
+            0x01 | 0xD0..=0xD7 => i += 2, //Markers without a length
+            _ => {
+                let length = u16::from_be_bytes([*data.get(i + 2)?, *data.get(i + 3)?]) as usize;
+                i += 2 + length;
+                if marker == 0xDA {
+                    //Entropy-coded data follows the scan header. An 0xFF byte in it is followed
+                    //by 0x00 (stuffing) or a restart marker, so any other marker ends the scan.
+                    while *data.get(i)? != 0xFF || matches!(data.get(i + 1)?, 0x00 | 0xD0..=0xD7) {
+                        i += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Width and height from the frame header (SOF) of a JPEG image.
+fn jpeg_size(data: &[u8]) -> Option<(u32, u32)> {
+    let mut i = 2;
+    while *data.get(i)? == 0xFF {
+        let marker = *data.get(i + 1)?;
+        let length = u16::from_be_bytes([*data.get(i + 2)?, *data.get(i + 3)?]) as usize;
+        //SOF0 to SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+        if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let height = u16::from_be_bytes([*data.get(i + 5)?, *data.get(i + 6)?]) as u32;
+            let width = u16::from_be_bytes([*data.get(i + 7)?, *data.get(i + 8)?]) as u32;
+            return Some((width, height));
+        }
+        if marker == 0xDA {
+            return None;
+        }
+        i += 2 + length;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::AviWriter;
+    use jpeg_encoder::{ColorType, Encoder};
+    use std::io::BufWriter;
+
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let pixels = vec![128; width as usize * height as usize * 3];
+        let mut data = Vec::new();
+        Encoder::new(&mut data, 90).encode(&pixels, width, height, ColorType::Rgb).unwrap();
+        data
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("swiftbot-replay-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn directories_are_replayed_in_name_order() {
+        let directory = temp_path("directory");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("b.jpg"), jpeg(16, 8)).unwrap();
+        fs::write(directory.join("a.JPEG"), jpeg(32, 16)).unwrap();
+        fs::write(directory.join("notes.txt"), "not an image").unwrap();
+
+        let camera = ReplayCamera::open(&directory, Duration::from_millis(20)).unwrap();
+        assert_eq!(camera.len(), 2);
+        assert_eq!(camera.resolution().unwrap(), (32, 16));
+
+        let start = Instant::now();
+        let sizes: Vec<(u32, u32)> = (0..3).map(|_| camera.next_frame().unwrap().resolution).collect();
+        assert_eq!(sizes, vec![(32, 16), (16, 8), (32, 16)]);
+        //The first frame is returned at once, the others at the frame rate
+        assert!(start.elapsed() >= Duration::from_millis(40));
+
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(ReplayCamera::open(&directory, Duration::from_millis(20)).is_err());
+    }
+
+    #[test]
+    fn mjpeg_streams_are_split_into_images() {
+        let (first, second) = (jpeg(16, 16), jpeg(24, 8));
+        let mut stream = b"--frame\r\nContent-Type: image/jpeg\r\n\r\n".to_vec();
+        stream.extend(&first);
+        stream.extend(b"\r\n--frame\r\n\r\n");
+        stream.extend(&second);
+
+        let path = temp_path("stream.mjpg");
+        fs::write(&path, &stream).unwrap();
+        let camera = ReplayCamera::open(&path, Duration::ZERO).unwrap();
+        assert_eq!(camera.len(), 2);
+
+        let frame = camera.next_frame().unwrap();
+        assert_eq!((frame.data, frame.resolution, frame.format), (first, (16, 16), *b"MJPG"));
+        assert_eq!(camera.next_frame().unwrap().data, second);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn recordings_are_replayed_frame_by_frame() {
+        let images = [jpeg(16, 16), jpeg(24, 8), jpeg(8, 8)];
+        let path = temp_path("recording.avi");
+        let mut avi = AviWriter::new(BufWriter::new(fs::File::create(&path).unwrap()), (16, 16)).unwrap();
+        for (i, image) in images.iter().enumerate() {
+            let frame = Frame { sequence: i as u64, timestamp_us: i as u64 * 1000, resolution: (16, 16), format: *b"MJPG", data: image.clone() };
+            avi.write_frame(&frame).unwrap();
+        }
+        avi.finish().unwrap();
+
+        let camera = ReplayCamera::open(&path, Duration::ZERO).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(camera.len(), 3);
+        for (i, image) in images.iter().enumerate() {
+            //Each image comes back whole, after the COM segment the recorder inserts
+            let frame = camera.next_frame().unwrap();
+            let text = format!("sequence={} timestamp_us={}", i, i * 1000);
+            assert_eq!(&frame.data[..4], b"\xFF\xD8\xFF\xFE");
+            assert_eq!(&frame.data[6..6 + text.len()], text.as_bytes());
+            assert_eq!(&frame.data[6 + text.len()..], &image[2..]);
+            assert_eq!(frame.resolution, jpeg_size(image).unwrap());
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crate::config::*;
+use rppal::gpio::{Gpio, InputPin};
+use rppal::i2c::I2c;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Pins already used by the SwiftBot's built-in hardware.
+pub const RESERVED_PINS: [u8; 17] = [
+    BUTTON_A_PIN, BUTTON_B_PIN, BUTTON_X_PIN, BUTTON_Y_PIN,
+    LED_A_PIN, LED_B_PIN, LED_X_PIN, LED_Y_PIN,
+    MOTOR_EN_PIN, MOTOR_LEFT_P, MOTOR_LEFT_N, MOTOR_RIGHT_P, MOTOR_RIGHT_N,
+    ULTRA_TRIG_PIN, ULTRA_ECHO_PIN,
+    I2C_SDA_PIN, I2C_SCL_PIN,
+];
+
+/// Most bytes read from an I2C device at once, the SMBus block limit.
+pub const I2C_MAX_READ_LENGTH: usize = 32;
+
+/// Checks a BCM GPIO pin number from the Raspberry Pi header.
+pub fn check_pin(pin: i32) -> Result<u8, Box<dyn Error>> {
+    if !(0..=27).contains(&pin) {
+        return Err(format!("Invalid GPIO pin: {}. Must be between 0 and 27.", pin).into());
+    }
+    Ok(pin as u8)
+}
+
+/// Checks a 7-bit I2C address, excluding the addresses reserved by the I2C specification.
+pub fn check_i2c_address(address: i32) -> Result<u16, Box<dyn Error>> {
+    if !(0x03..=0x77).contains(&address) {
+        return Err(format!("Invalid I2C address: {:#04x}. Must be between 0x03 and 0x77.", address).into());
+    }
+    Ok(address as u16)
+}
+
+/// Checks an I2C register number.
+pub fn check_register(register: i32) -> Result<u8, Box<dyn Error>> {
+    u8::try_from(register).map_err(|_| format!("Invalid I2C register: {}. Must be between 0 and 255.", register).into())
+}
+
+/// Checks the number of bytes to read from an I2C device.
+pub fn check_read_length(length: i32) -> Result<usize, Box<dyn Error>> {
+    if !(1..=I2C_MAX_READ_LENGTH as i32).contains(&length) {
+        return Err(format!("Invalid read length: {}. Must be between 1 and {}.", length, I2C_MAX_READ_LENGTH).into());
+    }
+    Ok(length as usize)
+}
+
+/// Internal pull resistor configuration for a digital input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputPull {
+    None,
+    Up,
+    Down,
+}
+
+impl InputPull {
+    /// Maps the integer IDs used across JNI (0 = none, 1 = pull-up, 2 = pull-down).
+    pub fn from_id(id: i32) -> Result<Self, Box<dyn Error>> {
+        match id {
+            0 => Ok(InputPull::None),
+            1 => Ok(InputPull::Up),
+            2 => Ok(InputPull::Down),
+            _ => Err(format!("Invalid pull configuration: {}. Must be between 0 and 2.", id).into()),
+        }
+    }
+}
+
+/// Declares a digital input (line sensor, bump switch, ...) on a spare GPIO pin.
+pub struct DigitalSensorConfig {
+    pub name: &'static str,
+    pub pin: u8,
+    pub pull: InputPull,
+    pub active_low: bool, //Report `true` when the pin reads low
+}
+
+/// Declares a device on an I2C bus.
+pub struct I2cDeviceConfig {
+    pub name: &'static str,
+    pub bus: u8,
+    pub address: u16,
+}
+
+struct DigitalSensor {
+    pin: InputPin,
+    active_low: bool,
+}
+
+/// Registry of additional sensors, addressed by name.
+pub struct SensorRegistry {
+    digital: BTreeMap<String, DigitalSensor>,
+    i2c: BTreeMap<String, I2c>,
+}
+
+impl SensorRegistry {
+    /// Creates the registry and registers the given sensors.
+    pub fn new(
+        gpio: &Gpio,
+        digital: &[DigitalSensorConfig],
+        i2c: &[I2cDeviceConfig],
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut registry = SensorRegistry {
+            digital: BTreeMap::new(),
+            i2c: BTreeMap::new(),
+        };
+        for sensor in digital {
+            registry.register_digital(gpio, sensor.name, sensor.pin, sensor.pull, sensor.active_low)?;
+        }
+        for device in i2c {
+            registry.register_i2c(device.name, device.bus, device.address)?;
+        }
+        Ok(registry)
+    }
+
+    /// Registers a digital input on a spare GPIO pin.
+    pub fn register_digital(
+        &mut self,
+        gpio: &Gpio,
+        name: &str,
+        pin: u8,
+        pull: InputPull,
+        active_low: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_name(name)?;
+        if RESERVED_PINS.contains(&pin) {
+            return Err(format!("GPIO {} is used by the SwiftBot and cannot be registered.", pin).into());
+        }
+
+        let pin = gpio.get(pin)?;
+        let pin = match pull {
+            InputPull::None => pin.into_input(),
+            InputPull::Up => pin.into_input_pullup(),
+            InputPull::Down => pin.into_input_pulldown(),
+        };
+
+        self.digital.insert(name.to_string(), DigitalSensor { pin, active_low });
+        Ok(())
+    }
+
+    /// Registers a device on an I2C bus.
+    pub fn register_i2c(&mut self, name: &str, bus: u8, address: u16) -> Result<(), Box<dyn Error>> {
+        self.check_name(name)?;
+
+        let mut i2c = I2c::with_bus(bus)?;
+        i2c.set_slave_address(address)?;
+
+        self.i2c.insert(name.to_string(), i2c);
+        Ok(())
+    }
+
+    /// Removes a sensor from the registry, releasing its pin or bus handle.
+    pub fn unregister(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        if self.digital.remove(name).is_none() && self.i2c.remove(name).is_none() {
+            return Err(format!("No sensor named '{}' is registered.", name).into());
+        }
+        Ok(())
+    }
+
+    /// Returns the names of all registered sensors.
+    pub fn names(&self) -> Vec<String> {
+        self.digital.keys().chain(self.i2c.keys()).cloned().collect()
+    }
+
+    /// Reads a digital sensor, taking its `active_low` setting into account.
+    pub fn read_digital(&self, name: &str) -> Result<bool, Box<dyn Error>> {
+        let sensor = self.digital.get(name)
+            .ok_or_else(|| format!("No digital sensor named '{}' is registered.", name))?;
+        Ok(sensor.pin.is_high() != sensor.active_low)
+    }
+
+    /// Reads `length` bytes from an I2C device, starting at `register`.
+    pub fn read_i2c(&mut self, name: &str, register: u8, length: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let device = self.i2c_device(name)?;
+        let mut buffer = vec![0u8; length];
+        device.write_read(&[register], &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Writes `data` to an I2C device, starting at `register`.
+    pub fn write_i2c(&mut self, name: &str, register: u8, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let device = self.i2c_device(name)?;
+        let mut buffer = Vec::with_capacity(data.len() + 1);
+        buffer.push(register);
+        buffer.extend_from_slice(data);
+        device.write(&buffer)?;
+        Ok(())
+    }
+
+    fn i2c_device(&mut self, name: &str) -> Result<&mut I2c, Box<dyn Error>> {
+        self.i2c.get_mut(name)
+            .ok_or_else(|| format!("No I2C device named '{}' is registered.", name).into())
+    }
+
+    fn check_name(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        if name.is_empty() {
+            return Err("Sensor name must not be empty.".into());
+        }
+        if self.digital.contains_key(name) || self.i2c.contains_key(name) {
+            return Err(format!("A sensor named '{}' is already registered.", name).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jni_arguments_are_range_checked() {
+        assert_eq!(check_pin(27).unwrap(), 27);
+        assert!(check_pin(28).is_err());
+        assert!(check_pin(260).is_err());
+        assert!(check_pin(-1).is_err());
+
+        assert_eq!(check_i2c_address(0x03).unwrap(), 0x03);
+        assert_eq!(check_i2c_address(0x77).unwrap(), 0x77);
+        assert!(check_i2c_address(0x02).is_err());
+        assert!(check_i2c_address(0x78).is_err());
+        assert!(check_i2c_address(0x148).is_err());
+
+        assert_eq!(check_register(255).unwrap(), 255);
+        assert!(check_register(256).is_err());
+        assert!(check_register(-1).is_err());
+
+        assert_eq!(check_read_length(32).unwrap(), 32);
+        assert!(check_read_length(0).is_err());
+        assert!(check_read_length(33).is_err());
+        assert!(check_read_length(i32::MAX).is_err());
+    }
+}
@@ -1,11 +1,90 @@
 use crate::config::*;
 use rppal::gpio::{Gpio, InputPin, OutputPin};
 use std::error::Error;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Unit in which distance readings are reported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DistanceUnit {
+    Centimeters,
+    Millimeters,
+    Inches,
+}
+
+impl DistanceUnit {
+    /// Maps the integer IDs used across JNI (0 = cm, 1 = mm, 2 = inches).
+    pub fn from_id(id: i32) -> Result<Self, Box<dyn Error>> {
+        match id {
+            0 => Ok(DistanceUnit::Centimeters),
+            1 => Ok(DistanceUnit::Millimeters),
+            2 => Ok(DistanceUnit::Inches),
+            _ => Err(format!("Invalid distance unit ID: {}. Must be between 0 and 2.", id).into()),
+        }
+    }
+
+    /// The integer ID used across JNI.
+    pub fn id(self) -> i32 {
+        match self {
+            DistanceUnit::Centimeters => 0,
+            DistanceUnit::Millimeters => 1,
+            DistanceUnit::Inches => 2,
+        }
+    }
+
+    /// Converts a distance in centimeters to this unit.
+    pub fn convert_cm(self, cm: f64) -> f64 {
+        match self {
+            DistanceUnit::Centimeters => cm,
+            DistanceUnit::Millimeters => cm * 10.0,
+            DistanceUnit::Inches => cm / 2.54,
+        }
+    }
+}
+
+/// Outcome of a single ultrasonic measurement.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DistanceStatus {
+    Valid,    //An echo was received from an object within range
+    TooClose, //An echo was received, but the object is closer than the minimum range
+    NoEcho,   //Nothing reflected the pulse within the maximum range (open space)
+    Timeout,  //The sensor did not answer in time, which usually means a wiring or hardware fault
+}
+
+impl DistanceStatus {
+    /// The integer ID used across JNI, matching the order of `DistanceReading.Status` in Java.
+    pub fn id(self) -> i32 {
+        match self {
+            DistanceStatus::Valid => 0,
+            DistanceStatus::TooClose => 1,
+            DistanceStatus::NoEcho => 2,
+            DistanceStatus::Timeout => 3,
+        }
+    }
+}
+
+/// A single ultrasonic measurement.
+#[derive(Clone, Copy, Debug)]
+pub struct DistanceReading {
+    pub value: f64,             //Distance in `unit`, or NaN if no echo pulse was measured
+    pub unit: DistanceUnit,     //Unit of `value`
+    pub timestamp: SystemTime,  //Time at which the measurement was triggered
+    pub status: DistanceStatus, //Whether `value` can be trusted
+    pub pulse_width: Duration,  //Raw width of the echo pulse
+}
+
+impl DistanceReading {
+    /// Returns `true` if the reading holds a distance within the sensor's range.
+    pub fn is_valid(&self) -> bool {
+        self.status == DistanceStatus::Valid
+    }
+}
 
 pub struct Sensors {
     pub ultra_trig: OutputPin,
     pub ultra_echo: InputPin,
+    pub speed_of_sound_cm_ns: f64, //Speed of sound used for the distance conversion
+    pub distance_unit: DistanceUnit, //Unit returned by `read_distance`
+    pub last_reading: Option<DistanceReading>, //Latest result of `read_distance`
 }
 
 impl Sensors {
@@ -16,11 +95,57 @@ impl Sensors {
         Ok(Sensors {
             ultra_trig,
             ultra_echo,
+            speed_of_sound_cm_ns: SPEED_OF_SOUND_CM_NS,
+            distance_unit: DistanceUnit::Centimeters,
+            last_reading: None,
         })
     }
 
-    pub fn read_distance(&mut self, timeout_ms: u64) -> Result<f64, Box<dyn Error>> {
+    /// Sets the speed of sound used for distance conversion, in metres per second.
+    pub fn set_speed_of_sound(&mut self, metres_per_second: f64) -> Result<(), Box<dyn Error>> {
+        if !metres_per_second.is_finite() || metres_per_second <= 0.0 {
+            return Err("Speed of sound must be a positive value.".into());
+        }
+        self.speed_of_sound_cm_ns = metres_per_second * 100.0 / 1e9;
+        Ok(())
+    }
+
+    /// Corrects the speed of sound for the ambient air temperature (°C) and, optionally,
+    /// the relative humidity (0 to 100 %).
+    pub fn set_ambient_conditions(&mut self, temperature_c: f64, humidity: Option<f64>) -> Result<(), Box<dyn Error>> {
+        if !(-40.0..=85.0).contains(&temperature_c) {
+            return Err(format!("Temperature {} °C is outside the sensor's operating range.", temperature_c).into());
+        }
+        let humidity = match humidity {
+            Some(h) if !(0.0..=100.0).contains(&h) => {
+                return Err(format!("Relative humidity {} % must be between 0 and 100.", h).into());
+            }
+            Some(h) => h,
+            None => 0.0,
+        };
+
+        //Linear approximation of the speed of sound in air, in m/s
+        self.set_speed_of_sound(331.4 + 0.606 * temperature_c + 0.0124 * humidity)
+    }
+
+    /// Selects the unit returned by `read_distance`.
+    pub fn set_distance_unit(&mut self, unit: DistanceUnit) {
+        self.distance_unit = unit;
+    }
+
+    /// Triggers the ultrasonic sensor and measures the echo.
+    ///
+    /// Out-of-range distances and missing echoes are normal conditions and are reported through
+    /// the reading's status rather than as errors.
+    pub fn read_distance(&mut self, timeout_ms: u64) -> DistanceReading {
+        let reading = self.measure_distance(timeout_ms);
+        self.last_reading = Some(reading);
+        reading
+    }
+
+    fn measure_distance(&mut self, timeout_ms: u64) -> DistanceReading {
         let timeout = Duration::from_millis(timeout_ms);
+        let timestamp = SystemTime::now();
 
         //Trigger a pulse by setting the trigger pin high for 10 microseconds
         self.ultra_trig.set_low();  //Ensure the trigger pin is low
@@ -29,11 +154,19 @@ impl Sensors {
         std::thread::sleep(Duration::from_micros(10));  //Pulse for 10 microseconds
         self.ultra_trig.set_low();
 
+        let timed_out = |pulse_width: Duration| DistanceReading {
+            value: f64::NAN,
+            unit: self.distance_unit,
+            timestamp,
+            status: DistanceStatus::Timeout,
+            pulse_width,
+        };
+
         //Wait for the echo pin to go high
         let start_wait = Instant::now();
         while self.ultra_echo.is_low() {
             if start_wait.elapsed() > timeout {
-                return Err("Timeout waiting for echo to start".into());
+                return timed_out(Duration::ZERO);
             }
         }
 
@@ -41,20 +174,28 @@ impl Sensors {
         let echo_start = Instant::now();
         while self.ultra_echo.is_high() {
             if echo_start.elapsed() > timeout {
-                return Err("Timeout waiting for echo to end".into());
+                return timed_out(echo_start.elapsed());
             }
         }
-        let pulse_duration = echo_start.elapsed();
+        let pulse_width = echo_start.elapsed();
 
-        //Calculate the distance in cm based on the duration and the speed of sound
-        //Speed of sound is ~34300 cm/s, which is ~0.0343 cm per microsecond
-        let distance = (pulse_duration.as_micros() as f64) * 0.0343 / 2.0;
+        //Calculate the distance in cm based on the round trip time and the speed of sound
+        let distance_cm = (pulse_width.as_nanos() as f64) * self.speed_of_sound_cm_ns / 2.0;
 
-        //Return the distance if within a reasonable range, otherwise return an error
-        if distance > 400.0 || distance < 2.0 {
-            Err("Distance out of range".into())
+        let status = if distance_cm < ULTRA_MIN_DISTANCE_CM {
+            DistanceStatus::TooClose
+        } else if distance_cm > ULTRA_MAX_DISTANCE_CM {
+            DistanceStatus::NoEcho
         } else {
-            Ok(distance)
+            DistanceStatus::Valid
+        };
+
+        DistanceReading {
+            value: self.distance_unit.convert_cm(distance_cm),
+            unit: self.distance_unit,
+            timestamp,
+            status,
+            pulse_width,
         }
     }
-}
\ No newline at end of file
+}
@@ -1,22 +1,165 @@
+use crate::color::Color;
+use crate::config::*;
+use crate::i2c::I2cBus;
+use crate::power::{LedGroup, SharedPowerBudget};
 use std::error::Error;
 use std::fs;
 use rppal::i2c::I2c;
 
 const REG_SHUTDOWN: u8 = 0x00;
 const REG_PWM_START: u8 = 0x01;
-const REG_ENABLE: u8 = 0x13;
+const REG_ENABLE: u8 = 0x13; //First of three LED control registers, 6 channels each
 const REG_UPDATE: u8 = 0x16;
 const REG_RESET: u8 = 0x17;
 const SLAVE_ADDRESS: u16 = 0x54;
+const NUM_CHANNELS: usize = 18;
+const ALL_CHANNELS: u32 = 0x3FFFF;
+const UNMAPPED: usize = 3; //Correction row for channels not wired to an underlight
+
+/// Order in which an RGB LED's color components are wired to consecutive SN3218 channels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+impl ChannelOrder {
+    /// Maps the integer IDs used across JNI (0 = RGB, 1 = RBG, 2 = GRB, 3 = GBR, 4 = BRG, 5 = BGR).
+    pub fn from_id(id: i32) -> Result<Self, Box<dyn Error>> {
+        match id {
+            0 => Ok(ChannelOrder::Rgb),
+            1 => Ok(ChannelOrder::Rbg),
+            2 => Ok(ChannelOrder::Grb),
+            3 => Ok(ChannelOrder::Gbr),
+            4 => Ok(ChannelOrder::Brg),
+            5 => Ok(ChannelOrder::Bgr),
+            _ => Err(format!("Invalid channel order: {}. Must be between 0 and 5.", id).into()),
+        }
+    }
+
+    /// Returns the color component (0 = red, 1 = green, 2 = blue) driven by each of the three channels.
+    fn components(self) -> [usize; 3] {
+        match self {
+            ChannelOrder::Rgb => [0, 1, 2],
+            ChannelOrder::Rbg => [0, 2, 1],
+            ChannelOrder::Grb => [1, 0, 2],
+            ChannelOrder::Gbr => [1, 2, 0],
+            ChannelOrder::Brg => [2, 0, 1],
+            ChannelOrder::Bgr => [2, 1, 0],
+        }
+    }
+}
+
+/// Wiring of one underlight to the SN3218.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LedLayout {
+    pub first_channel: u8,  //First of the three consecutive channels driving the LED
+    pub order: ChannelOrder,
+}
+
+/// Checks a gamma exponent for the color correction.
+pub fn check_gamma(gamma: f64) -> Result<(), Box<dyn Error>> {
+    if !(gamma > 0.0 && gamma <= 5.0) {
+        return Err(format!("Invalid gamma: {}. Must be greater than 0 and at most 5.", gamma).into());
+    }
+    Ok(())
+}
+
+/// Checks red, green and blue white balance gains for the color correction.
+pub fn check_white_balance(white_balance: [f64; 3]) -> Result<(), Box<dyn Error>> {
+    if white_balance.iter().any(|gain| !(0.0..=1.0).contains(gain)) {
+        return Err("White balance gains must be between 0.0 and 1.0.".into());
+    }
+    Ok(())
+}
+
+/// Checks an overall brightness limit for the color correction.
+pub fn check_brightness_limit(limit: f64) -> Result<(), Box<dyn Error>> {
+    if !(0.0..=1.0).contains(&limit) {
+        return Err(format!("Invalid brightness limit: {}. Must be between 0.0 and 1.0.", limit).into());
+    }
+    Ok(())
+}
+
+/// Gamma, white balance and brightness limit applied to every PWM value before it is written.
+struct ColorCorrection {
+    gamma: f64,
+    white_balance: [f64; 3], //Gain for the red, green and blue components
+    brightness_limit: f64,
+    table: [[u8; 256]; 4],   //Corrected output per component, plus unmapped channels, for each input value
+}
+
+impl ColorCorrection {
+    fn new(gamma: f64, white_balance: [f64; 3], brightness_limit: f64) -> Result<Self, Box<dyn Error>> {
+        let mut correction = ColorCorrection {
+            gamma: 1.0,
+            white_balance: [1.0; 3],
+            brightness_limit: 1.0,
+            table: [[0u8; 256]; 4],
+        };
+        correction.set_gamma(gamma)?;
+        correction.set_white_balance(white_balance)?;
+        correction.set_brightness_limit(brightness_limit)?;
+        Ok(correction)
+    }
+
+    fn set_gamma(&mut self, gamma: f64) -> Result<(), Box<dyn Error>> {
+        check_gamma(gamma)?;
+        self.gamma = gamma;
+        self.rebuild();
+        Ok(())
+    }
+
+    fn set_white_balance(&mut self, white_balance: [f64; 3]) -> Result<(), Box<dyn Error>> {
+        check_white_balance(white_balance)?;
+        self.white_balance = white_balance;
+        self.rebuild();
+        Ok(())
+    }
+
+    fn set_brightness_limit(&mut self, limit: f64) -> Result<(), Box<dyn Error>> {
+        check_brightness_limit(limit)?;
+        self.brightness_limit = limit;
+        self.rebuild();
+        Ok(())
+    }
+
+    fn rebuild(&mut self) {
+        for (component, table) in self.table.iter_mut().enumerate() {
+            //The unmapped row has no white balance gain
+            let balance = self.white_balance.get(component).copied().unwrap_or(1.0);
+            let gain = balance * self.brightness_limit;
+            for (input, output) in table.iter_mut().enumerate() {
+                let linear = (input as f64 / 255.0).powf(self.gamma);
+                *output = (linear * gain * 255.0).round() as u8;
+            }
+        }
+    }
+
+    /// Corrects `value` for a color component (0 = red, 1 = green, 2 = blue, 3 = unmapped).
+    fn apply(&self, component: usize, value: u8) -> u8 {
+        self.table[component][value as usize]
+    }
+}
 
 pub struct UnderlightLeds {
-    pub underlight: [u8; 18], // PWM values for each channel
-    pub sn3218: I2c,          // I2C interface for SN3218 communication
+    pub underlight: [u8; 18],     // PWM values for each channel
+    pub sn3218: Box<dyn I2cBus>,  // I2C interface for SN3218 communication
+    enable_mask: u32,             // Channels switched on in the LED control registers
+    correction: ColorCorrection,  // Applied to `underlight` when writing the PWM registers
+    layout: [LedLayout; NUM_UNDERLIGHTS], // Channels driving each underlight, indexed by light ID
+    components: [usize; NUM_CHANNELS],    // Color component driven by each channel
+    written: Option<[u8; NUM_CHANNELS]>,  // PWM values last sent, so unchanged frames are skipped
+    power: SharedPowerBudget,             // Scales the PWM values down when the LEDs draw too much current
 }
 
 impl UnderlightLeds {
     /// Initializes the SN3218 LED driver and performs an initial reset.
-    pub fn new() -> Result<Self, Box<dyn Error>> {
+    pub fn new(power: SharedPowerBudget) -> Result<Self, Box<dyn Error>> {
         if !Self::is_i2c_enabled()? {
             return Err("I2C is not enabled on this system. Enable it and try again.".into());
         }
@@ -24,12 +167,35 @@ impl UnderlightLeds {
         let mut sn3218 = I2c::new()?;
         sn3218.set_slave_address(SLAVE_ADDRESS)?;
 
+        Self::with_bus(Box::new(sn3218), power)
+    }
+
+    /// Initializes an SN3218 on the given bus: resets it, takes it out of shutdown,
+    /// switches every channel on and writes blank PWM values.
+    pub fn with_bus(sn3218: Box<dyn I2cBus>, power: SharedPowerBudget) -> Result<Self, Box<dyn Error>> {
         let underlight = [0u8; 18];
 
-        let mut leds = UnderlightLeds { underlight, sn3218 };
+        let correction = ColorCorrection::new(
+            UNDERLIGHT_GAMMA,
+            UNDERLIGHT_WHITE_BALANCE,
+            UNDERLIGHT_BRIGHTNESS_LIMIT,
+        )?;
+
+        let components = Self::map_components(&UNDERLIGHT_LAYOUT)?;
+
+        let mut leds = UnderlightLeds {
+            underlight,
+            sn3218,
+            enable_mask: ALL_CHANNELS,
+            correction,
+            layout: UNDERLIGHT_LAYOUT,
+            components,
+            written: None,
+            power,
+        };
         leds.reset()?;
         leds.enable()?;
-        leds.apply_changes()?;
+        leds.enable_leds(ALL_CHANNELS)?;
 
         Ok(leds)
     }
@@ -39,30 +205,118 @@ impl UnderlightLeds {
         Ok(fs::metadata("/dev/i2c-1").is_ok())
     }
 
+    /// Checks that every underlight has three channels of its own, and works out the color
+    /// component driven by each channel.
+    fn map_components(layout: &[LedLayout; NUM_UNDERLIGHTS]) -> Result<[usize; NUM_CHANNELS], Box<dyn Error>> {
+        let mut components = [UNMAPPED; NUM_CHANNELS];
+        for (light, led) in layout.iter().enumerate() {
+            let first = led.first_channel as usize;
+            if first + 3 > NUM_CHANNELS {
+                return Err(format!("Underlight {} is mapped past the last SN3218 channel.", light).into());
+            }
+            for (offset, component) in led.order.components().into_iter().enumerate() {
+                if components[first + offset] != UNMAPPED {
+                    return Err(format!("SN3218 channel {} is mapped to more than one underlight.", first + offset).into());
+                }
+                components[first + offset] = component;
+            }
+        }
+        Ok(components)
+    }
+
+    /// Returns the channels driving each underlight, indexed by light ID.
+    pub fn layout(&self) -> [LedLayout; NUM_UNDERLIGHTS] {
+        self.layout
+    }
+
+    /// Changes the channels driving every underlight at once, so lights can swap channels.
+    /// The whole layout is checked before anything changes. The current colors are kept and
+    /// re-sent on the new channels.
+    pub fn set_layout(&mut self, layout: [LedLayout; NUM_UNDERLIGHTS]) -> Result<(), Box<dyn Error>> {
+        let components = Self::map_components(&layout)?;
+
+        //Read back every color through the old layout before remapping
+        let frame = self.frame();
+        self.components = components;
+        self.layout = layout;
+
+        self.underlight.fill(0);
+        self.set_frame(&frame)
+    }
+
+    /// Changes the channels driving one underlight, keeping the others. The new channels must
+    /// not belong to another underlight; use `set_layout` to swap channels between lights.
+    pub fn set_light_layout(&mut self, led_id: u8, led: LedLayout) -> Result<(), Box<dyn Error>> {
+        if led_id as usize >= NUM_UNDERLIGHTS {
+            return Err(format!("Invalid RGB LED ID: {}. Must be between 0 and 5.", led_id).into());
+        }
+        let mut layout = self.layout;
+        layout[led_id as usize] = led;
+        self.set_layout(layout)
+    }
+
     /// Enables or disables specific channels based on an 18-bit mask.
     pub fn enable_leds(&mut self, enable_mask: u32) -> Result<(), Box<dyn Error>> {
-        if enable_mask > 0x3FFFF {
+        if enable_mask > ALL_CHANNELS {
             return Err("Enable mask must be a valid 18-bit value.".into());
         }
 
-        let mask_bytes = [
+        //The three control registers are written in one transaction, relying on the auto-incrementing address
+        let data = [
+            REG_ENABLE,
             (enable_mask & 0x3F) as u8,
             ((enable_mask >> 6) & 0x3F) as u8,
             ((enable_mask >> 12) & 0x3F) as u8,
         ];
+        self.sn3218.write(&data)
+            .map_err(|_| Box::<dyn Error>::from("Failed to write the SN3218 LED control registers."))?;
 
-        self.sn3218.write(&[REG_ENABLE])?;
-        self.sn3218.write(&mask_bytes)?;
-        self.apply_changes()
+        self.enable_mask = enable_mask;
+        self.apply_changes()?;
+
+        //Switched off channels no longer count against the power budget, which may rescale the rest
+        self.write_pwm_values()
+    }
+
+    /// Switches a single channel on or off, leaving the others unchanged.
+    pub fn set_channel_enabled(&mut self, channel: u8, enabled: bool) -> Result<(), Box<dyn Error>> {
+        if channel as usize >= NUM_CHANNELS {
+            return Err(format!("Invalid channel ID: {}. Must be between 0 and 17.", channel).into());
+        }
+        self.enable_leds(Self::with_bits(self.enable_mask, 0b1 << channel, enabled))
+    }
+
+    /// Switches all three channels of an underlight on or off, leaving the others unchanged.
+    /// A disabled underlight stays dark whatever color it is set to.
+    pub fn set_led_enabled(&mut self, led_id: u8, enabled: bool) -> Result<(), Box<dyn Error>> {
+        if led_id as usize >= NUM_UNDERLIGHTS {
+            return Err(format!("Invalid RGB LED ID: {}. Must be between 0 and 5.", led_id).into());
+        }
+        let bits = 0b111 << self.layout[led_id as usize].first_channel;
+        self.enable_leds(Self::with_bits(self.enable_mask, bits, enabled))
+    }
+
+    /// Returns `true` if all three channels of an underlight are switched on.
+    pub fn is_led_enabled(&self, led_id: u8) -> Result<bool, Box<dyn Error>> {
+        if led_id as usize >= NUM_UNDERLIGHTS {
+            return Err(format!("Invalid RGB LED ID: {}. Must be between 0 and 5.", led_id).into());
+        }
+        let bits = 0b111 << self.layout[led_id as usize].first_channel;
+        Ok(self.enable_mask & bits == bits)
+    }
+
+    fn with_bits(mask: u32, bits: u32, set: bool) -> u32 {
+        if set {
+            mask | bits
+        } else {
+            mask & !bits
+        }
     }
 
-    /// Sets each LED to a specified RGB color (assumes RGB configuration).
-    pub fn fill_underlight(&mut self, color: (u8, u8, u8)) -> Result<(), Box<dyn Error>> {
-        let (r, g, b) = color;
-        for i in 0..6 {
-            self.underlight[i * 3] = r;
-            self.underlight[i * 3 + 1] = g;
-            self.underlight[i * 3 + 2] = b;
+    /// Sets each LED to a specified RGB color.
+    pub fn fill_underlight(&mut self, color: Color) -> Result<(), Box<dyn Error>> {
+        for light in 0..NUM_UNDERLIGHTS as u8 {
+            self.set_rgb(light, color.r, color.g, color.b)?;
         }
         self.write_pwm_values()
     }
@@ -75,25 +329,48 @@ impl UnderlightLeds {
 
     /// Sets a specific channel's brightness.
     pub fn set_channel(&mut self, channel: u8, brightness: u8) -> Result<(), Box<dyn Error>> {
-        if channel >= 18 {
+        if channel as usize >= NUM_CHANNELS {
             return Err(format!("Invalid channel ID: {}. Must be between 0 and 17.", channel).into());
         }
         self.underlight[channel as usize] = brightness;
         Ok(())
     }
 
-    /// Sets an RGB LED's color (for RGB configuration).
+    /// Sets an RGB LED's color, using the channels and channel order from the layout.
     pub fn set_rgb(&mut self, led_id: u8, r: u8, g: u8, b: u8) -> Result<(), Box<dyn Error>> {
-        if led_id >= 6 {
+        if led_id as usize >= NUM_UNDERLIGHTS {
             return Err(format!("Invalid RGB LED ID: {}. Must be between 0 and 5.", led_id).into());
         }
-        let base_channel = led_id as usize * 3;
-        self.underlight[base_channel] = r;
-        self.underlight[base_channel + 1] = g;
-        self.underlight[base_channel + 2] = b;
+        let led = self.layout[led_id as usize];
+        let color = [r, g, b];
+        for (offset, component) in led.order.components().into_iter().enumerate() {
+            self.underlight[led.first_channel as usize + offset] = color[component];
+        }
         Ok(())
     }
 
+    /// Returns the color of every underlight, indexed by light ID, before color correction.
+    pub fn frame(&self) -> [Color; NUM_UNDERLIGHTS] {
+        let mut frame = [Color::BLACK; NUM_UNDERLIGHTS];
+        for (light, color) in frame.iter_mut().enumerate() {
+            let led = self.layout[light];
+            let mut components = [0u8; 3];
+            for (offset, component) in led.order.components().into_iter().enumerate() {
+                components[component] = self.underlight[led.first_channel as usize + offset];
+            }
+            *color = Color::rgb(components[0], components[1], components[2]);
+        }
+        frame
+    }
+
+    /// Sets the color of every underlight, indexed by light ID, and sends them in a single update.
+    pub fn set_frame(&mut self, frame: &[Color; NUM_UNDERLIGHTS]) -> Result<(), Box<dyn Error>> {
+        for (light, color) in frame.iter().enumerate() {
+            self.set_rgb(light as u8, color.r, color.g, color.b)?;
+        }
+        self.write_pwm_values()
+    }
+
     /// Updates the SN3218 to reflect the current values in `underlight`.
     pub fn update_underlighting(&mut self) -> Result<(), Box<dyn Error>> {
         self.write_pwm_values()
@@ -105,23 +382,43 @@ impl UnderlightLeds {
         self.update_underlighting()
     }
 
+    /// Sets the gamma used to linearise perceived brightness (1.0 disables correction).
+    pub fn set_gamma(&mut self, gamma: f64) -> Result<(), Box<dyn Error>> {
+        self.correction.set_gamma(gamma)?;
+        self.write_pwm_values()
+    }
+
+    /// Sets the red, green and blue gains (0.0 to 1.0) used to balance the LED colors.
+    pub fn set_white_balance(&mut self, white_balance: [f64; 3]) -> Result<(), Box<dyn Error>> {
+        self.correction.set_white_balance(white_balance)?;
+        self.write_pwm_values()
+    }
+
+    /// Limits the overall brightness (0.0 to 1.0) of every channel.
+    pub fn set_brightness_limit(&mut self, limit: f64) -> Result<(), Box<dyn Error>> {
+        self.correction.set_brightness_limit(limit)?;
+        self.write_pwm_values()
+    }
+
     /// Resets the SN3218 device.
     pub fn reset(&mut self) -> Result<(), Box<dyn Error>> {
         self.sn3218.write(&[REG_RESET, 0xFF])
             .map_err(|_| Box::<dyn Error>::from("Failed to reset SN3218."))?;
+        self.written = None;
         Ok(())
     }
 
-    /// Enables SN3218 output.
+    /// Enables SN3218 output by taking the chip out of software shutdown.
     pub fn enable(&mut self) -> Result<(), Box<dyn Error>> {
-        self.sn3218.write(&[REG_ENABLE, 0x00])
+        self.sn3218.write(&[REG_SHUTDOWN, 0x01])
             .map_err(|_| Box::<dyn Error>::from("Failed to enable SN3218."))?;
         Ok(())
     }
 
-    /// Disables SN3218 output.
+    /// Disables SN3218 output by putting the chip into software shutdown. PWM values and
+    /// channel enables are kept.
     pub fn disable(&mut self) -> Result<(), Box<dyn Error>> {
-        self.sn3218.write(&[REG_ENABLE, 0x01])
+        self.sn3218.write(&[REG_SHUTDOWN, 0x00])
             .map_err(|_| Box::<dyn Error>::from("Failed to disable SN3218."))?;
         Ok(())
     }
@@ -133,13 +430,254 @@ impl UnderlightLeds {
         Ok(())
     }
 
-    /// Writes the `underlight` buffer to the PWM registers.
+    /// Writes the `underlight` buffer to the PWM registers, after color correction and power
+    /// budget scaling. Nothing is sent if the values match those last written.
     fn write_pwm_values(&mut self) -> Result<(), Box<dyn Error>> {
-        self.sn3218.write(&[REG_PWM_START])
-            .map_err(|_| Box::<dyn Error>::from("Failed to start register writing for PWM."))?;
-        self.sn3218.write(&self.underlight)
+        let mut output = [0u8; NUM_CHANNELS];
+        for (channel, value) in self.underlight.iter().enumerate() {
+            output[channel] = self.correction.apply(self.components[channel], *value);
+        }
+
+        //Switched off channels draw no current
+        let demand: f64 = output.iter()
+            .enumerate()
+            .filter(|(channel, _)| self.enable_mask & (1 << channel) != 0)
+            .map(|(_, value)| *value as f64 / 255.0 * UNDERLIGHT_CHANNEL_MA)
+            .sum();
+        let scale = self.power.lock().unwrap().scale(LedGroup::Underlights, demand);
+        if scale < 1.0 {
+            for value in output.iter_mut() {
+                *value = (*value as f64 * scale) as u8;
+            }
+        }
+        if self.written == Some(output) {
+            return Ok(());
+        }
+
+        let mut data = [0u8; NUM_CHANNELS + 1];
+        data[0] = REG_PWM_START;
+        data[1..].copy_from_slice(&output);
+
+        //Forget the last frame until the update succeeds, so a failed write is retried
+        self.written = None;
+        self.sn3218.write(&data)
             .map_err(|_| Box::<dyn Error>::from("Failed to write PWM values to SN3218."))?;
         self.apply_changes()?;
+        self.written = Some(output);
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i2c::fake::FakeI2c;
+    use crate::power::PowerBudget;
+    use std::sync::Arc;
+
+    fn unlimited() -> SharedPowerBudget {
+        PowerBudget::shared(Arc::new(|| {}))
+    }
+
+    fn leds() -> (UnderlightLeds, FakeI2c) {
+        let bus = FakeI2c::new();
+        let leds = UnderlightLeds::with_bus(Box::new(bus.clone()), unlimited()).unwrap();
+        bus.clear_writes();
+        (leds, bus)
+    }
+
+    fn enable_registers(bus: &FakeI2c) -> [u8; 3] {
+        [bus.register(REG_ENABLE), bus.register(REG_ENABLE + 1), bus.register(REG_ENABLE + 2)]
+    }
+
+    fn pwm_registers(bus: &FakeI2c) -> Vec<u8> {
+        (0..NUM_CHANNELS as u8).map(|channel| bus.register(REG_PWM_START + channel)).collect()
+    }
+
+    #[test]
+    fn initialization_resets_and_enables_every_channel() {
+        let bus = FakeI2c::new();
+        UnderlightLeds::with_bus(Box::new(bus.clone()), unlimited()).unwrap();
+
+        let mut blank = vec![REG_PWM_START];
+        blank.extend_from_slice(&[0u8; NUM_CHANNELS]);
+        assert_eq!(
+            bus.writes(),
+            vec![
+                vec![REG_RESET, 0xFF],
+                vec![REG_SHUTDOWN, 0x01],
+                vec![REG_ENABLE, 0x3F, 0x3F, 0x3F],
+                vec![REG_UPDATE, 0xFF],
+                blank,
+                vec![REG_UPDATE, 0xFF],
+            ]
+        );
+        assert_eq!(bus.register(REG_SHUTDOWN), 0x01);
+        assert_eq!(enable_registers(&bus), [0x3F, 0x3F, 0x3F]);
+    }
+
+    #[test]
+    fn enable_mask_is_written_in_one_transaction() {
+        let (mut leds, bus) = leds();
+        leds.enable_leds(0b000111_000000_111111).unwrap();
+
+        assert_eq!(
+            bus.writes(),
+            vec![vec![REG_ENABLE, 0x3F, 0x00, 0x07], vec![REG_UPDATE, 0xFF]]
+        );
+        assert_eq!(enable_registers(&bus), [0x3F, 0x00, 0x07]);
+    }
+
+    #[test]
+    fn enable_mask_must_fit_in_18_bits() {
+        let (mut leds, bus) = leds();
+        assert!(leds.enable_leds(ALL_CHANNELS + 1).is_err());
+        assert!(bus.writes().is_empty());
+    }
+
+    #[test]
+    fn shutdown_register_controls_output() {
+        let (mut leds, bus) = leds();
+        leds.disable().unwrap();
+        assert_eq!(bus.register(REG_SHUTDOWN), 0x00);
+        leds.enable().unwrap();
+        assert_eq!(bus.register(REG_SHUTDOWN), 0x01);
+        assert_eq!(enable_registers(&bus), [0x3F, 0x3F, 0x3F]);
+    }
+
+    #[test]
+    fn leds_are_enabled_individually() {
+        let (mut leds, bus) = leds();
+        leds.set_led_enabled(LIGHT_MIDDLE_LEFT, false).unwrap();
+        assert_eq!(enable_registers(&bus), [0x3F, 0x38, 0x3F]);
+        assert!(!leds.is_led_enabled(LIGHT_MIDDLE_LEFT).unwrap());
+        assert!(leds.is_led_enabled(LIGHT_REAR_LEFT).unwrap());
+
+        leds.set_led_enabled(LIGHT_MIDDLE_RIGHT, false).unwrap();
+        assert_eq!(enable_registers(&bus), [0x3F, 0x38, 0x07]);
+
+        leds.set_led_enabled(LIGHT_MIDDLE_LEFT, true).unwrap();
+        assert_eq!(enable_registers(&bus), [0x3F, 0x3F, 0x07]);
+        assert!(leds.set_led_enabled(NUM_UNDERLIGHTS as u8, false).is_err());
+    }
+
+    #[test]
+    fn channels_are_enabled_individually() {
+        let (mut leds, bus) = leds();
+        leds.set_channel_enabled(17, false).unwrap();
+        leds.set_channel_enabled(0, false).unwrap();
+        assert_eq!(enable_registers(&bus), [0x3E, 0x3F, 0x1F]);
+        assert!(leds.set_channel_enabled(18, false).is_err());
+    }
+
+    #[test]
+    fn pwm_values_are_written_in_one_transaction() {
+        let (mut leds, bus) = leds();
+        leds.set_gamma(1.0).unwrap();
+        bus.clear_writes();
+
+        leds.set_rgb(LIGHT_FRONT_LEFT, 10, 20, 30).unwrap();
+        leds.update_underlighting().unwrap();
+
+        let mut expected = vec![0u8; NUM_CHANNELS];
+        expected[3..6].copy_from_slice(&[10, 20, 30]);
+        let mut transaction = vec![REG_PWM_START];
+        transaction.extend_from_slice(&expected);
+        assert_eq!(bus.writes(), vec![transaction, vec![REG_UPDATE, 0xFF]]);
+        assert_eq!(pwm_registers(&bus), expected);
+    }
+
+    #[test]
+    fn unchanged_frames_are_not_resent() {
+        let (mut leds, bus) = leds();
+        let mut frame = [Color::rgb(255, 0, 0); NUM_UNDERLIGHTS];
+        leds.set_frame(&frame).unwrap();
+        assert_eq!(bus.writes().len(), 2);
+
+        bus.clear_writes();
+        leds.set_frame(&frame).unwrap();
+        leds.update_underlighting().unwrap();
+        assert!(bus.writes().is_empty());
+
+        frame[LIGHT_REAR_LEFT as usize] = Color::rgb(0, 255, 0);
+        leds.set_frame(&frame).unwrap();
+        assert_eq!(bus.writes().len(), 2);
+        assert_eq!(bus.register(REG_PWM_START + 10), 255);
+
+        //Changing the correction changes the output, so the frame is sent again
+        bus.clear_writes();
+        leds.set_brightness_limit(0.5).unwrap();
+        assert_eq!(bus.writes().len(), 2);
+    }
+
+    #[test]
+    fn layout_sets_channels_and_order() {
+        let (mut leds, bus) = leds();
+        leds.set_gamma(1.0).unwrap();
+        leds.set_rgb(LIGHT_FRONT_RIGHT, 1, 2, 3).unwrap();
+        leds.set_light_layout(LIGHT_FRONT_RIGHT, LedLayout { first_channel: 0, order: ChannelOrder::Grb }).unwrap();
+
+        //The color set before remapping is moved onto the new order
+        assert_eq!(&pwm_registers(&bus)[0..3], &[2, 1, 3]);
+
+        //Channels 3 to 5 already belong to the front left light
+        let overlapping = LedLayout { first_channel: 3, order: ChannelOrder::Rgb };
+        assert!(leds.set_light_layout(LIGHT_FRONT_RIGHT, overlapping).is_err());
+        let out_of_range = LedLayout { first_channel: 16, order: ChannelOrder::Rgb };
+        assert!(leds.set_light_layout(LIGHT_FRONT_RIGHT, out_of_range).is_err());
+    }
+
+    #[test]
+    fn layout_can_swap_the_channels_of_two_lights() {
+        let (mut leds, bus) = leds();
+        leds.set_gamma(1.0).unwrap();
+        leds.set_rgb(LIGHT_FRONT_RIGHT, 1, 2, 3).unwrap();
+        leds.set_rgb(LIGHT_FRONT_LEFT, 4, 5, 6).unwrap();
+
+        let mut layout = leds.layout();
+        layout.swap(LIGHT_FRONT_RIGHT as usize, LIGHT_FRONT_LEFT as usize);
+        leds.set_layout(layout).unwrap();
+
+        //Each light keeps its color, on the other's channels
+        assert_eq!(leds.layout(), layout);
+        assert_eq!(&pwm_registers(&bus)[0..6], &[4, 5, 6, 1, 2, 3]);
+
+        //A rejected layout changes nothing
+        let mut overlapping = layout;
+        overlapping[LIGHT_REAR_LEFT as usize] = overlapping[LIGHT_FRONT_LEFT as usize];
+        assert!(leds.set_layout(overlapping).is_err());
+        assert_eq!(leds.layout(), layout);
+        assert_eq!(&pwm_registers(&bus)[0..6], &[4, 5, 6, 1, 2, 3]);
+    }
+
+    #[test]
+    fn correction_is_applied_to_pwm_registers() {
+        let (mut leds, bus) = leds();
+        leds.fill_underlight(Color::rgb(128, 128, 128)).unwrap();
+        let expected = ((128.0f64 / 255.0).powf(UNDERLIGHT_GAMMA) * 255.0).round() as u8;
+        assert_eq!(bus.register(REG_PWM_START), expected);
+
+        leds.set_gamma(1.0).unwrap();
+        leds.set_white_balance([1.0, 0.5, 0.25]).unwrap();
+        leds.set_brightness_limit(0.5).unwrap();
+        assert_eq!(&pwm_registers(&bus)[0..3], &[64, 32, 16]);
+    }
+
+    #[test]
+    fn power_budget_scales_pwm_registers() {
+        let bus = FakeI2c::new();
+        let power = unlimited();
+        let mut leds = UnderlightLeds::with_bus(Box::new(bus.clone()), Arc::clone(&power)).unwrap();
+        leds.fill_underlight(Color::rgb(255, 255, 255)).unwrap();
+        assert_eq!(bus.register(REG_PWM_START), 255);
+
+        //All 18 channels at full draw 360 mA, so a 90 mA budget quarters them
+        power.lock().unwrap().set_limit(Some(90.0)).unwrap();
+        leds.update_underlighting().unwrap();
+        assert!(pwm_registers(&bus).iter().all(|value| *value == 63));
+        assert_eq!(power.lock().unwrap().estimated_ma(), 90.0);
+
+        //Switched off channels are not counted
+        leds.enable_leds(0x3F).unwrap();
+        assert_eq!(&pwm_registers(&bus)[0..6], &[191; 6]);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::animation::{AnimationEngine, Effect};
+use crate::button_effects::{ButtonLedEffects, LedEffect};
+use crate::buttons::Buttons;
+use crate::color::Color;
+use crate::config::*;
+use crate::sn3218::UnderlightLeds;
+use std::error::Error;
+use std::sync::Mutex;
+
+/// Most snapshots that can be pushed without popping, to catch unbalanced push/pop calls.
+pub const MAX_SNAPSHOTS: usize = 16;
+
+/// The state of every LED at one moment, including the effects playing, so it can be
+/// put back after a temporary effect. Restored effects start again from the beginning.
+pub struct LedSnapshot {
+    frame: [Color; NUM_UNDERLIGHTS],
+    underlight_effect: Option<Effect>,
+    button_levels: [f64; NUM_BUTTONS],
+    button_effects: [Option<LedEffect>; NUM_BUTTONS],
+}
+
+impl LedSnapshot {
+    /// Records the current state of the LEDs.
+    pub fn capture(
+        underlights: &Mutex<UnderlightLeds>,
+        animation: &Mutex<AnimationEngine>,
+        buttons: &Mutex<Buttons>,
+        button_effects: &Mutex<ButtonLedEffects>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let underlight_effect = animation.lock().unwrap().effect().cloned();
+        let frame = underlights.lock().unwrap().frame();
+
+        let mut button_levels = [0.0; NUM_BUTTONS];
+        let mut effects = [None, None, None, None];
+        for button in 0..NUM_BUTTONS {
+            effects[button] = button_effects.lock().unwrap().effect(button as u8);
+            button_levels[button] = buttons.lock().unwrap().button_led(button as u8)?;
+        }
+
+        Ok(LedSnapshot {
+            frame,
+            underlight_effect,
+            button_levels,
+            button_effects: effects,
+        })
+    }
+
+    /// Puts the LEDs back into the recorded state, replacing any effects playing.
+    pub fn restore(
+        self,
+        underlights: &'static Mutex<UnderlightLeds>,
+        animation: &'static Mutex<AnimationEngine>,
+        buttons: &'static Mutex<Buttons>,
+        button_effects: &'static Mutex<ButtonLedEffects>,
+    ) -> Result<(), Box<dyn Error>> {
+        animation.lock().unwrap().stop();
+        underlights.lock().unwrap().set_frame(&self.frame)?;
+        if let Some(effect) = self.underlight_effect {
+            animation.lock().unwrap().start(underlights, effect);
+        }
+
+        for (button, effect) in self.button_effects.into_iter().enumerate() {
+            button_effects.lock().unwrap().stop(button as u8);
+            buttons.lock().unwrap().set_button_led(button as u8, self.button_levels[button])?;
+            if let Some(effect) = effect {
+                button_effects.lock().unwrap().start(buttons, button as u8, effect)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Snapshots saved by the application, restored newest first. Holds at most `MAX_SNAPSHOTS`.
+#[derive(Default)]
+pub struct SnapshotStack {
+    snapshots: Vec<LedSnapshot>,
+}
+
+impl SnapshotStack {
+    /// Checks there is room for another snapshot, so the LEDs are only captured if it can
+    /// be saved.
+    pub fn check_room(&self) -> Result<(), Box<dyn Error>> {
+        if self.snapshots.len() >= MAX_SNAPSHOTS {
+            return Err(format!("Too many LED snapshots. At most {} can be saved.", MAX_SNAPSHOTS).into());
+        }
+        Ok(())
+    }
+
+    /// Saves a snapshot, and returns the number saved including it.
+    pub fn push(&mut self, snapshot: LedSnapshot) -> Result<usize, Box<dyn Error>> {
+        self.check_room()?;
+        self.snapshots.push(snapshot);
+        Ok(self.snapshots.len())
+    }
+
+    /// Removes the newest snapshot, if any.
+    pub fn pop(&mut self) -> Option<LedSnapshot> {
+        self.snapshots.pop()
+    }
+
+    /// Returns the number of snapshots saved.
+    pub fn count(&self) -> usize {
+        self.snapshots.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(level: f64) -> LedSnapshot {
+        LedSnapshot {
+            frame: [Color::rgb(0, 0, 0); NUM_UNDERLIGHTS],
+            underlight_effect: None,
+            button_levels: [level; NUM_BUTTONS],
+            button_effects: [None, None, None, None],
+        }
+    }
+
+    #[test]
+    fn snapshots_are_popped_newest_first() {
+        let mut stack = SnapshotStack::default();
+        assert!(stack.pop().is_none());
+
+        assert_eq!(stack.push(snapshot(0.1)).unwrap(), 1);
+        assert_eq!(stack.push(snapshot(0.2)).unwrap(), 2);
+        assert_eq!(stack.pop().unwrap().button_levels[0], 0.2);
+        assert_eq!(stack.pop().unwrap().button_levels[0], 0.1);
+        assert!(stack.pop().is_none());
+        assert_eq!(stack.count(), 0);
+    }
+
+    #[test]
+    fn stack_is_limited_to_max_snapshots() {
+        let mut stack = SnapshotStack::default();
+        for i in 0..MAX_SNAPSHOTS {
+            assert_eq!(stack.push(snapshot(0.0)).unwrap(), i + 1);
+        }
+        assert!(stack.check_room().is_err());
+        assert!(stack.push(snapshot(1.0)).is_err());
+        assert_eq!(stack.count(), MAX_SNAPSHOTS);
+
+        //The rejected snapshot was not saved
+        assert_eq!(stack.pop().unwrap().button_levels[0], 0.0);
+        assert!(stack.check_room().is_ok());
+    }
+}
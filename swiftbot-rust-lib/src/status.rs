@@ -0,0 +1,424 @@
+use crate::animation::{AnimationEngine, Effect};
+use crate::button_effects::{ButtonLedEffects, LedEffect};
+use crate::buttons::Buttons;
+use crate::color::Color;
+use crate::config::*;
+use crate::sn3218::UnderlightLeds;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Highest priority an application layer can have. Layers sit above the normal statuses
+/// and below the safety statuses, so a warning is never hidden by a decorative effect.
+pub const MAX_LAYER_PRIORITY: i32 = 99;
+
+const LAYER_PRIORITY_BASE: i32 = 100;
+
+/// A state of the robot shown on the LEDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SystemStatus {
+    Idle,
+    Moving,
+    RemoteConnected,
+    Booting,
+    LowBattery,
+    ObstacleWarning,
+    Error,
+}
+
+impl SystemStatus {
+    /// Maps the integer IDs used across JNI (0 = booting, 1 = idle, 2 = moving,
+    /// 3 = obstacle warning, 4 = error, 5 = low battery, 6 = remote connected).
+    pub fn from_id(id: i32) -> Result<Self, Box<dyn Error>> {
+        match id {
+            0 => Ok(SystemStatus::Booting),
+            1 => Ok(SystemStatus::Idle),
+            2 => Ok(SystemStatus::Moving),
+            3 => Ok(SystemStatus::ObstacleWarning),
+            4 => Ok(SystemStatus::Error),
+            5 => Ok(SystemStatus::LowBattery),
+            6 => Ok(SystemStatus::RemoteConnected),
+            _ => Err(format!("Invalid status ID: {}. Must be between 0 and 6.", id).into()),
+        }
+    }
+
+    /// Every status, in ID order.
+    pub const ALL: [SystemStatus; 7] = [
+        SystemStatus::Booting,
+        SystemStatus::Idle,
+        SystemStatus::Moving,
+        SystemStatus::ObstacleWarning,
+        SystemStatus::Error,
+        SystemStatus::LowBattery,
+        SystemStatus::RemoteConnected,
+    ];
+
+    /// Name used in logs and snapshot sidecars.
+    pub fn name(self) -> &'static str {
+        match self {
+            SystemStatus::Booting => "booting",
+            SystemStatus::Idle => "idle",
+            SystemStatus::Moving => "moving",
+            SystemStatus::ObstacleWarning => "obstacle_warning",
+            SystemStatus::Error => "error",
+            SystemStatus::LowBattery => "low_battery",
+            SystemStatus::RemoteConnected => "remote_connected",
+        }
+    }
+
+    /// Returns `true` for the statuses shown above every application layer.
+    fn is_safety(self) -> bool {
+        self.priority() > LAYER_PRIORITY_BASE + MAX_LAYER_PRIORITY
+    }
+
+    /// Normal statuses rank below application layers, safety statuses above them.
+    fn priority(self) -> i32 {
+        match self {
+            SystemStatus::Idle => 10,
+            SystemStatus::Moving => 20,
+            SystemStatus::RemoteConnected => 30,
+            SystemStatus::Booting => 40,
+            SystemStatus::LowBattery => 200,
+            SystemStatus::ObstacleWarning => 210,
+            SystemStatus::Error => 220,
+        }
+    }
+
+    fn indicator(self) -> Indicator {
+        let ms = Duration::from_millis;
+        let (underlight, buttons) = match self {
+            SystemStatus::Idle => (Effect::Breathe { color: Color::rgb(0, 0, 64), period: ms(4000) }, None),
+            SystemStatus::Moving => (Effect::Chase { color: Color::rgb(0, 255, 0), period: ms(600) }, None),
+            SystemStatus::RemoteConnected => (Effect::Breathe { color: Color::rgb(0, 255, 255), period: ms(2000) }, None),
+            SystemStatus::Booting => (
+                Effect::Comet { color: Color::rgb(255, 255, 255), period: ms(1000) },
+                Some(LedEffect::Breathe { level: 0.5, period: ms(1000) }),
+            ),
+            SystemStatus::LowBattery => (
+                Effect::Blink { color: Color::rgb(255, 128, 0), period: ms(2000) },
+                None,
+            ),
+            SystemStatus::ObstacleWarning => (
+                Effect::Blink { color: Color::rgb(255, 0, 0), period: ms(250) },
+                None,
+            ),
+            SystemStatus::Error => (
+                Effect::Breathe { color: Color::rgb(255, 0, 0), period: ms(1000) },
+                Some(LedEffect::Blink { level: 1.0, period: ms(500), duty: 0.5 }),
+            ),
+        };
+        Indicator { underlight, buttons }
+    }
+}
+
+/// What a layer shows: an underlight effect, and optionally an effect on every button LED.
+#[derive(Clone)]
+struct Indicator {
+    underlight: Effect,
+    buttons: Option<LedEffect>,
+}
+
+/// Identifies the layer currently shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum LayerKey {
+    Status(SystemStatus),
+    App(u32),
+}
+
+/// The active statuses and application layers, and which of them is on the LEDs. Kept
+/// apart from the LEDs so the priority rules can be tested.
+struct LayerStack {
+    statuses: BTreeSet<SystemStatus>,        //Active statuses
+    layers: BTreeMap<u32, (i32, Indicator)>, //Application layers by ID, with their priority
+    next_layer: u32,
+    shown: Option<LayerKey>,                 //`None` if nothing is shown, or another writer took the LEDs
+}
+
+impl LayerStack {
+    fn new() -> Self {
+        LayerStack {
+            statuses: BTreeSet::from([SystemStatus::Idle]),
+            layers: BTreeMap::new(),
+            next_layer: 0,
+            shown: None,
+        }
+    }
+
+    /// Finds the highest priority layer. Ties go to the newest application layer.
+    fn top(&self) -> Option<(LayerKey, Indicator)> {
+        let statuses = self.statuses
+            .iter()
+            .map(|s| (s.priority(), LayerKey::Status(*s), s.indicator()));
+        let layers = self.layers
+            .iter()
+            .map(|(id, (priority, indicator))| (*priority, LayerKey::App(*id), indicator.clone()));
+        statuses
+            .chain(layers)
+            .max_by_key(|(priority, key, _)| (*priority, *key))
+            .map(|(_, key, indicator)| (key, indicator))
+    }
+
+    /// Returns what to show if the top layer is not the one shown, and records it as shown.
+    /// `Some(None)` means the LEDs should be turned off.
+    fn take_change(&mut self) -> Option<Option<Indicator>> {
+        let top = self.top();
+        let key = top.as_ref().map(|(key, _)| *key);
+        if key == self.shown {
+            return None;
+        }
+        self.shown = key;
+        Some(top.map(|(_, indicator)| indicator))
+    }
+
+    /// Hands the underlights to another writer, unless a safety status is shown. The top
+    /// layer is shown again on the next change.
+    fn release_underlights(&mut self) -> bool {
+        if matches!(self.shown, Some(LayerKey::Status(status)) if status.is_safety()) {
+            return false;
+        }
+        self.shown = None;
+        true
+    }
+
+    /// Hands the button LEDs to another writer, unless a safety status is shown on them.
+    /// Only a shown status with a button effect is affected.
+    fn release_buttons(&mut self) -> bool {
+        match self.shown {
+            Some(LayerKey::Status(status)) if status.indicator().buttons.is_some() => self.release_underlights(),
+            _ => true,
+        }
+    }
+}
+
+/// Shows the state of the robot on the underlights and button LEDs. Statuses and
+/// application layers are stacked by priority, and only the highest is shown.
+pub struct StatusManager {
+    underlights: &'static Mutex<UnderlightLeds>,
+    animation: &'static Mutex<AnimationEngine>,
+    buttons: &'static Mutex<Buttons>,
+    button_effects: &'static Mutex<ButtonLedEffects>,
+    enabled: bool,
+    stack: LayerStack,
+}
+
+impl StatusManager {
+    /// Creates a disabled manager driving the given LEDs.
+    pub fn new(
+        underlights: &'static Mutex<UnderlightLeds>,
+        animation: &'static Mutex<AnimationEngine>,
+        buttons: &'static Mutex<Buttons>,
+        button_effects: &'static Mutex<ButtonLedEffects>,
+    ) -> Self {
+        StatusManager {
+            underlights,
+            animation,
+            buttons,
+            button_effects,
+            enabled: false,
+            stack: LayerStack::new(),
+        }
+    }
+
+    /// Starts or stops showing status on the LEDs. When stopped, the LEDs are turned off
+    /// and left to the application.
+    pub fn set_enabled(&mut self, enabled: bool) -> Result<(), Box<dyn Error>> {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear()?;
+        }
+        self.render()
+    }
+
+    /// Turns a status on or off.
+    pub fn set_status(&mut self, status: SystemStatus, active: bool) -> Result<(), Box<dyn Error>> {
+        if active {
+            self.stack.statuses.insert(status);
+        } else {
+            self.stack.statuses.remove(&status);
+        }
+        self.render()
+    }
+
+    /// Returns `true` if a status is on.
+    pub fn is_status_active(&self, status: SystemStatus) -> bool {
+        self.stack.statuses.contains(&status)
+    }
+
+    /// Called before the application writes to the underlights, with the manager locked for
+    /// the duration of the write. Returns `false` if the write must be skipped because a
+    /// safety status is shown. Otherwise the application takes over the underlights until the
+    /// shown layer next changes, when it is shown again.
+    pub fn claim_underlights(&mut self) -> bool {
+        !self.enabled || self.stack.release_underlights()
+    }
+
+    /// Like `claim_underlights`, for a write to the button LEDs. Statuses that leave the
+    /// button LEDs alone do not stop the write.
+    pub fn claim_buttons(&mut self) -> bool {
+        !self.enabled || self.stack.release_buttons()
+    }
+
+    /// Adds an application layer showing `effect` on the underlights, and returns its ID.
+    /// Among layers of equal priority, the newest is shown.
+    pub fn push_layer(&mut self, priority: i32, effect: Effect) -> Result<u32, Box<dyn Error>> {
+        if !(0..=MAX_LAYER_PRIORITY).contains(&priority) {
+            return Err(format!("Invalid layer priority: {}. Must be between 0 and {}.", priority, MAX_LAYER_PRIORITY).into());
+        }
+        let id = self.stack.next_layer;
+        self.stack.next_layer += 1;
+        self.stack.layers.insert(id, (LAYER_PRIORITY_BASE + priority, Indicator { underlight: effect, buttons: None }));
+        self.render()?;
+        Ok(id)
+    }
+
+    /// Removes an application layer.
+    pub fn remove_layer(&mut self, id: u32) -> Result<(), Box<dyn Error>> {
+        if self.stack.layers.remove(&id).is_none() {
+            return Err(format!("No status layer with ID {}.", id).into());
+        }
+        self.render()
+    }
+
+    /// Shows the highest priority layer, if it has changed.
+    fn render(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.enabled {
+            self.stack.shown = None;
+            return Ok(());
+        }
+
+        let top = match self.stack.take_change() {
+            Some(top) => top,
+            None => return Ok(()),
+        };
+
+        //If showing it fails, it is tried again on the next change
+        let shown = self.stack.shown.take();
+        self.clear()?;
+        if let Some(indicator) = top {
+            self.animation.lock().unwrap().start(self.underlights, indicator.underlight);
+            if let Some(effect) = indicator.buttons {
+                let mut button_effects = self.button_effects.lock().unwrap();
+                for button in 0..NUM_BUTTONS as u8 {
+                    button_effects.start(self.buttons, button, effect.clone())?;
+                }
+            }
+        }
+        self.stack.shown = shown;
+        Ok(())
+    }
+
+    /// Stops every effect and turns the LEDs off.
+    fn clear(&mut self) -> Result<(), Box<dyn Error>> {
+        self.animation.lock().unwrap().stop();
+        self.underlights.lock().unwrap().clear_underlighting()?;
+
+        for button in 0..NUM_BUTTONS as u8 {
+            //The effect scheduler locks the button LEDs itself, so it is stopped before they are locked
+            let mut button_effects = self.button_effects.lock().unwrap();
+            if button_effects.is_running(button) {
+                button_effects.stop(button);
+                drop(button_effects);
+                self.buttons.lock().unwrap().set_button_led(button, 0.0)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(stack: &mut LayerStack, priority: i32) -> u32 {
+        let id = stack.next_layer;
+        stack.next_layer += 1;
+        let effect = Effect::Blink { color: Color::rgb(0, 0, 255), period: Duration::from_secs(1) };
+        stack.layers.insert(id, (LAYER_PRIORITY_BASE + priority, Indicator { underlight: effect, buttons: None }));
+        id
+    }
+
+    fn top(stack: &LayerStack) -> Option<LayerKey> {
+        stack.top().map(|(key, _)| key)
+    }
+
+    #[test]
+    fn top_orders_statuses_and_layers_by_priority() {
+        let mut stack = LayerStack::new();
+        assert_eq!(top(&stack), Some(LayerKey::Status(SystemStatus::Idle)));
+
+        stack.statuses.insert(SystemStatus::Moving);
+        assert_eq!(top(&stack), Some(LayerKey::Status(SystemStatus::Moving)));
+
+        //Application layers sit above normal statuses, and the newest wins a tie
+        let first = layer(&mut stack, 5);
+        assert_eq!(top(&stack), Some(LayerKey::App(first)));
+        let second = layer(&mut stack, 5);
+        assert_eq!(top(&stack), Some(LayerKey::App(second)));
+        layer(&mut stack, 0);
+        assert_eq!(top(&stack), Some(LayerKey::App(second)));
+
+        //Safety statuses sit above every layer, the most serious first
+        let highest = layer(&mut stack, MAX_LAYER_PRIORITY);
+        assert_eq!(top(&stack), Some(LayerKey::App(highest)));
+        stack.statuses.insert(SystemStatus::LowBattery);
+        stack.statuses.insert(SystemStatus::ObstacleWarning);
+        assert_eq!(top(&stack), Some(LayerKey::Status(SystemStatus::ObstacleWarning)));
+        stack.statuses.insert(SystemStatus::Error);
+        assert_eq!(top(&stack), Some(LayerKey::Status(SystemStatus::Error)));
+
+        stack.statuses.clear();
+        stack.layers.clear();
+        assert_eq!(top(&stack), None);
+    }
+
+    #[test]
+    fn status_is_shown_again_after_an_app_write() {
+        let mut stack = LayerStack::new();
+        assert!(stack.take_change().is_some());
+        assert!(stack.take_change().is_none());
+
+        //The application takes the underlights while a normal status is shown
+        assert!(stack.release_underlights());
+        stack.statuses.insert(SystemStatus::Moving);
+        stack.statuses.remove(&SystemStatus::Moving);
+        assert!(stack.take_change().is_some());
+        assert_eq!(stack.shown, Some(LayerKey::Status(SystemStatus::Idle)));
+
+        //A shown safety status keeps the underlights
+        stack.statuses.insert(SystemStatus::ObstacleWarning);
+        assert!(stack.take_change().is_some());
+        assert!(!stack.release_underlights());
+        assert!(stack.take_change().is_none());
+        assert_eq!(stack.shown, Some(LayerKey::Status(SystemStatus::ObstacleWarning)));
+
+        //Once it clears, the application may write again
+        stack.statuses.remove(&SystemStatus::ObstacleWarning);
+        assert!(stack.take_change().is_some());
+        assert!(stack.release_underlights());
+    }
+
+    #[test]
+    fn button_writes_only_yield_to_statuses_on_the_buttons() {
+        let mut stack = LayerStack::new();
+
+        //A safety status without a button effect leaves the buttons to the application
+        stack.statuses.insert(SystemStatus::LowBattery);
+        assert!(stack.take_change().is_some());
+        assert!(stack.release_buttons());
+        assert_eq!(stack.shown, Some(LayerKey::Status(SystemStatus::LowBattery)));
+
+        //The error blink on the buttons is kept
+        stack.statuses.insert(SystemStatus::Error);
+        assert!(stack.take_change().is_some());
+        assert!(!stack.release_buttons());
+        assert_eq!(stack.shown, Some(LayerKey::Status(SystemStatus::Error)));
+
+        //A normal status on the buttons is handed over, and shown again on the next change
+        stack.statuses = BTreeSet::from([SystemStatus::Booting]);
+        assert!(stack.take_change().is_some());
+        assert!(stack.release_buttons());
+        assert_eq!(stack.shown, None);
+        assert!(stack.take_change().is_some());
+    }
+}
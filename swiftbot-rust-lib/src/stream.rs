@@ -0,0 +1,268 @@
+use crate::capture::{Frame, FrameCapture};
+use crate::config::*;
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+//Separates the JPEG images in the multipart stream
+const BOUNDARY: &str = "frame";
+
+//How often the server checks whether it has been stopped while no clients connect
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+//How long a client may take to send its request, or to accept data, before it is dropped
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Minimal HTTP server for watching the camera from a browser. Serves the continuous
+/// capture as a `multipart/x-mixed-replace` MJPEG stream at `/` and `/stream.mjpg`, and
+/// the latest frame at `/snapshot.jpg`. Only MJPG frames can be served.
+pub struct StreamServer {
+    address: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Server {
+    capture: FrameCapture,
+    stopped: Arc<AtomicBool>,
+    clients: AtomicUsize,
+}
+
+impl StreamServer {
+    /// Starts serving frames from `capture` on `address`, e.g. "0.0.0.0:8080". Port 0 picks
+    /// a free port, see `address()`. The capture must be started separately.
+    pub fn start(address: impl ToSocketAddrs, capture: FrameCapture) -> Result<Self, Box<dyn Error>> {
+        let listener = TcpListener::bind(address).map_err(|e| format!("Failed to start the camera stream: {}", e))?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let server = Arc::new(Server {
+            capture,
+            stopped: Arc::clone(&stopped),
+            clients: AtomicUsize::new(0),
+        });
+        let thread = thread::spawn(move || accept(listener, server));
+
+        Ok(StreamServer { address, stopped, thread: Some(thread) })
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Stops accepting connections and ends the open streams after their current frame.
+    pub fn stop(mut self) {
+        self.shut_down();
+    }
+
+    fn shut_down(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for StreamServer {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+fn accept(listener: TcpListener, server: Arc<Server>) {
+    while !server.stopped.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let server = Arc::clone(&server);
+                thread::spawn(move || {
+                    //Errors only mean the client went away
+                    let _ = serve(stream, &server);
+                });
+            }
+            //Nothing to accept yet, or a connection failed before it was accepted
+            Err(_) => thread::sleep(ACCEPT_POLL_INTERVAL),
+        }
+    }
+}
+
+fn serve(stream: TcpStream, server: &Server) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    //Only the request line matters, the headers are read and ignored
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut stream = &stream;
+    let mut parts = request.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = path.split('?').next().unwrap_or("");
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"Only GET is supported.\n");
+    }
+
+    match path {
+        "/" | "/stream.mjpg" => {
+            if server.clients.fetch_add(1, Ordering::SeqCst) >= CAMERA_STREAM_MAX_CLIENTS {
+                server.clients.fetch_sub(1, Ordering::SeqCst);
+                return respond(&mut stream, "503 Service Unavailable", "text/plain", b"Too many viewers.\n");
+            }
+            let result = stream_frames(&mut stream, server);
+            server.clients.fetch_sub(1, Ordering::SeqCst);
+            result
+        }
+        "/snapshot.jpg" => match server.capture.wait_for_frame(None, CAMERA_FRAME_TIMEOUT) {
+            Some(frame) if is_jpeg(&frame) => respond(&mut stream, "200 OK", "image/jpeg", &frame.data),
+            Some(_) => respond(&mut stream, "415 Unsupported Media Type", "text/plain", b"The camera is not capturing MJPG.\n"),
+            None => respond(&mut stream, "503 Service Unavailable", "text/plain", b"No frame available.\n"),
+        },
+        _ => respond(&mut stream, "404 Not Found", "text/plain", b"Not found.\n"),
+    }
+}
+
+fn stream_frames(stream: &mut impl Write, server: &Server) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: multipart/x-mixed-replace; boundary={}\r\n\
+         Cache-Control: no-cache\r\n\
+         Connection: close\r\n\r\n",
+        BOUNDARY,
+    )?;
+
+    let mut last = None;
+    while !server.stopped.load(Ordering::SeqCst) {
+        let frame = match server.capture.wait_for_frame(last, CAMERA_FRAME_TIMEOUT) {
+            Some(frame) => frame,
+            None if server.capture.is_running() => continue,
+            None => return Ok(()),
+        };
+        last = Some(frame.sequence);
+        if !is_jpeg(&frame) {
+            continue;
+        }
+
+        write!(
+            stream,
+            "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\nX-Timestamp-Us: {}\r\n\r\n",
+            BOUNDARY,
+            frame.data.len(),
+            frame.timestamp_us,
+        )?;
+        stream.write_all(&frame.data)?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()?;
+    }
+    Ok(())
+}
+
+fn respond(stream: &mut impl Write, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len(),
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+fn is_jpeg(frame: &Frame) -> bool {
+    matches!(&frame.format, b"MJPG" | b"JPEG")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::fake::FakeCamera;
+    use std::io::Read;
+
+    fn start() -> (FrameCapture, StreamServer) {
+        let capture = FrameCapture::new(2);
+        let images = vec![b"\xFF\xD8one\xFF\xD9".to_vec(), b"\xFF\xD8two\xFF\xD9".to_vec()];
+        capture.start(Arc::new(FakeCamera::new(images, Duration::from_millis(5))));
+        let server = StreamServer::start("127.0.0.1:0", capture.clone()).unwrap();
+        (capture, server)
+    }
+
+    fn get(server: &StreamServer, path: &str) -> TcpStream {
+        let mut client = TcpStream::connect(server.address()).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        write!(client, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        client
+    }
+
+    fn read_headers(reader: &mut impl BufRead) -> Vec<String> {
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim_end().is_empty() {
+                return headers;
+            }
+            headers.push(line.trim_end().to_string());
+        }
+    }
+
+    #[test]
+    fn snapshot_returns_the_latest_frame() {
+        let (capture, server) = start();
+
+        let mut reader = BufReader::new(get(&server, "/snapshot.jpg"));
+        let headers = read_headers(&mut reader);
+        assert_eq!(headers[0], "HTTP/1.1 200 OK");
+        assert!(headers.contains(&"Content-Type: image/jpeg".to_string()));
+        let mut image = Vec::new();
+        reader.read_to_end(&mut image).unwrap();
+        assert!(image == b"\xFF\xD8one\xFF\xD9" || image == b"\xFF\xD8two\xFF\xD9");
+
+        let mut response = String::new();
+        get(&server, "/missing").read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        server.stop();
+        capture.stop();
+    }
+
+    #[test]
+    fn stream_sends_consecutive_jpeg_parts() {
+        let (capture, server) = start();
+
+        let mut reader = BufReader::new(get(&server, "/stream.mjpg"));
+        let headers = read_headers(&mut reader);
+        assert_eq!(headers[0], "HTTP/1.1 200 OK");
+        assert!(headers.contains(&format!("Content-Type: multipart/x-mixed-replace; boundary={}", BOUNDARY)));
+
+        let mut timestamps = Vec::new();
+        for _ in 0..3 {
+            let headers = read_headers(&mut reader);
+            assert_eq!(headers[0], format!("--{}", BOUNDARY));
+            let value = |name: &str| -> u64 {
+                headers.iter().find_map(|header| header.strip_prefix(name)).unwrap().parse().unwrap()
+            };
+            assert!(headers.contains(&"Content-Type: image/jpeg".to_string()));
+            let mut image = vec![0; value("Content-Length: ") as usize + 2];
+            reader.read_exact(&mut image).unwrap();
+            assert!(image.starts_with(b"\xFF\xD8") && image.ends_with(b"\xFF\xD9\r\n"));
+            timestamps.push(value("X-Timestamp-Us: "));
+        }
+        //Each frame is sent once
+        assert!(timestamps.windows(2).all(|pair| pair[1] > pair[0]));
+
+        server.stop();
+        capture.stop();
+    }
+}